@@ -0,0 +1,173 @@
+/// Provides steering for computer controlled snakes
+use std::collections::VecDeque;
+
+use crate::{
+    collision,
+    snake::Snake,
+    types::{Cell, Direction, Position, FOOD_COLOR},
+};
+
+/// Every direction of travel, in order of preference when scores tie
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+
+/// Whether a snake heading in `direction` may safely move into a cell
+pub fn is_safe(cell: Cell, direction: Direction) -> bool {
+    cell == FOOD_COLOR || collision::is_passable(cell, direction)
+}
+
+/// Choose a direction that greedily approaches the nearest target without colliding
+///
+/// Keeps the current `direction` when no move is safe or it is as good as any other.
+pub fn seek(
+    grid: &[Vec<Cell>],
+    snake: &Snake,
+    direction: Direction,
+    targets: &[Position],
+) -> Direction {
+    let (height, width) = (grid.len() as u32, grid[0].len() as u32);
+    let score = |d: &Direction| {
+        let (next, heading) = snake.next_move(d, height as i32, width as i32);
+        if !is_safe(grid[next.0 as usize][next.1 as usize], heading) {
+            return None;
+        }
+        targets
+            .iter()
+            .map(|target| snake.topology.distance(&next, target, height, width))
+            .min()
+            .or(Some(0))
+    };
+
+    let mut best = (score(&direction), direction);
+    for d in DIRECTIONS.iter().filter(|d| **d != direction) {
+        let candidate = score(d);
+        match (candidate, best.0) {
+            (Some(c), Some(b)) if c < b => best = (candidate, *d),
+            (Some(_), None) => best = (candidate, *d),
+            _ => (),
+        }
+    }
+
+    best.1
+}
+
+/// Find the first step of a shortest path from `snake`'s head to `target`
+///
+/// Searches at most `max_depth` steps out. The path only crosses safe cells, apart from `target`
+/// itself, and follows the same edge rules as the snake.
+pub fn path_toward(
+    grid: &[Vec<Cell>],
+    snake: &Snake,
+    target: Position,
+    max_depth: u32,
+) -> Option<Direction> {
+    let (height, width) = (grid.len() as i32, grid[0].len() as i32);
+    let start = *snake.position();
+    let mut visited = vec![vec![false; width as usize]; height as usize];
+    visited[start.0 as usize][start.1 as usize] = true;
+
+    let mut queue = VecDeque::new();
+    queue.push_back((start, None, 0));
+    while let Some((position, first_step, depth)) = queue.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        for d in DIRECTIONS.iter() {
+            let (next, heading) = snake.step(position, d, height, width);
+            // the snake can't turn back on itself
+            if first_step.is_none() && snake.body.get(1) == Some(&next) {
+                continue;
+            }
+            let first_step = first_step.or(Some(*d));
+            if next == target {
+                return first_step;
+            }
+
+            let (row, col) = (next.0 as usize, next.1 as usize);
+            if !visited[row][col] && is_safe(grid[row][col], heading) {
+                visited[row][col] = true;
+                queue.push_back((next, first_step, depth + 1));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Topology, BG_COLOR, WALL_COLOR};
+
+    #[test]
+    fn seeks_across_topology() {
+        let snake = Snake::new(2, 0, None);
+        let grid = snake.render(vec![vec![BG_COLOR; 5]; 5]);
+        assert_eq!(
+            seek(&grid, &snake, Direction::Left, &[(2, 4)]),
+            Direction::Left
+        );
+
+        // a bounded board has no way round
+        let snake = snake.with_topology(Topology::Bounded);
+        assert_eq!(
+            seek(&grid, &snake, Direction::Left, &[(2, 4)]),
+            Direction::Right
+        );
+    }
+
+    #[test]
+    fn seeks_food() {
+        let grid = vec![vec![BG_COLOR; 5]; 5];
+        let snake = Snake::new(2, 2, None);
+        assert_eq!(
+            seek(&grid, &snake, Direction::Up, &[(2, 4)]),
+            Direction::Right
+        );
+    }
+
+    #[test]
+    fn avoids_walls() {
+        let mut grid = vec![vec![BG_COLOR; 5]; 5];
+        grid[1][2] = WALL_COLOR;
+        let snake = Snake::new(2, 2, None);
+        let actual = seek(&grid, &snake, Direction::Up, &[(0, 2)]);
+        assert_ne!(actual, Direction::Up);
+    }
+
+    #[test]
+    fn paths_around_walls() {
+        // wall directly between the snake and its target
+        let mut grid = vec![vec![BG_COLOR; 5]; 5];
+        for cell in grid[2].iter_mut().take(4) {
+            *cell = WALL_COLOR;
+        }
+        let snake = Snake::new(3, 0, None);
+        let actual = path_toward(&grid, &snake, (1, 0), 20);
+        // wrapping off the bottom edge is the shorter way round
+        assert_eq!(actual, Some(Direction::Down));
+    }
+
+    #[test]
+    fn path_limited_by_depth() {
+        let grid = vec![vec![BG_COLOR; 9]];
+        let snake = Snake::new(0, 0, None);
+        assert_eq!(path_toward(&grid, &snake, (0, 4), 3), None);
+        assert_eq!(
+            path_toward(&grid, &snake, (0, 3), 3),
+            Some(Direction::Right)
+        );
+    }
+
+    #[test]
+    fn no_path_when_enclosed() {
+        let mut grid = vec![vec![WALL_COLOR; 3]; 3];
+        grid[1][1] = BG_COLOR;
+        let snake = Snake::new(1, 1, None);
+        assert_eq!(path_toward(&grid, &snake, (0, 0), 10), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Command-line toolkit for map files, built on the game's own map loading
+
+use std::{env, path::Path, process};
+
+use rs_snake::{
+    map::{self, MapError, Palette, Transform},
+    types::{Tile, Topology, WorldMap},
+};
+
+const USAGE: &str = "Usage:
+  snek-map validate [--size <rows>x<cols>] [--topology <name>] <map file>...
+  snek-map convert <map file> <output file>
+  snek-map render <map file> [<output.png> [<pixels per cell>]]
+  snek-map transform <map file> <output file> <transform>...
+  snek-map stats <map file>...
+
+Maps are read from .csv, .txt, .snek, .png, .tmx and .tmj files and written as .csv, .txt,
+.snek or .png, chosen by extension. Transforms are rotate-cw, rotate-ccw, rotate-180, flip-h,
+flip-v and transpose, applied in order. Maps are validated on a board of their own size, with
+its edges joined as a torus, unless told otherwise. Topologies are torus, bounded, klein,
+projective and sphere.";
+
+/// Pixels per cell of a rendered image, unless told otherwise
+const RENDER_SCALE: u32 = 8;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => usage(),
+    };
+
+    let result = match command {
+        "validate" => validate(args),
+        "convert" => convert(args),
+        "render" => render(args),
+        "transform" => transform(args),
+        "stats" => stats(args),
+        _ => usage(),
+    };
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Print usage and quit
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+/// Load a map, along with the board size it was drawn for
+fn load(path: &Path) -> Result<(WorldMap, (u32, u32)), MapError> {
+    let world_map = map::load_file(path)?;
+    let size = world_map.size.ok_or_else(|| MapError::Invalid {
+        path: path.to_path_buf(),
+        reason: "map doesn't say what size it is".to_string(),
+    })?;
+    Ok((world_map, size))
+}
+
+/// Parse a board size written as eg "36x36"
+fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.split('x').map(str::parse);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(rows)), Some(Ok(cols)), None) => Some((rows, cols)),
+        _ => None,
+    }
+}
+
+/// Check each map on a board of the given size, else its own, with edges joined as the given
+/// topology, else a torus. Returns whether all passed
+fn validate(args: &[String]) -> Result<bool, MapError> {
+    let (mut board, mut topology, mut paths) = (None, Topology::Torus, args);
+    loop {
+        match paths {
+            [flag, size, rest @ ..] if flag == "--size" => {
+                board = Some(parse_size(size).unwrap_or_else(|| usage()));
+                paths = rest;
+            }
+            [flag, name, rest @ ..] if flag == "--topology" => {
+                topology = Topology::from_name(name).unwrap_or_else(|| {
+                    eprintln!("Unknown topology '{}'", name);
+                    usage()
+                });
+                paths = rest;
+            }
+            _ => break,
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut ok = true;
+    for path in paths.iter().map(Path::new) {
+        let loaded = map::load_file(path);
+        let size = loaded
+            .as_ref()
+            .ok()
+            .and_then(|world_map| board.or(world_map.size));
+        let problems = match (loaded, size) {
+            (Err(e), _) => vec![e.to_string()],
+            (Ok(_), None) => vec![],
+            (Ok(world_map), Some((rows, cols))) => map::validate(&world_map, rows, cols, topology)
+                .iter()
+                .map(|problem| format!("{}: {}", path.display(), problem))
+                .collect(),
+        };
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for problem in problems.iter() {
+            ok = false;
+            println!("{}", problem);
+        }
+    }
+    Ok(ok)
+}
+
+fn convert(args: &[String]) -> Result<bool, MapError> {
+    let (input, output) = match args {
+        [input, output] => (Path::new(input), Path::new(output)),
+        _ => usage(),
+    };
+    let (world_map, (rows, cols)) = load(input)?;
+    map::save_file(output, &world_map, rows, cols)?;
+    Ok(true)
+}
+
+/// Draw a map as a PNG image, or in the terminal when no image is named
+fn render(args: &[String]) -> Result<bool, MapError> {
+    let (input, output, scale) = match args {
+        [input] => (input, None, RENDER_SCALE),
+        [input, output] => (input, Some(output), RENDER_SCALE),
+        [input, output, scale] => (
+            input,
+            Some(output),
+            scale
+                .parse()
+                .ok()
+                .filter(|s| *s > 0)
+                .unwrap_or_else(|| usage()),
+        ),
+        _ => usage(),
+    };
+    let (world_map, (rows, cols)) = load(Path::new(input))?;
+
+    match output {
+        Some(output) => {
+            let bytes = map::to_png(&world_map, rows, cols, &Palette::default(), scale);
+            std::fs::write(output, bytes).map_err(|source| MapError::Io {
+                path: output.into(),
+                source,
+            })?;
+        }
+        // two characters per cell keep the preview roughly square
+        None => {
+            for line in map::to_ascii(&world_map, rows, cols).lines() {
+                let cells: String = line
+                    .chars()
+                    .map(|symbol| match symbol {
+                        '#' => "██".to_string(),
+                        'o' => "▒▒".to_string(),
+                        '.' => "  ".to_string(),
+                        '~' => "░░".to_string(),
+                        // spawns, food, numbered portal ends, conveyors and gates
+                        'P' | 'E' | 'F' | '0'..='9' => format!("{} ", symbol),
+                        _ if Tile::from_symbol(symbol).is_some() => format!("{} ", symbol),
+                        _ => "??".to_string(),
+                    })
+                    .collect();
+                println!("{}", cells.trim_end());
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn transform(args: &[String]) -> Result<bool, MapError> {
+    let (input, output, names) = match args {
+        [input, output, names @ ..] if !names.is_empty() => (input, output, names),
+        _ => usage(),
+    };
+    let transforms: Vec<Transform> = names
+        .iter()
+        .map(|name| {
+            Transform::from_name(name).unwrap_or_else(|| {
+                eprintln!("Unknown transform '{}'", name);
+                usage()
+            })
+        })
+        .collect();
+
+    let (mut world_map, mut size) = load(Path::new(input))?;
+    for t in transforms {
+        let (transformed, transformed_size) = map::transform(&world_map, size.0, size.1, t);
+        world_map = transformed;
+        size = transformed_size;
+    }
+    map::save_file(Path::new(output), &world_map, size.0, size.1)?;
+    Ok(true)
+}
+
+fn stats(args: &[String]) -> Result<bool, MapError> {
+    if args.is_empty() {
+        usage();
+    }
+    for (i, path) in args.iter().map(Path::new).enumerate() {
+        let (world_map, (rows, cols)) = load(path)?;
+        if i > 0 {
+            println!();
+        }
+        println!("{}", path.display());
+        println!("{}", map::stats(&world_map, rows, cols));
+    }
+    Ok(true)
+}
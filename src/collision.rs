@@ -1,24 +1,66 @@
 /// Provides collision detection functions
 use crate::{
     snake::Snake,
-    types::{Cell, Direction, SnakeEvent, BG_COLOR, FOOD_COLOR},
+    types::{Cell, Direction, SnakeEvent, Tile, DRILL_COLOR, FOOD_COLOR, KEY_COLORS, SWITCH_COLOR},
 };
 
+/// Whether a snake heading in `heading` may move into a cell without harm, food aside
+///
+/// Open ground, terrain that admits the snake, keys, switches and drills are all passable.
+pub fn is_passable(cell: Cell, heading: Direction) -> bool {
+    KEY_COLORS.contains(&cell)
+        || cell == SWITCH_COLOR
+        || cell == DRILL_COLOR
+        || Tile::from_color(cell).is_some_and(|tile| tile.admits(heading))
+}
+
 /// Check for collisions in the player's *next* position and return appropriate `SnakeEvent`
+///
+/// Running into a wall a drill could break is a `Ram` rather than a `Death`, left for the game to
+/// settle. Going nowhere means running off the edge of a bounded board, which is a `Death`.
 pub fn collision_check(
     grid: &[Vec<Cell>],
     player: &Snake,
     direction: &Direction,
 ) -> Option<SnakeEvent> {
-    let (row, col) = player.next_position(direction, grid.len() as i32, grid[0].len() as i32);
+    let ((row, col), heading) =
+        player.next_move(direction, grid.len() as i32, grid[0].len() as i32);
+    if (row, col) == *player.position() {
+        return Some(SnakeEvent::Death);
+    }
 
     match grid[row as usize][col as usize] {
-        _x if _x == BG_COLOR => None,
         _x if _x == FOOD_COLOR => Some(SnakeEvent::Food),
+        _x if is_passable(_x, heading) => None,
+        _x if Tile::from_color(_x).is_some_and(|tile| tile.is_destructible()) => {
+            Some(SnakeEvent::Ram)
+        }
         _ => Some(SnakeEvent::Death),
     }
 }
 
+/// Check every snake's *next* position at once and return each snake's `SnakeEvent`
+///
+/// Snakes moving into the same cell collide head-on, and all of them die.
+pub fn resolve_moves(grid: &[Vec<Cell>], moves: &[(&Snake, Direction)]) -> Vec<Option<SnakeEvent>> {
+    let (height, width) = (grid.len() as i32, grid[0].len() as i32);
+    let targets: Vec<_> = moves
+        .iter()
+        .map(|(snake, direction)| snake.next_position(direction, height, width))
+        .collect();
+
+    moves
+        .iter()
+        .zip(targets.iter())
+        .map(|((snake, direction), target)| {
+            match targets.iter().filter(|other| *other == target).count() {
+                1 => collision_check(grid, snake, direction),
+                _ => Some(SnakeEvent::Death),
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -28,15 +70,26 @@ mod tests {
     fn collision_food() {
         let grid = vec![vec![Cell::RGB(0, 0, 0), FOOD_COLOR]];
         let expected = Some(SnakeEvent::Food);
-        let actual = collision_check(&grid, &Snake::new(0, 0, None, None), &Direction::Right);
+        let actual = collision_check(&grid, &Snake::new(0, 0, None), &Direction::Right);
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn collision_bounded_edge() {
+        let grid = vec![vec![types::BG_COLOR; 3]; 3];
+        let snake = Snake::new(0, 1, None).with_topology(types::Topology::Bounded);
+        assert_eq!(
+            collision_check(&grid, &snake, &Direction::Up),
+            Some(SnakeEvent::Death)
+        );
+        assert_eq!(collision_check(&grid, &snake, &Direction::Down), None);
+    }
+
     #[test]
     fn collision_death() {
         let grid = vec![vec![Cell::RGB(0, 0, 0), types::SNAKE_COLOR]];
         let expected = Some(SnakeEvent::Death);
-        let actual = collision_check(&grid, &Snake::new(0, 0, None, None), &Direction::Left);
+        let actual = collision_check(&grid, &Snake::new(0, 0, None), &Direction::Left);
         assert_eq!(actual, expected);
     }
 
@@ -44,7 +97,54 @@ mod tests {
     fn collision_none() {
         let grid = vec![vec![types::BG_COLOR, types::BG_COLOR]];
         let expected = None;
-        let actual = collision_check(&grid, &Snake::new(0, 0, None, None), &Direction::Left);
+        let actual = collision_check(&grid, &Snake::new(0, 0, None), &Direction::Left);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn collision_gate() {
+        let gate = Tile::Gate(Direction::Right).color();
+        let grid = vec![vec![types::BG_COLOR, gate, types::BG_COLOR]];
+        let snake = Snake::new(0, 0, None);
+        assert_eq!(collision_check(&grid, &snake, &Direction::Right), None);
+        let snake = Snake::new(0, 2, None);
+        let expected = Some(SnakeEvent::Death);
+        assert_eq!(collision_check(&grid, &snake, &Direction::Left), expected);
+    }
+
+    #[test]
+    fn collision_puzzle() {
+        let door = types::KeyColor::Red.door_cell();
+        let grid = vec![vec![types::KEY_COLORS[0], types::BG_COLOR, door]];
+        let snake = Snake::new(0, 1, None);
+        assert_eq!(collision_check(&grid, &snake, &Direction::Left), None);
+        let expected = Some(SnakeEvent::Death);
+        assert_eq!(collision_check(&grid, &snake, &Direction::Right), expected);
+    }
+
+    #[test]
+    fn collision_brick() {
+        let grid = vec![vec![types::BG_COLOR, Tile::Brick(2).color()]];
+        let snake = Snake::new(0, 0, None);
+        let expected = Some(SnakeEvent::Ram);
+        assert_eq!(collision_check(&grid, &snake, &Direction::Right), expected);
+    }
+
+    #[test]
+    fn simultaneous_head_on() {
+        let grid = vec![vec![types::BG_COLOR; 3]];
+        let (a, b) = (Snake::new(0, 0, None), Snake::new(0, 2, None));
+        let expected = vec![Some(SnakeEvent::Death), Some(SnakeEvent::Death)];
+        let actual = resolve_moves(&grid, &[(&a, Direction::Right), (&b, Direction::Left)]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn simultaneous_independent() {
+        let grid = vec![vec![types::BG_COLOR, FOOD_COLOR], vec![types::BG_COLOR; 2]];
+        let (a, b) = (Snake::new(0, 0, None), Snake::new(1, 0, None));
+        let expected = vec![Some(SnakeEvent::Food), None];
+        let actual = resolve_moves(&grid, &[(&a, Direction::Right), (&b, Direction::Right)]);
         assert_eq!(actual, expected);
     }
 }
@@ -0,0 +1,461 @@
+use std::collections::VecDeque;
+
+use crate::{
+    map,
+    types::{self, Direction, Grid, Position, Spawn, WorldMap},
+};
+
+/// What the editor paints onto a cell
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Brush {
+    Wall,
+    Floor,
+    PlayerSpawn,
+    EnemySpawn,
+}
+
+impl Brush {
+    /// Returns a display name for this `Brush`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Wall => "Wall",
+            Self::Floor => "Floor",
+            Self::PlayerSpawn => "Player Spawn",
+            Self::EnemySpawn => "Enemy Spawn",
+        }
+    }
+}
+
+/// How the editor applies its `Brush`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tool {
+    /// Paint single cells
+    Pencil,
+    /// Paint a straight line between two chosen cells
+    Line,
+    /// Paint the whole open area or wall around a cell
+    Fill,
+}
+
+impl Tool {
+    /// Returns a display name for this `Tool`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Pencil => "Pencil",
+            Self::Line => "Line",
+            Self::Fill => "Fill",
+        }
+    }
+}
+
+/// Symmetry applied to everything the editor paints
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mirror {
+    Off,
+    /// Mirror left to right
+    Horizontal,
+    /// Mirror top to bottom
+    Vertical,
+    /// Mirror into all four quarters
+    Both,
+}
+
+impl Mirror {
+    /// Returns a display name for this `Mirror`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Off => "Off",
+            Self::Horizontal => "Horizontal",
+            Self::Vertical => "Vertical",
+            Self::Both => "Both",
+        }
+    }
+
+    /// Returns the following symmetry, wrapping back to `Off`
+    pub fn next(&self) -> Self {
+        match *self {
+            Self::Off => Self::Horizontal,
+            Self::Horizontal => Self::Vertical,
+            Self::Vertical => Self::Both,
+            Self::Both => Self::Off,
+        }
+    }
+}
+
+/// Everything the editor can undo
+#[derive(Debug, PartialEq, Clone)]
+struct Layout {
+    walls: Vec<Vec<bool>>,
+    player_spawns: Vec<Position>,
+    enemy_spawns: Vec<Position>,
+}
+
+/// A map being drawn in the editor
+#[derive(Debug)]
+pub struct Editor {
+    rows: u32,
+    cols: u32,
+    layout: Layout,
+    undo: Vec<Layout>,
+    redo: Vec<Layout>,
+    /// First end of a line being drawn with the `Line` tool
+    line_start: Option<Position>,
+    pub brush: Brush,
+    pub tool: Tool,
+    pub mirror: Mirror,
+    /// Cell the keyboard acts on
+    pub cursor: Position,
+}
+
+impl Editor {
+    /// Create an `Editor` holding an empty `rows` x `cols` map
+    pub fn new(rows: u32, cols: u32) -> Self {
+        Editor {
+            rows,
+            cols,
+            layout: Layout {
+                walls: vec![vec![false; cols as usize]; rows as usize],
+                player_spawns: vec![],
+                enemy_spawns: vec![],
+            },
+            undo: vec![],
+            redo: vec![],
+            line_start: None,
+            brush: Brush::Wall,
+            tool: Tool::Pencil,
+            mirror: Mirror::Off,
+            cursor: (rows / 2, cols / 2),
+        }
+    }
+
+    /// Choose a different `Tool`, abandoning any line in progress
+    pub fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        self.line_start = None;
+    }
+
+    /// Move the cursor, wrapping around the edges of the map
+    pub fn move_cursor(&mut self, direction: Direction) {
+        let (row, col) = self.cursor;
+        let (rows, cols) = (self.rows, self.cols);
+        self.cursor = match direction {
+            Direction::Up => ((row + rows - 1) % rows, col),
+            Direction::Down => ((row + 1) % rows, col),
+            Direction::Left => (row, (col + cols - 1) % cols),
+            Direction::Right => (row, (col + 1) % cols),
+        };
+    }
+
+    /// Use the current `Tool` on a cell, as one undoable step
+    ///
+    /// The `Line` tool needs two calls, one for each end of the line.
+    pub fn apply(&mut self, position: Position) {
+        if !self.contains(position) {
+            return;
+        }
+
+        match self.tool {
+            Tool::Pencil => {
+                self.checkpoint();
+                self.paint_mirrored(position);
+            }
+            Tool::Line => match self.line_start.take() {
+                None => self.line_start = Some(position),
+                Some(start) => {
+                    self.checkpoint();
+                    for point in line(start, position) {
+                        self.paint_mirrored(point);
+                    }
+                }
+            },
+            Tool::Fill => {
+                self.checkpoint();
+                for start in self.mirrored(position) {
+                    self.fill(start);
+                }
+            }
+        }
+    }
+
+    /// Continue a pencil stroke onto another cell, as part of the last undoable step
+    pub fn drag(&mut self, position: Position) {
+        if self.tool == Tool::Pencil && self.contains(position) {
+            self.paint_mirrored(position);
+        }
+    }
+
+    /// Step back one edit. Returns false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(layout) => {
+                self.redo.push(std::mem::replace(&mut self.layout, layout));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Step forward one undone edit. Returns false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(layout) => {
+                self.undo.push(std::mem::replace(&mut self.layout, layout));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the map drawn so far
+    pub fn world_map(&self) -> WorldMap {
+        let mut walls = vec![];
+        for (row, cells) in (0..).zip(self.layout.walls.iter()) {
+            for (col, wall) in (0..).zip(cells.iter()) {
+                if *wall {
+                    walls.push((row, col));
+                }
+            }
+        }
+        let spawns = |positions: &[Position]| -> Vec<Spawn> {
+            positions
+                .iter()
+                .map(|&position| Spawn {
+                    position,
+                    direction: map::facing(position.0, self.rows),
+                })
+                .collect()
+        };
+
+        WorldMap {
+            size: Some((self.rows, self.cols)),
+            player_spawns: spawns(&self.layout.player_spawns),
+            enemy_spawns: spawns(&self.layout.enemy_spawns),
+            ..WorldMap::new(walls, types::WALL_COLOR)
+        }
+    }
+
+    /// Returns the map drawn so far in the CSV format
+    pub fn to_csv(&self) -> String {
+        map::to_csv(&self.world_map(), self.rows, self.cols)
+    }
+
+    /// Draw the map, spawns and cursor for display
+    pub fn grid(&self) -> Grid {
+        let mut grid = self.world_map().render(self.rows, self.cols);
+        let mut draw = |(row, col): Position, cell| grid[row as usize][col as usize] = cell;
+        for spawn in self.layout.player_spawns.iter() {
+            draw(*spawn, types::SNAKE_COLOR);
+        }
+        for spawn in self.layout.enemy_spawns.iter() {
+            draw(*spawn, types::EVIL_COLOR);
+        }
+        if let Some(start) = self.line_start {
+            draw(start, types::TEXT_COLOR);
+        }
+        draw(self.cursor, types::FOOD_COLOR);
+
+        grid
+    }
+
+    fn contains(&self, (row, col): Position) -> bool {
+        row < self.rows && col < self.cols
+    }
+
+    /// Remember the current layout so the next edit can be undone
+    fn checkpoint(&mut self) {
+        self.undo.push(self.layout.clone());
+        self.redo.clear();
+    }
+
+    /// Returns a position and its reflections under the current `Mirror`
+    fn mirrored(&self, (row, col): Position) -> Vec<Position> {
+        let flip_col = (row, self.cols - 1 - col);
+        let flip_row = (self.rows - 1 - row, col);
+        let mut positions = match self.mirror {
+            Mirror::Off => vec![(row, col)],
+            Mirror::Horizontal => vec![(row, col), flip_col],
+            Mirror::Vertical => vec![(row, col), flip_row],
+            Mirror::Both => vec![
+                (row, col),
+                flip_col,
+                flip_row,
+                (self.rows - 1 - row, self.cols - 1 - col),
+            ],
+        };
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+
+    fn paint_mirrored(&mut self, position: Position) {
+        for position in self.mirrored(position) {
+            self.paint(position);
+        }
+    }
+
+    /// Paint the current `Brush` onto a single cell
+    fn paint(&mut self, position: Position) {
+        let layout = &mut self.layout;
+        layout.player_spawns.retain(|spawn| *spawn != position);
+        layout.enemy_spawns.retain(|spawn| *spawn != position);
+        layout.walls[position.0 as usize][position.1 as usize] = self.brush == Brush::Wall;
+        match self.brush {
+            Brush::PlayerSpawn => layout.player_spawns.push(position),
+            Brush::EnemySpawn => layout.enemy_spawns.push(position),
+            _ => (),
+        }
+    }
+
+    /// Paint every cell connected to `start` that is walled the same way it is
+    fn fill(&mut self, start: Position) {
+        if matches!(self.brush, Brush::PlayerSpawn | Brush::EnemySpawn) {
+            return self.paint(start);
+        }
+
+        let is_wall =
+            |layout: &Layout, (row, col): Position| layout.walls[row as usize][col as usize];
+        let target = is_wall(&self.layout, start);
+        if target == (self.brush == Brush::Wall) {
+            return;
+        }
+
+        let mut queue = VecDeque::from(vec![start]);
+        while let Some(position) = queue.pop_front() {
+            if is_wall(&self.layout, position) != target {
+                continue;
+            }
+            self.paint(position);
+
+            let (row, col) = position;
+            let neighbors = [
+                (row.wrapping_sub(1), col),
+                (row + 1, col),
+                (row, col.wrapping_sub(1)),
+                (row, col + 1),
+            ];
+            for neighbor in neighbors.iter().filter(|p| self.contains(**p)) {
+                queue.push_back(*neighbor);
+            }
+        }
+    }
+}
+
+/// Returns the cells on a straight line between two positions, both ends included
+fn line((row0, col0): Position, (row1, col1): Position) -> Vec<Position> {
+    let (mut row, mut col) = (row0 as i32, col0 as i32);
+    let (row1, col1) = (row1 as i32, col1 as i32);
+    let d_row = -(row1 - row).abs();
+    let d_col = (col1 - col).abs();
+    let step_row = if row < row1 { 1 } else { -1 };
+    let step_col = if col < col1 { 1 } else { -1 };
+    let mut error = d_col + d_row;
+
+    let mut points = vec![];
+    loop {
+        points.push((row as u32, col as u32));
+        if (row, col) == (row1, col1) {
+            break points;
+        }
+        let e2 = 2 * error;
+        if e2 >= d_row {
+            error += d_row;
+            col += step_col;
+        }
+        if e2 <= d_col {
+            error += d_col;
+            row += step_row;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paints_and_undoes() {
+        let mut editor = Editor::new(5, 5);
+        editor.apply((1, 1));
+        editor.drag((1, 2));
+        editor.apply((3, 3));
+        assert_eq!(editor.world_map().walls, vec![(1, 1), (1, 2), (3, 3)]);
+
+        assert!(editor.undo());
+        assert_eq!(editor.world_map().walls, vec![(1, 1), (1, 2)]);
+        assert!(editor.undo());
+        assert!(!editor.undo());
+        assert!(editor.world_map().walls.is_empty());
+
+        assert!(editor.redo());
+        assert_eq!(editor.world_map().walls, vec![(1, 1), (1, 2)]);
+        editor.apply((0, 0));
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn draws_lines() {
+        let mut editor = Editor::new(5, 5);
+        editor.set_tool(Tool::Line);
+        editor.apply((0, 0));
+        assert!(editor.world_map().walls.is_empty());
+        editor.apply((2, 4));
+        assert_eq!(
+            editor.world_map().walls,
+            vec![(0, 0), (1, 1), (1, 2), (2, 3), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn fills_areas() {
+        let mut editor = Editor::new(4, 4);
+        editor.set_tool(Tool::Line);
+        editor.apply((0, 2));
+        editor.apply((3, 2));
+        editor.set_tool(Tool::Fill);
+        editor.apply((0, 3));
+        let walls = editor.world_map().walls;
+        assert_eq!(walls.len(), 8);
+        assert!(!walls.contains(&(0, 0)));
+
+        editor.brush = Brush::Floor;
+        editor.apply((0, 3));
+        assert!(editor.world_map().walls.is_empty());
+    }
+
+    #[test]
+    fn mirrors_painting() {
+        let mut editor = Editor::new(4, 6);
+        editor.mirror = Mirror::Both;
+        editor.apply((0, 1));
+        assert_eq!(
+            editor.world_map().walls,
+            vec![(0, 1), (0, 4), (3, 1), (3, 4)]
+        );
+    }
+
+    #[test]
+    fn places_spawns() {
+        let mut editor = Editor::new(6, 6);
+        editor.apply((1, 1));
+        editor.brush = Brush::PlayerSpawn;
+        editor.apply((1, 1));
+        editor.brush = Brush::EnemySpawn;
+        editor.apply((4, 4));
+
+        let world_map = editor.world_map();
+        assert!(world_map.walls.is_empty());
+        assert_eq!(world_map.player_spawns[0].position, (1, 1));
+        assert_eq!(world_map.player_spawns[0].direction, Direction::Down);
+        assert_eq!(world_map.enemy_spawns[0].position, (4, 4));
+        assert!(editor.to_csv().starts_with("0,0,0,0,0,0\n0,2,0"));
+    }
+
+    #[test]
+    fn cursor_wraps() {
+        let mut editor = Editor::new(4, 6);
+        editor.cursor = (0, 0);
+        editor.move_cursor(Direction::Up);
+        editor.move_cursor(Direction::Left);
+        assert_eq!(editor.cursor, (3, 5));
+    }
+}
@@ -0,0 +1,228 @@
+use rand::Rng;
+
+use crate::{
+    ai,
+    snake::Snake,
+    types::{Behavior, Cell, Difficulty, Direction, Position},
+};
+
+/// A computer controlled snake that menaces the player
+#[derive(Debug)]
+pub struct Enemy {
+    /// The enemy's avatar
+    pub snake: Snake,
+
+    /// The enemy's direction of travel
+    pub direction: Direction,
+
+    /// How the enemy moves about the board
+    pub behavior: Behavior,
+
+    /// How capable the enemy is
+    pub difficulty: Difficulty,
+
+    /// Steps taken along the current side of a patroller's beat
+    patrol_steps: u32,
+}
+
+/// What an enemy can see of the world when picking its next move
+pub struct Surroundings<'a> {
+    pub grid: &'a [Vec<Cell>],
+    /// Head of the player avatar
+    pub player: Position,
+    /// The player's direction of travel
+    pub player_direction: Direction,
+    /// Location of the food
+    pub food: Position,
+}
+
+impl Enemy {
+    /// Create a new `Enemy`
+    pub fn new(snake: Snake, behavior: Behavior, difficulty: Difficulty) -> Self {
+        Enemy {
+            snake,
+            direction: Direction::Up,
+            behavior,
+            difficulty,
+            patrol_steps: 0,
+        }
+    }
+
+    /// Whether this enemy grows when the player eats, rather than when it eats
+    pub fn shadows_player(&self) -> bool {
+        self.behavior != Behavior::Thief
+    }
+
+    /// Choose this enemy's direction for the next move
+    pub fn steer<R: Rng>(&mut self, world: &Surroundings, rng: &mut R) -> Direction {
+        if self.behavior == Behavior::Mirror {
+            self.direction = world.player_direction.flip();
+            return self.direction;
+        }
+
+        if rng.gen_range(0, 100) < self.difficulty.blunder() {
+            self.direction = self.random_safe_direction(world.grid, rng);
+            return self.direction;
+        }
+
+        let sight = self.difficulty.sight();
+        self.direction = match self.behavior {
+            Behavior::Chaser => ai::path_toward(world.grid, &self.snake, world.player, sight)
+                .unwrap_or_else(|| ai::seek(world.grid, &self.snake, self.direction, &[])),
+            Behavior::Thief => ai::path_toward(world.grid, &self.snake, world.food, sight)
+                .unwrap_or_else(|| ai::seek(world.grid, &self.snake, self.direction, &[])),
+            Behavior::Patroller => {
+                let alert = self.difficulty.alert();
+                ai::path_toward(world.grid, &self.snake, world.player, alert)
+                    .unwrap_or_else(|| self.patrol(world.grid))
+            }
+            Behavior::Mirror => unreachable!(),
+        };
+
+        self.direction
+    }
+
+    /// Continue along a square beat, turning clockwise at each corner or obstacle
+    fn patrol(&mut self, grid: &[Vec<Cell>]) -> Direction {
+        let (height, width) = (grid.len() as i32, grid[0].len() as i32);
+        self.patrol_steps += 1;
+        let mut direction = self.direction;
+        if self.patrol_steps > self.difficulty.patrol_leg() {
+            self.patrol_steps = 1;
+            direction = direction.clockwise();
+        }
+
+        for _ in 0..4 {
+            let ((row, col), heading) = self.snake.next_move(&direction, height, width);
+            if ai::is_safe(grid[row as usize][col as usize], heading) {
+                return direction;
+            }
+            self.patrol_steps = 1;
+            direction = direction.clockwise();
+        }
+
+        direction
+    }
+
+    /// Pick any direction that doesn't immediately collide
+    fn random_safe_direction<R: Rng>(&self, grid: &[Vec<Cell>], rng: &mut R) -> Direction {
+        let (height, width) = (grid.len() as i32, grid[0].len() as i32);
+        let options: Vec<_> = [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+        .iter()
+        .copied()
+        .filter(|d| {
+            let ((row, col), heading) = self.snake.next_move(d, height, width);
+            ai::is_safe(grid[row as usize][col as usize], heading)
+        })
+        .collect();
+
+        match options.len() {
+            0 => self.direction,
+            n => options[rng.gen_range(0, n)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::types::{BG_COLOR, WALL_COLOR};
+
+    fn enemy(row: u32, col: u32, behavior: Behavior) -> Enemy {
+        Enemy::new(Snake::new(row, col, None), behavior, Difficulty::Hard)
+    }
+
+    fn world(grid: &[Vec<Cell>], player: Position, food: Position) -> Surroundings<'_> {
+        Surroundings {
+            grid,
+            player,
+            player_direction: Direction::Left,
+            food,
+        }
+    }
+
+    #[test]
+    fn mirror_opposes_player() {
+        let grid = vec![vec![BG_COLOR; 5]; 5];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(0, 0, Behavior::Mirror);
+        let actual = enemy.steer(&world(&grid, (4, 4), (2, 2)), &mut rng);
+        assert_eq!(actual, Direction::Right);
+    }
+
+    #[test]
+    fn chaser_hunts_player() {
+        let mut grid = vec![vec![BG_COLOR; 7]; 7];
+        // block the direct route up
+        grid[2][3] = WALL_COLOR;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(3, 3, Behavior::Chaser);
+        let actual = enemy.steer(&world(&grid, (1, 3), (6, 6)), &mut rng);
+        assert_ne!(actual, Direction::Up);
+        assert_ne!(actual, Direction::Down);
+    }
+
+    #[test]
+    fn thief_races_to_food() {
+        let grid = vec![vec![BG_COLOR; 5]; 5];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(2, 2, Behavior::Thief);
+        let actual = enemy.steer(&world(&grid, (2, 0), (4, 2)), &mut rng);
+        assert_eq!(actual, Direction::Down);
+    }
+
+    #[test]
+    fn patroller_walks_a_square() {
+        let grid = vec![vec![BG_COLOR; 36]; 36];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(10, 10, Behavior::Patroller);
+        // player far out of sight
+        let world = world(&grid, (22, 30), (22, 31));
+        let leg = Difficulty::Hard.patrol_leg();
+
+        for _ in 0..4 * leg {
+            let direction = enemy.steer(&world, &mut rng);
+            enemy.snake.update_position(&direction, 36, 36);
+        }
+        assert_eq!(*enemy.snake.position(), (10, 10));
+    }
+
+    #[test]
+    fn patroller_turns_at_walls() {
+        let mut grid = vec![vec![BG_COLOR; 5]; 5];
+        grid[1][2] = WALL_COLOR;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(2, 2, Behavior::Patroller);
+        let actual = enemy.steer(&world(&grid, (4, 4), (4, 0)), &mut rng);
+        assert_eq!(actual, Direction::Right);
+    }
+
+    #[test]
+    fn patroller_charges_nearby_player() {
+        let grid = vec![vec![BG_COLOR; 36]; 36];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(10, 10, Behavior::Patroller);
+        let actual = enemy.steer(&world(&grid, (10, 7), (30, 30)), &mut rng);
+        assert_eq!(actual, Direction::Left);
+    }
+
+    #[test]
+    fn easy_enemies_blunder() {
+        let grid = vec![vec![BG_COLOR; 36]; 36];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut enemy = enemy(18, 18, Behavior::Thief);
+        enemy.difficulty = Difficulty::Easy;
+        let world = world(&grid, (0, 0), (18, 20));
+        let wrong_turns = (0..200)
+            .filter(|_| enemy.steer(&world, &mut rng) != Direction::Right)
+            .count();
+        assert!(wrong_turns > 20 && wrong_turns < 100, "{}", wrong_turns);
+    }
+}
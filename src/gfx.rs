@@ -2,7 +2,7 @@ use sdl2::{rect::Rect, render::Canvas, ttf, video::Window, EventPump};
 
 use crate::{
     menu,
-    types::{self, Cell},
+    types::{self, Cell, Tile},
 };
 
 /// Initialize the canvas
@@ -33,36 +33,77 @@ fn clear_frame(renderer: &mut Canvas<Window>) {
     renderer.clear();
 }
 
-/// Render a single cell from the grid
-///
-/// Translates from game space to pixel space
-pub fn display_cell(
+/// Returns the largest square cell size that fits a `rows` x `cols` board in a `size` window,
+/// and the top left corner that centers the board
+pub fn board_layout(size: (u32, u32), rows: u32, cols: u32) -> (u32, (i32, i32)) {
+    let cell_width = (size.0 / cols.max(1)).min(size.1 / rows.max(1)).max(1);
+    let x = size.0.saturating_sub(cell_width * cols) / 2;
+    let y = size.1.saturating_sub(cell_width * rows) / 2;
+    (cell_width, (x as i32, y as i32))
+}
+
+/// Returns the cell size and top left corner of a `Grid` drawn by `render_frame`
+pub fn frame_layout(renderer: &Canvas<Window>, grid: &[Vec<Cell>]) -> (u32, (i32, i32)) {
+    let rows = grid.len() as u32;
+    let cols = grid.first().map_or(0, |cells| cells.len()) as u32;
+    board_layout(renderer.window().size(), rows, cols)
+}
+
+/// Render a `Grid` on the current draw buffer, scaled to fit and centered in the window
+pub fn render_frame(renderer: &mut Canvas<Window>, grid: &[Vec<Cell>]) {
+    clear_frame(renderer);
+
+    let (cell_width, origin) = frame_layout(renderer, grid);
+    render_preview(renderer, grid, cell_width, origin);
+}
+
+/// Render a `Grid` with its top left corner at `origin`, over whatever is already drawn
+pub fn render_preview(
     renderer: &mut Canvas<Window>,
-    row: u32,
-    col: u32,
-    cell: Cell,
+    grid: &[Vec<Cell>],
     cell_width: u32,
+    origin: (i32, i32),
 ) {
-    let cell_height = cell_width; // All cells are square
-    let x = cell_width * col;
-    let y = cell_width * row;
-
-    renderer.set_draw_color(cell);
-    if let Err(e) = renderer.fill_rect(Rect::new(x as i32, y as i32, cell_width, cell_height)) {
-        println!("{}", e)
+    for (row, cells) in grid.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            let x = origin.0 + (col as u32 * cell_width) as i32;
+            let y = origin.1 + (row as u32 * cell_width) as i32;
+            renderer.set_draw_color(*cell);
+            if let Err(e) = renderer.fill_rect(Rect::new(x, y, cell_width, cell_width)) {
+                println!("{}", e)
+            }
+            if let Some(tile) = Tile::from_color(*cell) {
+                render_marking(renderer, tile, (x, y), cell_width);
+            }
+        }
     }
 }
 
-/// Render a `Grid` on the current draw buffer
-// pub fn render_frame(renderer: &mut Canvas<Window>, grid: &Grid, cell_width: u32) {
-pub fn render_frame(renderer: &mut Canvas<Window>, grid: &[Vec<Cell>], cell_width: u32) {
-    clear_frame(renderer);
+/// Mark which way a conveyor pushes or a gate opens on its cell, once cells are big enough
+///
+/// A conveyor shows a notch at the edge it pushes toward, and a gate a bar across the edge it is
+/// entered by.
+fn render_marking(renderer: &mut Canvas<Window>, tile: Tile, (x, y): (i32, i32), cell_width: u32) {
+    let (direction, size) = match tile {
+        _ if cell_width < 6 => return,
+        Tile::Conveyor(direction) => (direction, (cell_width / 3, cell_width / 3)),
+        Tile::Gate(direction) => (direction.flip(), (cell_width, cell_width / 5)),
+        _ => return,
+    };
+    let (dy, dx) = direction.value();
+    let (width, height) = match dx {
+        0 => size,
+        _ => (size.1, size.0),
+    };
+    let center = |offset: i32, length: u32| {
+        let slack = (cell_width - length) as i32 / 2;
+        slack + offset * slack
+    };
+    let marking = Rect::new(x + center(dx, width), y + center(dy, height), width, height);
 
-    for row in 0..grid.len() as u32 {
-        for col in 0..grid[0].len() as u32 {
-            let cell = grid[row as usize][col as usize];
-            display_cell(renderer, row, col, cell, cell_width);
-        }
+    renderer.set_draw_color(types::TEXT_COLOR);
+    if let Err(e) = renderer.fill_rect(marking) {
+        println!("{}", e)
     }
 }
 
@@ -106,6 +147,71 @@ pub fn render_text(font: &ttf::Font, renderer: &mut Canvas<Window>, text: &str)
         .unwrap();
 }
 
+/// Display a row of colored squares in the top left corner of the window, eg the keys a player
+/// holds
+pub fn render_inventory(renderer: &mut Canvas<Window>, items: &[Cell]) {
+    const SIZE: u32 = 14;
+    const GAP: i32 = 6;
+    for (i, item) in (0..).zip(items.iter()) {
+        renderer.set_draw_color(*item);
+        let slot = Rect::new(GAP + i * (SIZE as i32 + GAP), GAP, SIZE, SIZE);
+        if let Err(e) = renderer.fill_rect(slot) {
+            println!("{}", e)
+        }
+    }
+}
+
+/// Display a text `&str` over a dark band at the bottom of the window, eg to report a problem
+/// without leaving the game
+pub fn render_notice(font: &ttf::Font, renderer: &mut Canvas<Window>, text: &str) {
+    let surface = font.render(text).blended(types::TEXT_COLOR).unwrap();
+    let (width, height) = (surface.width(), surface.height());
+    let (window_width, window_height) = renderer.window().size();
+    let band_height = height + 10;
+    let band_y = (window_height - band_height) as i32;
+
+    renderer.set_draw_color(types::BG_COLOR);
+    renderer
+        .fill_rect(Rect::new(0, band_y, window_width, band_height))
+        .unwrap();
+
+    let texture_creator = renderer.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(surface)
+        .unwrap();
+    let text_center = ((window_width / 2) as i32, band_y + band_height as i32 / 2);
+    renderer
+        .copy(
+            &texture,
+            None,
+            Rect::from_center(text_center, width, height),
+        )
+        .unwrap();
+}
+
+/// Display a text `&str` alone in the middle of the window
+pub fn render_message(renderer: &mut Canvas<Window>, font: &ttf::Font, text: &str) {
+    clear_frame(renderer);
+
+    let surface = font.render(text).blended(types::TEXT_COLOR).unwrap();
+    let (width, height) = (surface.width(), surface.height());
+    let (window_width, window_height) = renderer.window().size();
+    let text_center = ((window_width / 2) as i32, (window_height / 2) as i32);
+
+    let texture_creator = renderer.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(surface)
+        .unwrap();
+
+    renderer
+        .copy(
+            &texture,
+            None,
+            Rect::from_center(text_center, width, height),
+        )
+        .unwrap();
+}
+
 /// Render a `Menu`
 pub fn render_menu(renderer: &mut Canvas<Window>, font: &ttf::Font, menu: &menu::Menu) {
     clear_frame(renderer);
@@ -151,3 +257,17 @@ pub fn render_menu_item(
         .copy(&texture, None, Rect::from_center((x, y), width, height))
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_board_in_window() {
+        assert_eq!(board_layout((721, 721), 36, 36), (20, (0, 0)));
+        // wide boards are limited by the width and centered vertically
+        assert_eq!(board_layout((721, 721), 20, 72), (10, (0, 260)));
+        assert_eq!(board_layout((721, 721), 40, 10), (18, (270, 0)));
+        assert_eq!(board_layout((10, 10), 36, 36).0, 1);
+    }
+}
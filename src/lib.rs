@@ -1,8 +1,12 @@
+pub mod ai;
 pub mod collision;
+pub mod editor;
+pub mod enemy;
 pub mod gfx;
 pub mod input;
 pub mod map;
 pub mod menu;
+pub mod net;
 pub mod snake;
 pub mod types;
 pub mod world;
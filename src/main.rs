@@ -1,21 +1,193 @@
-use std::{thread, time};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    thread, time,
+};
 
-use sdl2::{event::Event, keyboard::Keycode, render::Canvas, ttf, video::Window};
+use sdl2::{
+    event::Event, keyboard::Keycode, mouse::MouseButton, render::Canvas, ttf, video::Window,
+};
 
 use rs_snake::{
+    editor::{Brush, Editor, Tool},
     gfx, input,
+    map::{self, GeneratedMapper, Level, MapError, MapLibrary, MapWatcher, Mapper},
     menu::{self, MenuEvent},
-    types::{self, GameMode, SnakeEvent},
-    world::Gamestate,
+    net::{self, Host, NetError, Session, Spectator, SpectatorServer, TickInput},
+    types::{
+        self, Behavior, Cell, Difficulty, EvilConfig, GameEvent, GameMode, Goal, KeyColor, Ruleset,
+        SnakeEvent, Topology, WorldMap,
+    },
+    world::{Gamestate, ROYALE_SNAKES},
 };
 
+/// Board dimensions, in cells, unless chosen with `--board` or set by a map
+const ROWS: u32 = 36;
+const COLS: u32 = ROWS;
+
+const USAGE: &str = "Usage:
+  rs_snake [--board <rows>x<cols>] [--topology <name>] [--maps <path>]...
+           [--evil <behavior>] [--difficulty <level>] [--enemies <count>]
+           [--host <port> [--peers <count>] | --join <address> | --watch <address>]
+           [--spectate <port>]
+  rs_snake --validate <map file>...
+  rs_snake --convert <map file> <output file>
+
+Topologies are torus, bounded, klein, projective and sphere. Evil twins are mirror, chaser,
+thief and patroller, at easy, normal or hard difficulty.";
+
+/// How this instance takes part in a game
+enum NetRole {
+    /// Single player, no networking
+    Local,
+    /// Host games on the given port for the given number of remote players
+    Host(u16, usize),
+    /// Join the game hosted at the given address
+    Join(String),
+    /// Watch the game streamed from the given address
+    Watch(String),
+}
+
+/// Print what went wrong with the command line, then usage, and quit
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    std::process::exit(2)
+}
+
+/// Parse the enemy selection from command line arguments, falling back to the defaults
+fn parse_evil(args: &[String]) -> Result<EvilConfig, String> {
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+    let mut config = EvilConfig::default();
+
+    if let Some(name) = value_of("--evil") {
+        config.behavior = match name.as_str() {
+            "mirror" => Behavior::Mirror,
+            "chaser" => Behavior::Chaser,
+            "thief" => Behavior::Thief,
+            "patroller" => Behavior::Patroller,
+            _ => return Err(format!("Unknown evil twin behavior: {}", name)),
+        };
+    }
+    if let Some(name) = value_of("--difficulty") {
+        config.difficulty = match name.as_str() {
+            "easy" => Difficulty::Easy,
+            "normal" => Difficulty::Normal,
+            "hard" => Difficulty::Hard,
+            _ => return Err(format!("Unknown difficulty: {}", name)),
+        };
+    }
+    if let Some(count) = value_of("--enemies") {
+        config.count = count
+            .parse()
+            .map_err(|_| format!("Invalid enemy count: {}", count))?;
+    }
+
+    Ok(config)
+}
+
+/// Returns the topology chosen with `--topology <name>`, if any
+fn parse_topology(args: &[String]) -> Result<Option<Topology>, String> {
+    let i = match args.iter().position(|arg| arg == "--topology") {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    let name = args.get(i + 1).ok_or("--topology needs a name")?;
+    match Topology::from_name(name) {
+        Some(topology) => Ok(Some(topology)),
+        None => Err(format!("Unknown topology: {}", name)),
+    }
+}
+
+/// Returns the board size chosen with `--board <rows>x<cols>`, else the default
+fn parse_board(args: &[String]) -> Result<(u32, u32), String> {
+    let size = match args.iter().position(|arg| arg == "--board") {
+        Some(i) => args.get(i + 1).ok_or("--board needs a size")?,
+        None => return Ok((ROWS, COLS)),
+    };
+    let mut parts = size.split('x').map(str::parse::<u32>);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(rows)), Some(Ok(cols)), None) if rows > 0 && cols > 0 => Ok((rows, cols)),
+        _ => Err(format!("Invalid board size: {}", size)),
+    }
+}
+
+/// Returns the extra map files, directories and packs named with `--maps`
+fn parse_map_paths(args: &[String]) -> Vec<PathBuf> {
+    args.windows(2)
+        .filter(|pair| pair[0] == "--maps")
+        .map(|pair| PathBuf::from(&pair[1]))
+        .collect()
+}
+
+/// Parse command line arguments. Returns this instance's role and optional spectator port
+fn parse_args() -> Result<(NetRole, Option<u16>), String> {
+    let args: Vec<String> = env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let parse_port = |port: &String| port.parse().map_err(|_| format!("Invalid port: {}", port));
+    let spectate_port = value_of("--spectate").map(parse_port).transpose()?;
+
+    if let Some(addr) = value_of("--watch") {
+        return Ok((NetRole::Watch(addr.to_string()), None));
+    }
+    if let Some(addr) = value_of("--join") {
+        return Ok((NetRole::Join(addr.to_string()), spectate_port));
+    }
+    if let Some(port) = value_of("--host").map(parse_port).transpose()? {
+        let peers = value_of("--peers")
+            .map(|n| n.parse().map_err(|_| format!("Invalid peer count: {}", n)))
+            .transpose()?
+            .unwrap_or(1);
+        return Ok((NetRole::Host(port, peers), spectate_port));
+    }
+
+    Ok((NetRole::Local, spectate_port))
+}
+
+/// Rewrite any map file in the format named by the output file's extension
+fn convert_map(input: &Path, output: &Path) -> Result<(), MapError> {
+    let world_map = map::load_file(input)?;
+    let (rows, cols) = world_map.size.unwrap_or((ROWS, COLS));
+    map::save_file(output, &world_map, rows, cols)
+}
+
 fn main() {
     const CANVAS_WIDTH: u32 = 720_u32;
     const CANVAS_HEIGHT: u32 = CANVAS_WIDTH;
-    const ROWS: u32 = 36;
-    // const COLS: u32 = ROWS;
 
-    let cell_width = CANVAS_WIDTH / ROWS;
+    let args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--validate") {
+        if !validate_maps(&args[i + 1..]) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--convert") {
+        match (args.get(i + 1), args.get(i + 2)) {
+            (Some(input), Some(output)) => {
+                if let Err(e) = convert_map(Path::new(input), Path::new(output)) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            _ => usage("--convert needs a map file and an output file"),
+        }
+        return;
+    }
+
+    let board = parse_board(&args).unwrap_or_else(|e| usage(&e));
+    let (net_role, spectate_port) = parse_args().unwrap_or_else(|e| usage(&e));
+    let mut evil_config = parse_evil(&args).unwrap_or_else(|e| usage(&e));
+    let mut topology = parse_topology(&args).unwrap_or_else(|e| usage(&e));
+    let map_paths = parse_map_paths(&args);
 
     let (mut canvas, mut event_pump) = gfx::init(CANVAS_WIDTH, CANVAS_HEIGHT);
 
@@ -24,35 +196,464 @@ fn main() {
     let menu_font = gfx::init_font(&ttf_context, types::FONT_PATH, types::FONT_SIZE_MD);
     let game_font = gfx::init_font(&ttf_context, types::FONT_PATH, types::FONT_SIZE_SM);
 
+    let mut spectators = spectate_port.and_then(|port| {
+        SpectatorServer::bind(("0.0.0.0", port))
+            .map_err(|e| println!("Unable to stream on port {}: {}", port, e))
+            .ok()
+    });
+
+    match &net_role {
+        NetRole::Watch(addr) => {
+            return watch_game(&mut canvas, &mut event_pump, (&menu_font, &game_font), addr)
+        }
+        NetRole::Join(addr) => {
+            gfx::render_message(&mut canvas, &menu_font, &format!("Joining {}...", addr));
+            gfx::display_frame(&mut canvas);
+            match net::join(addr.as_str()) {
+                Ok((session, game_state)) => run_net_game(
+                    &mut canvas,
+                    &mut event_pump,
+                    &game_font,
+                    (session, game_state),
+                    &mut spectators,
+                ),
+                Err(e) => println!("Unable to join {}: {}", addr, e),
+            }
+            return;
+        }
+        _ => (),
+    }
+
+    let mut custom_rules = Ruleset::from(GameMode::Normal);
+    custom_rules.topology = topology.unwrap_or(custom_rules.topology);
+
     'menu: loop {
-        match menu::main_menu(&mut canvas, &mut event_pump, &menu_font) {
-            MenuEvent::Start(game_mode) => run_game(
+        let mut choice = menu::main_menu(
+            &mut canvas,
+            &mut event_pump,
+            &menu_font,
+            &mut evil_config,
+            &mut topology,
+        );
+        if choice == MenuEvent::Custom {
+            choice = match menu::custom_menu(
                 &mut canvas,
                 &mut event_pump,
-                &game_font,
-                cell_width,
-                game_mode,
-            ),
+                &menu_font,
+                &mut custom_rules,
+                &mut evil_config,
+            ) {
+                MenuEvent::Quit => continue 'menu,
+                choice => choice,
+            };
+        }
+        match choice {
+            MenuEvent::Start(rules) => {
+                let (world_map, level) = match rules.labyrinth {
+                    true => {
+                        match choose_map(
+                            &mut canvas,
+                            &mut event_pump,
+                            &menu_font,
+                            &map_paths,
+                            board,
+                            rules.topology,
+                        ) {
+                            Some(Ok((world_map, level))) => (Some(world_map), level),
+                            Some(Err(e)) => {
+                                show_error(
+                                    &mut canvas,
+                                    &mut event_pump,
+                                    &game_font,
+                                    &e.to_string(),
+                                );
+                                continue 'menu;
+                            }
+                            None => continue 'menu,
+                        }
+                    }
+                    false => (None, None),
+                };
+                let hosting = matches!(net_role, NetRole::Host(..));
+                let game_state = new_game(rules, evil_config, world_map, board, hosting);
+                match net_role {
+                    NetRole::Host(port, peers) => host_game(
+                        &mut canvas,
+                        &mut event_pump,
+                        (&menu_font, &game_font),
+                        game_state,
+                        (port, peers),
+                        &mut spectators,
+                    ),
+                    _ => run_game(
+                        &mut canvas,
+                        &mut event_pump,
+                        &game_font,
+                        game_state,
+                        level.map(MapWatcher::new),
+                        &mut spectators,
+                    ),
+                }
+            }
+            MenuEvent::Edit => {
+                // test-play by the custom rules, on the chosen edges
+                let rules = Ruleset {
+                    topology: topology.unwrap_or(custom_rules.topology),
+                    ..custom_rules
+                };
+                run_editor(
+                    &mut canvas,
+                    &mut event_pump,
+                    &game_font,
+                    board,
+                    (rules, evil_config),
+                    &mut spectators,
+                )
+            }
             MenuEvent::Quit => break 'menu,
+            _ => (),
         }
     }
 }
 
-fn run_game(
+/// Display an error until the user presses a key
+fn show_error(
     canvas: &mut Canvas<Window>,
     event_pump: &mut sdl2::EventPump,
     font: &ttf::Font,
-    cell_width: u32,
-    game_mode: GameMode,
+    msg: &str,
 ) {
-    const ROWS: u32 = 36;
-    const COLS: u32 = ROWS;
-    let mapper = Box::new(rs_snake::map::CsvMapper {});
-    let mut game_state = match game_mode {
-        GameMode::Map => Gamestate::new(ROWS, COLS, game_mode, Some(mapper)),
-        _ => Gamestate::new(ROWS, COLS, game_mode, None),
+    println!("{}", msg);
+    gfx::render_message(canvas, font, &format!("{} (press any key)", msg));
+    gfx::display_frame(canvas);
+
+    loop {
+        match event_pump.wait_event() {
+            Event::Quit { .. } | Event::KeyDown { .. } => return,
+            _ => (),
+        }
+    }
+}
+
+/// Check a loaded map suits a `rows` x `cols` game board whose edges join as `topology` says
+fn fits_board(
+    path: &Path,
+    world_map: WorldMap,
+    (rows, cols): (u32, u32),
+    topology: Topology,
+) -> Result<WorldMap, MapError> {
+    let problems: Vec<_> = map::validate(&world_map, rows, cols, topology)
+        .iter()
+        .map(map::Problem::to_string)
+        .collect();
+    match problems.is_empty() {
+        true => Ok(world_map),
+        false => Err(MapError::Invalid {
+            path: path.to_path_buf(),
+            reason: problems.join("; "),
+        }),
+    }
+}
+
+/// Check map files from the command line on the boards they declare, printing every problem
+/// found. Returns whether they were all playable
+fn validate_maps(paths: &[String]) -> bool {
+    let mut ok = true;
+    for path in paths.iter().map(Path::new) {
+        let checked = map::load_file(path).and_then(|world_map| {
+            let size = world_map.size.unwrap_or((ROWS, COLS));
+            fits_board(path, world_map, size, Topology::Torus)
+        });
+        match checked {
+            Ok(_) => println!("{}: ok", path.display()),
+            Err(e) => {
+                ok = false;
+                println!("{}", e);
+            }
+        }
+    }
+    ok
+}
+
+/// Save an edited map as the next unused custom_NN.csv in the user's map directory, or the
+/// resource directory if there is no user directory
+fn save_map(editor: &Editor) -> Result<PathBuf, MapError> {
+    let dir = map::user_map_dir().unwrap_or_else(map::resource_dir);
+    fs::create_dir_all(&dir).map_err(|source| MapError::Io {
+        path: dir.clone(),
+        source,
+    })?;
+    let path = (0..)
+        .map(|i| dir.join(format!("custom_{:02}.csv", i)))
+        .find(|path| !path.exists())
+        .unwrap();
+    fs::write(&path, editor.to_csv()).map_err(|source| MapError::Io {
+        path: path.clone(),
+        source,
+    })?;
+
+    Ok(path)
+}
+
+/// Draw a map with the mouse and keyboard
+///
+/// Click or press space to use the current tool. Keys 1-4 pick the brush (wall, floor, player
+/// spawn, enemy spawn), P, L and B the pencil, line and fill tools, M cycles mirroring, Z and Y
+/// undo and redo, T test-plays the map by `rules` and S saves it.
+fn run_editor(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    font: &ttf::Font,
+    (rows, cols): (u32, u32),
+    (rules, evil_config): (Ruleset, EvilConfig),
+    spectators: &mut Option<SpectatorServer>,
+) {
+    let mut editor = Editor::new(rows, cols);
+    let mut status = String::new();
+    let (cell_width, (left, top)) = gfx::board_layout(canvas.window().size(), rows, cols);
+    let cell = |x: i32, y: i32| {
+        (
+            ((y - top).max(0) as u32) / cell_width,
+            ((x - left).max(0) as u32) / cell_width,
+        )
     };
 
+    'editor: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                // back out on escape key
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'editor,
+
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => editor.apply(cell(x, y)),
+                Event::MouseMotion {
+                    mousestate, x, y, ..
+                } if mousestate.left() => editor.drag(cell(x, y)),
+
+                Event::KeyDown {
+                    keycode: Some(k), ..
+                } => {
+                    status.clear();
+                    match k {
+                        Keycode::Up => editor.move_cursor(types::Direction::Up),
+                        Keycode::Down => editor.move_cursor(types::Direction::Down),
+                        Keycode::Left => editor.move_cursor(types::Direction::Left),
+                        Keycode::Right => editor.move_cursor(types::Direction::Right),
+                        Keycode::Space | Keycode::Return => editor.apply(editor.cursor),
+                        Keycode::Num1 => editor.brush = Brush::Wall,
+                        Keycode::Num2 => editor.brush = Brush::Floor,
+                        Keycode::Num3 => editor.brush = Brush::PlayerSpawn,
+                        Keycode::Num4 => editor.brush = Brush::EnemySpawn,
+                        Keycode::P => editor.set_tool(Tool::Pencil),
+                        Keycode::L => editor.set_tool(Tool::Line),
+                        Keycode::B => editor.set_tool(Tool::Fill),
+                        Keycode::M => editor.mirror = editor.mirror.next(),
+                        Keycode::Z if !editor.undo() => status = "Nothing to undo".to_string(),
+                        Keycode::Y if !editor.redo() => status = "Nothing to redo".to_string(),
+                        Keycode::T => {
+                            // check the map as the map menu would before playing it
+                            let rules = Ruleset {
+                                labyrinth: true,
+                                ..rules
+                            };
+                            let path = Path::new("edited map");
+                            let board = (rows, cols);
+                            let checked =
+                                fits_board(path, editor.world_map(), board, rules.topology);
+                            let world_map = match checked {
+                                Ok(world_map) => Some(world_map),
+                                Err(e) => {
+                                    show_error(canvas, event_pump, font, &e.to_string());
+                                    continue 'editor;
+                                }
+                            };
+                            let game_state =
+                                new_game(rules, evil_config, world_map, (rows, cols), false);
+                            run_game(canvas, event_pump, font, game_state, None, spectators);
+                            continue 'editor;
+                        }
+                        Keycode::S => {
+                            status = match save_map(&editor) {
+                                Ok(path) => format!("Saved {}", path.display()),
+                                Err(e) => e.to_string(),
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+
+                _ => continue 'editor,
+            }
+        }
+
+        if status.is_empty() {
+            status = format!(
+                "{} | {} | Mirror: {}",
+                editor.tool.name(),
+                editor.brush.name(),
+                editor.mirror.name()
+            );
+        }
+        gfx::render_frame(canvas, &editor.grid());
+        gfx::render_text(font, canvas, &status);
+        gfx::display_frame(canvas);
+        thread::sleep(time::Duration::from_millis(16));
+    }
+}
+
+/// Let the user pick a labyrinth map from the map directories, `map_paths` and any packs in
+/// them, and load it along with the level it came from, if any. Maps that don't declare their
+/// size, and generated labyrinths, are laid out on `board`, and maps are checked with its edges
+/// joined as `topology` says. Returns `None` if they back out
+fn choose_map(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    font: &ttf::Font,
+    map_paths: &[PathBuf],
+    board: (u32, u32),
+    topology: Topology,
+) -> Option<Result<(WorldMap, Option<Level>), MapError>> {
+    let library = MapLibrary::discover(map_paths);
+    for problem in library.problems.iter() {
+        println!("{}", problem);
+    }
+
+    let levels = &library.levels;
+    let level = match menu::map_menu(canvas, event_pump, font, levels, board, topology) {
+        MenuEvent::SelectMap(Some(i)) => &levels[i],
+        MenuEvent::SelectMap(None) => match library.random() {
+            Some(level) => level,
+            None => return Some(Err(MapError::NoMaps(map::resource_dir()))),
+        },
+        MenuEvent::Generate(algorithm, seed) => {
+            let mapper = GeneratedMapper::new(algorithm, board.0, board.1)
+                .with_topology(topology)
+                .with_seed(seed);
+            let path = PathBuf::from(algorithm.name());
+            let world_map = mapper
+                .load_map()
+                .and_then(|world_map| fits_board(&path, world_map, board, topology));
+            return Some(world_map.map(|world_map| (world_map, None)));
+        }
+        _ => return None,
+    };
+    let path = level.source.path();
+    let world_map = level.load().and_then(|world_map| {
+        let size = world_map.size.unwrap_or(board);
+        fits_board(&path, world_map, size, topology)
+    });
+    Some(world_map.map(|world_map| (world_map, Some(level.clone()))))
+}
+
+/// Create the initial state for a game on a `rows` x `cols` board, or the map's own size
+///
+/// Bots take the empty seats of a last snake standing game, unless `hosting`, when they are
+/// seated after the remote players join.
+fn new_game(
+    rules: Ruleset,
+    evil_config: EvilConfig,
+    world_map: Option<WorldMap>,
+    (rows, cols): (u32, u32),
+    hosting: bool,
+) -> Gamestate {
+    let mut game_state = Gamestate::new(rows, cols, rules, world_map);
+    if rules.goal == Goal::LastStanding && !hosting {
+        game_state.fill_with_bots(ROYALE_SNAKES);
+    }
+    game_state.with_evil(evil_config)
+}
+
+/// Check whether a simulation event ends the round, announcing the outcome if so
+fn round_over(
+    canvas: &mut Canvas<Window>,
+    font: &ttf::Font,
+    evt: Option<SnakeEvent>,
+    local_player: usize,
+) -> bool {
+    match evt {
+        Some(SnakeEvent::Death) => {
+            thread::sleep(time::Duration::from_millis(800));
+            true
+        }
+        Some(SnakeEvent::Game(GameEvent::Finish(winner))) => {
+            let msg = match winner {
+                Some(id) if id == local_player => "You win!".to_string(),
+                Some(id) => format!("Player {} wins", id + 1),
+                None => "Nobody survived".to_string(),
+            };
+            gfx::render_message(canvas, font, &msg);
+            gfx::display_frame(canvas);
+            thread::sleep(time::Duration::from_millis(2000));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Format the score line for the player followed by any rivals
+fn score_text(scores: &[usize], par: Option<usize>) -> String {
+    let scores: Vec<_> = scores.iter().map(usize::to_string).collect();
+    match par {
+        Some(par) => format!("Score: {}  (par {})", scores.join("  |  "), par),
+        None => format!("Score: {}", scores.join("  |  ")),
+    }
+}
+
+/// Display the current game state with an optional notice, and stream it to any spectators
+///
+/// The keys and drill held by `local_player` are shown alongside the scores.
+fn draw_game(
+    canvas: &mut Canvas<Window>,
+    font: &ttf::Font,
+    game_state: &mut Gamestate,
+    local_player: usize,
+    notice: Option<&str>,
+    spectators: &mut Option<SpectatorServer>,
+) {
+    game_state.refresh_grid();
+
+    let scores = game_state.scores();
+    let player = &game_state.players[local_player];
+    let mut items: Vec<Cell> = player.keys.iter().map(KeyColor::key_cell).collect();
+    if player.drill > 0 {
+        items.push(types::DRILL_COLOR);
+    }
+
+    gfx::render_frame(canvas, &game_state.grid);
+    gfx::render_text(font, canvas, &score_text(&scores, game_state.par()));
+    gfx::render_inventory(canvas, &items);
+    if let Some(notice) = notice {
+        gfx::render_notice(font, canvas, notice);
+    }
+    gfx::display_frame(canvas);
+
+    if let Some(server) = spectators {
+        if let Err(e) = server.broadcast(game_state) {
+            println!("Spectator stream error: {}", e);
+        }
+    }
+}
+
+/// Play a local game. With a `watcher`, edits to the map's file are applied as they're saved
+fn run_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    font: &ttf::Font,
+    mut game_state: Gamestate,
+    mut watcher: Option<MapWatcher>,
+    spectators: &mut Option<SpectatorServer>,
+) {
+    // problem with the latest edit to the map, shown until it's fixed
+    let mut notice: Option<String> = None;
+
     'game: loop {
         for event in event_pump.poll_iter() {
             match event {
@@ -72,25 +673,194 @@ fn run_game(
             }
         }
 
-        // fresh state for this game step
-        game_state.grid = game_state.grid_init();
-        // update grid with position of snake
-        game_state.grid = game_state.player.render(game_state.grid);
-        game_state.grid = game_state.evil.render(game_state.grid);
-        // update grid with position of food
-        game_state.grid = game_state.food.render(game_state.grid);
+        // pick up edits to the map's file
+        if let Some(watcher) = watcher.as_mut() {
+            let path = watcher.level().source.path();
+            match watcher.poll() {
+                Some(Ok(world_map)) => {
+                    let topology = game_state.rules().topology;
+                    match fits_board(&path, world_map, game_state.world_size, topology) {
+                        Ok(world_map) => {
+                            game_state.replace_map(world_map);
+                            notice = None;
+                        }
+                        Err(e) => notice = Some(e.to_string()),
+                    }
+                }
+                Some(Err(e)) => notice = Some(e.to_string()),
+                None => (),
+            }
+        }
 
         // display frame
-        gfx::render_frame(canvas, &game_state.grid, cell_width);
-        gfx::render_text(font, canvas, &format!("Score: {}", game_state.score));
-        gfx::display_frame(canvas);
+        draw_game(
+            canvas,
+            font,
+            &mut game_state,
+            0,
+            notice.as_deref(),
+            spectators,
+        );
 
         // update world state
-        if let Some(SnakeEvent::Death) = game_state.simulate(1) {
-            thread::sleep(time::Duration::from_millis(800));
+        if round_over(canvas, font, game_state.simulate(1), 0) {
+            break 'game;
+        }
+
+        thread::sleep(time::Duration::from_millis(game_state.speed()));
+    }
+}
+
+/// Wait for remote players to join, then play a networked game
+fn host_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    (menu_font, game_font): (&ttf::Font, &ttf::Font),
+    game_state: Gamestate,
+    (port, peers): (u16, usize),
+    spectators: &mut Option<SpectatorServer>,
+) {
+    let mut host = match Host::bind(("0.0.0.0", port)) {
+        Ok(host) => host,
+        Err(e) => return println!("Unable to host on port {}: {}", port, e),
+    };
+
+    'lobby: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return,
+                _ => (),
+            }
+        }
+
+        let joined = host.poll_accept();
+        if joined >= peers {
+            break 'lobby;
+        }
+
+        let msg = format!("Waiting for players on port {}: {}/{}", port, joined, peers);
+        gfx::render_message(canvas, menu_font, &msg);
+        gfx::display_frame(canvas);
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    match host.start(game_state) {
+        Ok(game) => run_net_game(canvas, event_pump, game_font, game, spectators),
+        Err(e) => println!("Unable to start game: {}", e),
+    }
+}
+
+/// Play a lockstep networked game
+fn run_net_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    font: &ttf::Font,
+    (mut session, mut game_state): (Session, Gamestate),
+    spectators: &mut Option<SpectatorServer>,
+) {
+    let mut pending = TickInput::Idle;
+
+    'game: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                // exit on escape key
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'game,
+
+                // inputs are held until the next tick's exchange
+                Event::KeyDown {
+                    keycode: Some(k), ..
+                } => match net::tick_input(input::map_key_input(k)) {
+                    TickInput::Idle => (),
+                    input => pending = input,
+                },
+
+                _ => continue 'game,
+            }
+        }
+
+        // display frame
+        draw_game(
+            canvas,
+            font,
+            &mut game_state,
+            session.player_id(),
+            None,
+            spectators,
+        );
+
+        // trade inputs with the other players, then update world state
+        match session.exchange(pending, &game_state) {
+            Ok(inputs) => net::apply_inputs(&mut game_state, &inputs),
+            Err(NetError::Disconnected) => {
+                println!("Host left the game");
+                return;
+            }
+            Err(e) => {
+                println!("{}", e);
+                break 'game;
+            }
+        }
+        pending = TickInput::Idle;
+
+        let evt = game_state.simulate(1);
+        if round_over(canvas, font, evt, session.player_id()) {
             break 'game;
         }
 
         thread::sleep(time::Duration::from_millis(game_state.speed()));
     }
+
+    session.close();
+}
+
+/// Render a game streamed by another instance, read-only
+fn watch_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut sdl2::EventPump,
+    (menu_font, game_font): (&ttf::Font, &ttf::Font),
+    addr: &str,
+) {
+    let mut spectator = match Spectator::connect(addr) {
+        Ok(spectator) => spectator,
+        Err(e) => return println!("Unable to watch {}: {}", addr, e),
+    };
+    gfx::render_message(canvas, menu_font, "Waiting for the game to start...");
+    gfx::display_frame(canvas);
+
+    'watch: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                // exit on escape key
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'watch,
+                _ => (),
+            }
+        }
+
+        match spectator.poll() {
+            Ok(Some(snapshot)) => {
+                gfx::render_frame(canvas, &snapshot.to_grid());
+                gfx::render_text(game_font, canvas, &score_text(&snapshot.scores, None));
+                gfx::display_frame(canvas);
+            }
+            Ok(None) => (),
+            Err(e) => {
+                println!("Stream ended: {}", e);
+                break 'watch;
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(10));
+    }
 }
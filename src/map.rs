@@ -1,84 +1,259 @@
 use std::io::prelude::*;
 use std::{
-    error::Error,
     ffi::OsString,
     fs::{self, File},
     path::{Path, PathBuf},
 };
 
-use crate::types::{self, Position, WorldMap};
+use rand::Rng;
 
+use crate::types::{self, Direction, Position, Topology, WorldMap};
+
+mod ascii_mapper;
 mod csv_mapper;
+mod error;
+mod generated_mapper;
+mod library;
 mod mem_mapper;
+mod pack;
+mod png_mapper;
+mod snek_mapper;
+mod stats;
+mod tiled_mapper;
+mod transform;
+mod validate;
+mod watch;
 
-pub use csv_mapper::CsvMapper;
+pub use ascii_mapper::{parse_ascii, to_ascii, AsciiMapper};
+pub use csv_mapper::{parse_csv, to_csv, CsvMapper};
+pub use error::MapError;
+pub use generated_mapper::{Algorithm, GeneratedMapper};
+pub use library::{resource_dir, user_map_dir, MapLibrary};
 pub use mem_mapper::MemMapper;
+pub use pack::{Level, MapPack, MapSource, MANIFEST};
+pub use png_mapper::{parse_png, to_png, Legend, Palette, PngMapper};
+pub use snek_mapper::{parse_snek, to_snek, SnekMapper, FORMAT_VERSION};
+pub use stats::{stats, Stats};
+pub use tiled_mapper::{parse_tmj, parse_tmx, TiledMapper};
+pub use transform::{transform, Transform};
+pub use validate::{validate, Problem};
+pub use watch::MapWatcher;
 
 /// `WorldMap` loading interface
 pub trait Mapper {
     /// Returns a result that may have a WorldMap instance loaded from...somewhere.
-    fn load_map(&self) -> Result<WorldMap, Box<dyn Error>>;
+    fn load_map(&self) -> Result<WorldMap, MapError>;
 }
 
 /// Get list of files in directory with .csv extension
-fn get_csvs_from_dir(dir_path: &str) -> Vec<PathBuf> {
-    fs::read_dir(dir_path)
-        .unwrap_or_else(|e| panic!("Unable to read dir {}: {}", dir_path, e))
-        .filter(|entry| entry.is_ok())
-        .map(|entry| entry.unwrap().path())
+fn get_csvs_from_dir(dir_path: &Path) -> Result<Vec<PathBuf>, MapError> {
+    let entries = fs::read_dir(dir_path).map_err(|source| MapError::Io {
+        path: dir_path.to_path_buf(),
+        source,
+    })?;
+
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
         .filter(|file_path| file_path.extension() == Some(&OsString::from("csv")))
-        .collect()
+        .collect())
+}
+
+/// List the maps in a directory, sorted by file name
+pub fn list_maps(dir_path: &Path) -> Result<Vec<PathBuf>, MapError> {
+    let mut maps = get_csvs_from_dir(dir_path)?;
+    maps.extend(list_dir_with_ext("snek")(dir_path)?);
+    maps.extend(list_dir_with_ext("txt")(dir_path)?);
+    maps.extend(list_dir_with_ext("png")(dir_path)?);
+    maps.extend(list_dir_with_ext("tmx")(dir_path)?);
+    maps.extend(list_dir_with_ext("tmj")(dir_path)?);
+    maps.sort();
+    Ok(maps)
+}
+
+/// Load a map file of any supported format, chosen by its extension
+pub fn load_file(path: &Path) -> Result<WorldMap, MapError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("snek") => SnekMapper::new(path).load_map(),
+        Some("txt") => AsciiMapper::new(path).load_map(),
+        Some("png") => PngMapper::new(path).load_map(),
+        Some("tmx") | Some("tmj") => TiledMapper::new(path).load_map(),
+        _ => CsvMapper::new(path).load_map(),
+    }
+}
+
+/// Load a map from the contents of a file, in the format its path's extension names
+pub fn parse_bytes(path: &Path, bytes: &[u8]) -> Result<WorldMap, MapError> {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    if ext == Some("png") {
+        return checked(path, parse_png(path, bytes, &Palette::default())?);
+    }
+
+    let s = std::str::from_utf8(bytes).map_err(|_| MapError::Invalid {
+        path: path.to_path_buf(),
+        reason: "not a text file".to_string(),
+    })?;
+    let world_map = match ext {
+        Some("snek") => parse_snek(path, s)?,
+        Some("txt") => parse_ascii(path, s)?,
+        Some("tmx") => parse_tmx(path, s)?,
+        Some("tmj") => parse_tmj(path, s)?,
+        _ => parse_csv(path, s)?,
+    };
+    checked(path, world_map)
+}
+
+/// Write a map of `rows` x `cols` cells to a file, in the format its extension names
+///
+/// Writes .csv, .txt, .snek and .png maps. Formats that can't hold some detail of the map leave
+/// it out, eg spawn directions in .txt.
+pub fn save_file(path: &Path, world_map: &WorldMap, rows: u32, cols: u32) -> Result<(), MapError> {
+    let bytes = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => to_csv(world_map, rows, cols).into_bytes(),
+        Some("txt") => to_ascii(world_map, rows, cols).into_bytes(),
+        Some("snek") => to_snek(world_map, rows, cols).into_bytes(),
+        Some("png") => to_png(world_map, rows, cols, &Palette::default(), 1),
+        _ => {
+            return Err(MapError::Invalid {
+                path: path.to_path_buf(),
+                reason: "unsupported map format, expected .csv, .txt, .snek or .png".to_string(),
+            })
+        }
+    };
+    fs::write(path, bytes).map_err(|source| MapError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Load a random map from a directory
+pub fn load_random(dir_path: &Path) -> Result<WorldMap, MapError> {
+    let maps = list_maps(dir_path)?;
+    if maps.is_empty() {
+        return Err(MapError::NoMaps(dir_path.to_path_buf()));
+    }
+    let selection = rand::thread_rng().gen_range(0, maps.len());
+    load_file(&maps[selection])
+}
+
+/// Validate a freshly loaded map against its own dimensions, turning any problems into an error
+///
+/// Maps don't say how their edges join, so reachability is left to be checked against the
+/// topology of the game the map is played in.
+fn checked(path: &Path, world_map: WorldMap) -> Result<WorldMap, MapError> {
+    let (rows, cols) = match world_map.size {
+        Some(size) => size,
+        None => return Ok(world_map),
+    };
+    let problems: Vec<_> = validate(&world_map, rows, cols, Topology::Torus)
+        .into_iter()
+        .filter(|problem| !matches!(problem, Problem::Unreachable { .. }))
+        .collect();
+    if problems.is_empty() {
+        return Ok(world_map);
+    }
+
+    let problems: Vec<_> = problems.iter().map(Problem::to_string).collect();
+    Err(MapError::Invalid {
+        path: path.to_path_buf(),
+        reason: problems.join("; "),
+    })
+}
+
+/// Returns the display name of a map file
+pub fn map_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Returns the direction a spawn in `row` of a `rows` tall map faces when the map doesn't say,
+/// toward the middle of the board
+pub(crate) fn facing(row: u32, rows: u32) -> Direction {
+    if row < rows / 2 {
+        Direction::Down
+    } else {
+        Direction::Up
+    }
+}
+
+/// Parse a spawn direction as written in a map file, eg "up"
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
 }
 
 /// Returns a function that takes a directory and lists files with given extension
-fn list_dir_with_ext<'a>(extension: &'a str) -> Box<dyn Fn(&str) -> Vec<PathBuf> + 'a> {
+#[allow(clippy::type_complexity)]
+fn list_dir_with_ext<'a>(
+    extension: &'a str,
+) -> Box<dyn Fn(&Path) -> Result<Vec<PathBuf>, MapError> + 'a> {
     Box::new(move |dir_path| {
-        fs::read_dir(dir_path)
-            .unwrap_or_else(|e| panic!("Unable to read dir {}: {}", dir_path, e))
-            .filter(|entry| entry.is_ok())
-            .map(|entry| entry.unwrap().path())
+        let entries = fs::read_dir(dir_path).map_err(|source| MapError::Io {
+            path: dir_path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
             .filter(|file_path| file_path.extension() == Some(&OsString::from(extension)))
-            .collect()
+            .collect())
     })
 }
 
-/// Load contents of a file into a String. **`panic!`s on errors.**
-fn load_to_string(file_path: &str) -> String {
-    let path = Path::new(file_path);
-    let display = path.display();
+/// Load contents of a file into a String
+fn load_to_string(path: &Path) -> Result<String, MapError> {
+    let io_error = |source| MapError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
 
     // open the path in read only mode, returns `io::Result<File>`
-    let mut file = match File::open(&path) {
-        Err(why) => panic!("couldnt open {}: {}", display, why),
-        Ok(file) => file,
-    };
+    let mut file = File::open(path).map_err(io_error)?;
 
     // read the contents into a string
     let mut s = String::new();
-    if let Err(why) = file.read_to_string(&mut s) {
-        panic!("couldnt open {}: {}", display, why);
-    }
+    file.read_to_string(&mut s).map_err(io_error)?;
 
-    s
+    Ok(s)
 }
 
-/// parse string slices to u32, `panic!` on failure
-fn parse_u32(s: &str) -> u32 {
-    s.parse::<u32>()
-        .unwrap_or_else(|_| panic!("Unable to parse {} to u32", s))
+/// parse string slices to u32
+fn parse_u32(s: &str) -> Option<u32> {
+    s.parse::<u32>().ok()
 }
 
-/// parse csv (as a `&str`) into a 2d Vec of `u32` values
-fn csv_into_vec(s: &str) -> Vec<Vec<u32>> {
-    s.split('\n')
-        .filter(|ln| !ln.is_empty())
-        .map(|str| {
-            str.split(',')
-                .map(|str| str.trim())
-                .map(parse_u32)
-                .collect()
-        })
-        .collect()
+/// parse csv (as a `&str`) read from `path` into a 2d Vec of `u32` values
+fn csv_into_vec(path: &Path, s: &str) -> Result<Vec<Vec<u32>>, MapError> {
+    let mut grid = vec![];
+    for (line, text) in (1..).zip(s.split('\n')) {
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let mut row = vec![];
+        let mut column = 1;
+        for field in text.split(',') {
+            let token = field.trim();
+            let value = parse_u32(token).ok_or_else(|| MapError::Parse {
+                path: path.to_path_buf(),
+                line,
+                column: column + field.chars().count() - field.trim_start().chars().count(),
+                message: format!("unexpected '{}'", token),
+            })?;
+            row.push(value);
+            column += field.chars().count() + 1;
+        }
+        grid.push(row);
+    }
+
+    Ok(grid)
 }
 
 /// parse 2d Vec of `u32` values into a Vec of `Position`s
@@ -103,41 +278,67 @@ mod test {
 
     #[test]
     fn parses_u32() {
-        let expected: u32 = 123;
+        let expected = Some(123);
         let actual = parse_u32("123");
         assert_eq!(actual, expected);
     }
 
     #[test]
-    #[should_panic]
-    fn panics_on_parse_failure() {
-        parse_u32("foo");
+    fn rejects_non_u32() {
+        assert_eq!(parse_u32("foo"), None);
     }
 
     #[test]
     fn csv_to_vec() {
         let expected = vec![[1, 1], [0, 0]];
         let actual = csv_into_vec(
+            Path::new("test.csv"),
             "1,1
             0,0",
-        );
+        )
+        .unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn csv_error_location() {
+        let actual = csv_into_vec(Path::new("test.csv"), "1,1\n\n0, x\n");
+        match actual {
+            Err(MapError::Parse {
+                path,
+                line,
+                column,
+                message,
+            }) => {
+                assert_eq!(path, PathBuf::from("test.csv"));
+                assert_eq!((line, column), (3, 4));
+                assert_eq!(message, "unexpected 'x'");
+            }
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn loads_to_string() {
         let expected = "1,1
 0,0
 ";
-        let actual = load_to_string("./fixture/map_00.csv");
+        let actual = load_to_string(Path::new("./fixture/map_00.csv")).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn missing_file_is_io_error() {
+        let actual = load_to_string(Path::new("./fixture/nope.csv"));
+        assert!(matches!(actual, Err(MapError::Io { .. })));
+    }
+
     #[test]
     fn file_to_vec() {
         let expected = vec![[1, 1], [0, 0]];
-        let s = load_to_string("./fixture/map_00.csv");
-        let actual = csv_into_vec(&s);
+        let path = Path::new("./fixture/map_00.csv");
+        let s = load_to_string(path).unwrap();
+        let actual = csv_into_vec(path, &s).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -152,7 +353,7 @@ mod test {
     #[test]
     fn list_csv() {
         let expected = vec![PathBuf::from("./fixture/map_00.csv")];
-        let actual = get_csvs_from_dir("./fixture");
+        let actual = get_csvs_from_dir(Path::new("./fixture")).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -160,7 +361,86 @@ mod test {
     fn list_csv_curried() {
         let expected = vec![PathBuf::from("./fixture/map_00.csv")];
         let list_csvs = list_dir_with_ext("csv");
-        let actual = list_csvs("./fixture");
+        let actual = list_csvs(Path::new("./fixture")).unwrap();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn lists_every_format() {
+        let expected = vec![
+            PathBuf::from("./fixture/map_00.csv"),
+            PathBuf::from("./fixture/tiny.png"),
+            PathBuf::from("./fixture/tiny.snek"),
+            PathBuf::from("./fixture/tiny.tmx"),
+            PathBuf::from("./fixture/tiny.txt"),
+        ];
+        assert_eq!(list_maps(Path::new("./fixture")).unwrap(), expected);
+    }
+
+    #[test]
+    fn loads_by_extension() {
+        let snek = load_file(Path::new("./fixture/tiny.snek")).unwrap();
+        assert_eq!(snek.meta.name.as_deref(), Some("Fixture"));
+        let ascii = load_file(Path::new("./fixture/tiny.txt")).unwrap();
+        assert_eq!(ascii.food_spots, vec![(2, 2)]);
+        let png = load_file(Path::new("./fixture/tiny.png")).unwrap();
+        assert_eq!(png.player_spawns[0].position, (0, 0));
+        let csv = load_file(Path::new("./fixture/map_00.csv")).unwrap();
+        assert_eq!(csv.walls, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn saves_by_extension() {
+        let tiny = load_file(Path::new("./fixture/tiny.txt")).unwrap();
+        for ext in ["csv", "txt", "snek", "png"].iter() {
+            let path = std::env::temp_dir().join(format!("rs_snake_save.{}", ext));
+            save_file(&path, &tiny, 4, 4).unwrap();
+            let saved = load_file(&path).unwrap();
+            assert_eq!(saved.walls, tiny.walls, "{}", ext);
+            assert_eq!(saved.size, Some((4, 4)));
+        }
+        let path = std::env::temp_dir().join("rs_snake_save.bmp");
+        assert!(matches!(
+            save_file(&path, &tiny, 4, 4),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn bundled_maps_load() {
+        for path in list_maps(Path::new(types::MAP_DIR)).unwrap() {
+            if let Err(e) = load_file(&path) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn validates_at_load() {
+        let path = std::env::temp_dir().join("rs_snake_solid.txt");
+        fs::write(&path, "##\n##\n").unwrap();
+        match load_file(&path) {
+            Err(MapError::Invalid { reason, .. }) => {
+                assert!(reason.contains("floor"), "{}", reason)
+            }
+            other => panic!("Expected invalid map, got {:?}", other),
+        }
+
+        // a pocket may open up on other topologies, so it's left to the game to reject
+        let path = std::env::temp_dir().join("rs_snake_pocket.txt");
+        fs::write(&path, "....\n.#..\n#.#.\n.#..\n").unwrap();
+        let pocket = load_file(&path).unwrap();
+        assert!(!validate(&pocket, 4, 4, Topology::Torus).is_empty());
+    }
+
+    #[test]
+    fn names_maps() {
+        assert_eq!(map_name(Path::new("./resource/map_01.csv")), "map_01");
+    }
+
+    #[test]
+    fn missing_dir_is_io_error() {
+        let actual = get_csvs_from_dir(Path::new("./no_such_dir"));
+        assert!(matches!(actual, Err(MapError::Io { .. })));
+    }
 }
@@ -0,0 +1,238 @@
+use super::*;
+use crate::types::{Portal, Spawn, Tile};
+
+/// Character marking a player spawn in an ASCII map
+const PLAYER: char = 'P';
+/// Character marking an enemy spawn in an ASCII map
+const ENEMY: char = 'E';
+/// Character marking a fixed food spot in an ASCII map
+const FOOD: char = 'F';
+
+/// A `Mapper` implementation backed by a plain text drawing of the map
+///
+/// Each line is a row of the board, one character per cell: `.` for floor, `#` for wall,
+/// `P` and `E` for player and enemy spawns, `F` for a fixed food spot, a digit for each end of
+/// a portal, and the other `Tile` symbols. The two cells marked with the same digit are linked.
+/// Spawns face toward the middle of the board, and snakes keep their direction through portals.
+///
+/// ```text
+/// #..#
+/// 1P.1
+/// ..F.
+/// #E.#
+/// ```
+pub struct AsciiMapper {
+    file: PathBuf,
+}
+
+impl AsciiMapper {
+    /// Create an `AsciiMapper` that loads the given file
+    pub fn new<P: Into<PathBuf>>(file: P) -> Self {
+        AsciiMapper { file: file.into() }
+    }
+}
+
+impl Mapper for AsciiMapper {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let content = load_to_string(&self.file)?;
+        checked(&self.file, parse_ascii(&self.file, &content)?)
+    }
+}
+
+/// Parse an ASCII map read from `path`
+pub fn parse_ascii(path: &Path, s: &str) -> Result<WorldMap, MapError> {
+    let lines: Vec<&str> = s.trim_end().lines().collect();
+    let cols = lines.first().map_or(0, |line| line.chars().count());
+    if cols == 0 {
+        return Err(MapError::Invalid {
+            path: path.to_path_buf(),
+            reason: "map is empty".to_string(),
+        });
+    }
+    let rows = lines.len() as u32;
+
+    let mut world_map = WorldMap::new(vec![], types::WALL_COLOR);
+    let mut portal_ends: Vec<Vec<Position>> = vec![vec![]; 10];
+    for (row, (line, text)) in (0..).zip((1..).zip(lines.iter())) {
+        let parse_error = |column, message| MapError::Parse {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message,
+        };
+        let width = text.chars().count();
+        if width != cols {
+            let message = format!("expected {} columns, found {}", cols, width);
+            return Err(parse_error(std::cmp::min(width, cols) + 1, message));
+        }
+
+        for (col, symbol) in (0..).zip(text.chars()) {
+            let position = (row, col);
+            let spawn = Spawn {
+                position,
+                direction: facing(row, rows),
+            };
+            match symbol {
+                PLAYER => world_map.player_spawns.push(spawn),
+                ENEMY => world_map.enemy_spawns.push(spawn),
+                FOOD => world_map.food_spots.push(position),
+                digit if digit.is_ascii_digit() => {
+                    let pair = digit.to_digit(10).unwrap_or_default() as usize;
+                    portal_ends[pair].push(position);
+                }
+                _ => {
+                    let tile = Tile::from_symbol(symbol).ok_or_else(|| {
+                        parse_error(col as usize + 1, format!("unknown tile '{}'", symbol))
+                    })?;
+                    world_map.place(position, tile);
+                }
+            }
+        }
+    }
+
+    for (pair, ends) in portal_ends.iter().enumerate() {
+        match ends[..] {
+            [] => (),
+            [a, b] => world_map.portals.push(Portal::new(a, b)),
+            _ => {
+                return Err(MapError::Invalid {
+                    path: path.to_path_buf(),
+                    reason: format!("portal '{}' has {} ends, expected 2", pair, ends.len()),
+                })
+            }
+        }
+    }
+
+    Ok(WorldMap {
+        size: Some((rows, cols as u32)),
+        ..world_map
+    })
+}
+
+/// Draw a `WorldMap` as an ASCII map of `rows` x `cols` cells
+///
+/// Spawn directions and portal exit directions are not kept, nor any portals past the tenth.
+/// Anything outside the board is left out.
+pub fn to_ascii(world_map: &WorldMap, rows: u32, cols: u32) -> String {
+    let mut grid = vec![vec![Tile::Floor.symbol(); cols as usize]; rows as usize];
+    let mut draw = |(row, col): Position, symbol: char| {
+        if row < rows && col < cols {
+            grid[row as usize][col as usize] = symbol;
+        }
+    };
+
+    for wall in world_map.walls.iter() {
+        draw(*wall, Tile::Wall.symbol());
+    }
+    for (position, tile) in world_map.tiles.iter() {
+        draw(*position, tile.symbol());
+    }
+    for spot in world_map.food_spots.iter() {
+        draw(*spot, FOOD);
+    }
+    for (digit, portal) in ('0'..='9').zip(world_map.portals.iter()) {
+        for end in portal.ends.iter() {
+            draw(end.position, digit);
+        }
+    }
+    for spawn in world_map.enemy_spawns.iter() {
+        draw(spawn.position, ENEMY);
+    }
+    for spawn in world_map.player_spawns.iter() {
+        draw(spawn.position, PLAYER);
+    }
+
+    grid.iter()
+        .map(|cells| cells.iter().collect::<String>() + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Direction;
+
+    const TINY: &str = "#..#\n.P..\n..Fo\n#E.#\n";
+
+    fn parse(s: &str) -> Result<WorldMap, MapError> {
+        parse_ascii(Path::new("test.txt"), s)
+    }
+
+    #[test]
+    fn parses_symbols() {
+        let map = parse(TINY).unwrap();
+        assert_eq!(map.size, Some((4, 4)));
+        assert_eq!(map.walls, vec![(0, 0), (0, 3), (2, 3), (3, 0), (3, 3)]);
+        assert_eq!(map.tiles, vec![((2, 3), Tile::Rock)]);
+        assert_eq!(
+            map.player_spawns,
+            vec![Spawn {
+                position: (1, 1),
+                direction: Direction::Down,
+            }]
+        );
+        assert_eq!(map.enemy_spawns[0].position, (3, 1));
+        assert_eq!(map.enemy_spawns[0].direction, Direction::Up);
+        assert_eq!(map.food_spots, vec![(2, 2)]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let map = parse(TINY).unwrap();
+        assert_eq!(to_ascii(&map, 4, 4), TINY);
+        assert_eq!(parse(&to_ascii(&map, 4, 4)).unwrap(), map);
+    }
+
+    #[test]
+    fn reads_terrain() {
+        let ascii = "~>.\nr#v\n";
+        let map = parse(ascii).unwrap();
+        assert_eq!(map.walls, vec![(1, 1)]);
+        assert_eq!(
+            map.tiles,
+            vec![
+                ((0, 0), Tile::Ice),
+                ((0, 1), Tile::Conveyor(Direction::Right)),
+                ((1, 0), Tile::Gate(Direction::Right)),
+                ((1, 2), Tile::Conveyor(Direction::Down)),
+            ]
+        );
+        assert_eq!(to_ascii(&map, 2, 3), ascii);
+    }
+
+    #[test]
+    fn pairs_portals_by_digit() {
+        let map = parse("1.2.\n....\n..21\n").unwrap();
+        assert_eq!(
+            map.portals,
+            vec![Portal::new((0, 0), (2, 3)), Portal::new((0, 2), (2, 2))]
+        );
+        assert_eq!(to_ascii(&map, 3, 4), "0.1.\n....\n..10\n");
+        assert!(matches!(
+            parse("1.1.\n..1.\n"),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn reports_bad_symbols() {
+        match parse("#..#\n.P?.\n") {
+            Err(MapError::Parse { line, column, .. }) => assert_eq!((line, column), (2, 3)),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+        match parse("#..#\n.P.\n") {
+            Err(MapError::Parse { line, column, .. }) => assert_eq!((line, column), (2, 4)),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+        assert!(matches!(parse("\n"), Err(MapError::Invalid { .. })));
+    }
+
+    #[test]
+    fn converts_csv() {
+        let csv = CsvMapper::new("./fixture/map_00.csv").load_map().unwrap();
+        let (rows, cols) = csv.size.unwrap();
+        let ascii = parse(&to_ascii(&csv, rows, cols)).unwrap();
+        assert_eq!(ascii.walls, csv.walls);
+        assert_eq!(ascii.size, csv.size);
+    }
+}
@@ -1,25 +1,184 @@
-use rand::Rng;
-
 use super::*;
+use crate::types::{Portal, Spawn};
+
+/// Cell values in a CSV map. Anything else is floor
+const WALL_CELL: u32 = 1;
+const PLAYER_CELL: u32 = 2;
+const ENEMY_CELL: u32 = 3;
+/// Cell value of the first portal pair's ends. Each further pair takes the next value
+const PORTAL_CELL: u32 = 10;
 
 /// A `Mapper` implementation backed by .csv file(s)
-pub struct CsvMapper {}
+///
+/// Cells holding `1` are walls, `2` player spawns and `3` enemy spawns. Cells holding `10` or more
+/// are portal ends, the two holding the same value linked. Spawns face toward the middle of the
+/// board.
+pub struct CsvMapper {
+    /// The map file to load. A random map from the resource directory when `None`
+    file: Option<PathBuf>,
+}
+
+impl CsvMapper {
+    /// Create a `CsvMapper` that loads the given file
+    pub fn new<P: Into<PathBuf>>(file: P) -> Self {
+        CsvMapper {
+            file: Some(file.into()),
+        }
+    }
+
+    /// Create a `CsvMapper` that loads a random map each time
+    pub fn random() -> Self {
+        CsvMapper { file: None }
+    }
+}
 
 impl Mapper for CsvMapper {
-    fn load_map(&self) -> Result<WorldMap, Box<dyn Error>> {
-        let mut map_list = get_csvs_from_dir("./resource");
-        map_list.sort();
-        let map_selection = rand::thread_rng().gen_range(0, map_list.len());
-        let file_path = map_list
-            .get(map_selection)
-            .ok_or("No maps found in resource dir")?;
-        let content = load_to_string(file_path.to_str().ok_or("PathBuf conversion err")?);
-        let grid = csv_into_vec(&content);
-        let walls = grid_to_position_vec(grid);
-
-        Ok(WorldMap {
-            color: types::WALL_COLOR,
-            walls,
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let file_path = match &self.file {
+            Some(file_path) => file_path.clone(),
+            None => {
+                let dir = resource_dir();
+                let map_list = get_csvs_from_dir(&dir)?;
+                if map_list.is_empty() {
+                    return Err(MapError::NoMaps(dir));
+                }
+                let map_selection = rand::thread_rng().gen_range(0, map_list.len());
+                map_list[map_selection].clone()
+            }
+        };
+        let content = load_to_string(&file_path)?;
+        checked(&file_path, parse_csv(&file_path, &content)?)
+    }
+}
+
+/// Parse the contents of a CSV map read from `path`
+pub fn parse_csv(path: &Path, s: &str) -> Result<WorldMap, MapError> {
+    let grid = csv_into_vec(path, s)?;
+    let size = (grid.len() as u32, grid.first().map_or(0, Vec::len) as u32);
+    let cells_holding = |value: u32| -> Vec<Position> {
+        (0..)
+            .zip(grid.iter())
+            .flat_map(|(row, cells)| {
+                (0..)
+                    .zip(cells.iter())
+                    .filter(move |(_, cell)| **cell == value)
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect()
+    };
+    let spawns = |value: u32| -> Vec<Spawn> {
+        cells_holding(value)
+            .into_iter()
+            .map(|position| Spawn {
+                position,
+                direction: facing(position.0, size.0),
+            })
+            .collect()
+    };
+    let player_spawns = spawns(PLAYER_CELL);
+    let enemy_spawns = spawns(ENEMY_CELL);
+
+    let mut values: Vec<u32> = grid.iter().flatten().copied().collect();
+    values.retain(|value| *value >= PORTAL_CELL);
+    values.sort_unstable();
+    values.dedup();
+    let mut portals = vec![];
+    for value in values {
+        match cells_holding(value)[..] {
+            [a, b] => portals.push(Portal::new(a, b)),
+            ref ends => {
+                return Err(MapError::Invalid {
+                    path: path.to_path_buf(),
+                    reason: format!("portal {} has {} ends, expected 2", value, ends.len()),
+                })
+            }
+        }
+    }
+    let walls = grid_to_position_vec(grid);
+
+    Ok(WorldMap {
+        size: Some(size),
+        player_spawns,
+        enemy_spawns,
+        portals,
+        ..WorldMap::new(walls, types::WALL_COLOR)
+    })
+}
+
+/// Write a `WorldMap` as a `rows` x `cols` CSV map
+///
+/// Only walls, spawns and portals are kept, without directions. Anything outside the board is
+/// left out.
+pub fn to_csv(world_map: &WorldMap, rows: u32, cols: u32) -> String {
+    let mut grid = vec![vec![0; cols as usize]; rows as usize];
+    let mut draw = |(row, col): Position, value: u32| {
+        if row < rows && col < cols {
+            grid[row as usize][col as usize] = value;
+        }
+    };
+    for wall in world_map.walls.iter() {
+        draw(*wall, WALL_CELL);
+    }
+    for (value, portal) in (PORTAL_CELL..).zip(world_map.portals.iter()) {
+        for end in portal.ends.iter() {
+            draw(end.position, value);
+        }
+    }
+    for spawn in world_map.enemy_spawns.iter() {
+        draw(spawn.position, ENEMY_CELL);
+    }
+    for spawn in world_map.player_spawns.iter() {
+        draw(spawn.position, PLAYER_CELL);
+    }
+
+    grid.iter()
+        .map(|cells| {
+            let cells: Vec<_> = cells.iter().map(u32::to_string).collect();
+            cells.join(",") + "\n"
         })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_chosen_file() {
+        let actual = CsvMapper::new("./fixture/map_00.csv").load_map().unwrap();
+        assert_eq!(actual.walls, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn round_trips_spawns() {
+        let csv = "1,0,2\n0,3,0\n";
+        let path = std::env::temp_dir().join("rs_snake_round_trip.csv");
+        fs::write(&path, csv).unwrap();
+        let map = CsvMapper::new(&path).load_map().unwrap();
+        assert_eq!(map.walls, vec![(0, 0)]);
+        assert_eq!(map.player_spawns[0].position, (0, 2));
+        assert_eq!(map.enemy_spawns[0].position, (1, 1));
+        assert_eq!(to_csv(&map, 2, 3), csv);
+    }
+
+    #[test]
+    fn pairs_portals_by_value() {
+        let csv = "10,0,11\n11,0,10\n";
+        let map = parse_csv(Path::new("test.csv"), csv).unwrap();
+        assert_eq!(
+            map.portals,
+            vec![Portal::new((0, 0), (1, 2)), Portal::new((0, 2), (1, 0))]
+        );
+        assert_eq!(to_csv(&map, 2, 3), csv);
+        assert!(matches!(
+            parse_csv(Path::new("test.csv"), "10,0,10\n10,0,0\n"),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let actual = CsvMapper::new("./fixture/missing.csv").load_map();
+        assert!(matches!(actual, Err(MapError::Io { .. })));
     }
 }
@@ -0,0 +1,60 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Errors encountered while loading a `WorldMap`
+#[derive(Debug)]
+pub enum MapError {
+    /// The map file or directory couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    /// A map file contains something other than a map. `line` and `column` count from 1
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A map file is well formed but describes an impossible map
+    Invalid { path: PathBuf, reason: String },
+    /// A map directory contains no maps
+    NoMaps(PathBuf),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "Unable to read {}: {}", path.display(), source),
+            Self::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            Self::Invalid { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            Self::NoMaps(path) => write!(f, "No maps found in {}", path.display()),
+        }
+    }
+}
+
+impl Error for MapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_location() {
+        let err = MapError::Parse {
+            path: PathBuf::from("maps/map_00.csv"),
+            line: 3,
+            column: 7,
+            message: "unexpected 'x'".to_string(),
+        };
+        assert_eq!(err.to_string(), "maps/map_00.csv:3:7: unexpected 'x'");
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::*;
+use crate::{
+    snake::Snake,
+    types::MapMeta,
+    world::{enemy_point, spawn_point, ROYALE_SNAKES},
+};
+
+/// Cells cleared around every default spawn point, in each direction
+const SPAWN_RADIUS: u32 = 2;
+
+/// Number of default enemy spawn points kept clear
+const ENEMY_POINTS: usize = 4;
+
+/// A way of generating a labyrinth
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Algorithm {
+    /// A maze carved by a randomized depth-first search, with long winding corridors
+    Backtracker,
+    /// A maze grown by randomized Prim's algorithm, with many short dead ends
+    Prim,
+    /// Organic caverns smoothed out of random noise by a cellular automaton
+    Caves,
+    /// Walled rooms joined by doorways, mirrored into all four quarters of the board
+    Rooms,
+}
+
+impl Algorithm {
+    /// Every algorithm
+    pub const ALL: [Algorithm; 4] = [
+        Algorithm::Backtracker,
+        Algorithm::Prim,
+        Algorithm::Caves,
+        Algorithm::Rooms,
+    ];
+
+    /// Returns a display name for this `Algorithm`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Backtracker => "Winding Maze",
+            Self::Prim => "Branching Maze",
+            Self::Caves => "Caves",
+            Self::Rooms => "Rooms",
+        }
+    }
+}
+
+/// A `Mapper` implementation that generates a fresh labyrinth
+///
+/// The same algorithm, size, topology, density and seed always produce the same map. Every
+/// generated map has its floor connected (through the board's edges as its topology joins them,
+/// as snakes move) and room around each default spawn point.
+pub struct GeneratedMapper {
+    algorithm: Algorithm,
+    rows: u32,
+    cols: u32,
+    /// How the edges of the board the map is played on join
+    topology: Topology,
+    /// How much of the algorithm's walls to keep, from 0 (open) to 1 (all)
+    density: f64,
+    seed: u64,
+}
+
+impl GeneratedMapper {
+    /// Create a `GeneratedMapper` for `rows` x `cols` torus boards, with a random seed
+    pub fn new(algorithm: Algorithm, rows: u32, cols: u32) -> Self {
+        GeneratedMapper {
+            algorithm,
+            rows,
+            cols,
+            topology: Topology::Torus,
+            density: 0.75,
+            seed: rand::random(),
+        }
+    }
+
+    /// Generate the map for a particular seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Connect the floor across the edges of a board joined as `topology` says
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Keep this share of the algorithm's walls, from 0 (open) to 1 (all)
+    pub fn with_density(mut self, density: f64) -> Self {
+        self.density = density.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl Mapper for GeneratedMapper {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let (rows, cols) = (self.rows, self.cols);
+        if rows < 2 * SPAWN_RADIUS + 2 || cols < 2 * SPAWN_RADIUS + 2 {
+            return Err(MapError::Invalid {
+                path: PathBuf::from(self.algorithm.name()),
+                reason: format!("can't generate a map as small as {}x{}", rows, cols),
+            });
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut walls = match self.algorithm {
+            Algorithm::Backtracker => backtracker(rows, cols, &mut rng),
+            Algorithm::Prim => prim(rows, cols, &mut rng),
+            Algorithm::Caves => caves(rows, cols, self.density, &mut rng),
+            Algorithm::Rooms => rooms(rows, cols, &mut rng),
+        };
+        if self.algorithm != Algorithm::Caves {
+            thin(
+                &mut walls,
+                self.density,
+                self.algorithm == Algorithm::Rooms,
+                &mut rng,
+            );
+        }
+        clear_spawns(&mut walls, self.algorithm == Algorithm::Rooms);
+        connect(&mut walls, self.topology);
+
+        let mut positions = vec![];
+        for (row, cells) in (0..).zip(walls.iter()) {
+            for (col, wall) in (0..).zip(cells.iter()) {
+                if *wall {
+                    positions.push((row, col));
+                }
+            }
+        }
+        Ok(WorldMap {
+            size: Some((rows, cols)),
+            meta: MapMeta {
+                name: Some(format!("{} #{}", self.algorithm.name(), self.seed % 10_000)),
+                ..MapMeta::default()
+            },
+            ..WorldMap::new(positions, types::WALL_COLOR)
+        })
+    }
+}
+
+/// Grid of wall flags, indexed by row then column
+type Walls = Vec<Vec<bool>>;
+
+/// Returns the cells a snake can step to from a cell, crossing the edges of the board as
+/// `walker`'s topology says
+fn neighbors(position: Position, walker: &Snake, rows: u32, cols: u32) -> Vec<Position> {
+    [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ]
+    .iter()
+    .map(|direction| walker.step_from(position, direction, rows as i32, cols as i32))
+    .filter(|next| *next != position)
+    .collect()
+}
+
+/// Returns the maze cells next to a maze cell, in a grid of `rows` x `cols` maze cells
+fn maze_neighbors((row, col): Position, rows: u32, cols: u32) -> Vec<Position> {
+    let mut cells = vec![];
+    if row > 0 {
+        cells.push((row - 1, col));
+    }
+    if row + 1 < rows {
+        cells.push((row + 1, col));
+    }
+    if col > 0 {
+        cells.push((row, col - 1));
+    }
+    if col + 1 < cols {
+        cells.push((row, col + 1));
+    }
+    cells
+}
+
+/// Open a maze cell and the wall between it and a neighboring maze cell
+///
+/// Maze cell (r, c) sits on board cell (2r + 1, 2c + 1).
+fn carve(walls: &mut Walls, (row, col): Position, from: Option<Position>) {
+    walls[2 * row as usize + 1][2 * col as usize + 1] = false;
+    if let Some((from_row, from_col)) = from {
+        walls[(row + from_row + 1) as usize][(col + from_col + 1) as usize] = false;
+    }
+}
+
+fn backtracker(rows: u32, cols: u32, rng: &mut StdRng) -> Walls {
+    let mut walls = vec![vec![true; cols as usize]; rows as usize];
+    let (maze_rows, maze_cols) = ((rows - 1) / 2, (cols - 1) / 2);
+    let mut visited = vec![vec![false; maze_cols as usize]; maze_rows as usize];
+
+    let mut stack = vec![(0, 0)];
+    visited[0][0] = true;
+    carve(&mut walls, (0, 0), None);
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<Position> = maze_neighbors(cell, maze_rows, maze_cols)
+            .into_iter()
+            .filter(|(r, c)| !visited[*r as usize][*c as usize])
+            .collect();
+        match unvisited.choose(rng) {
+            Some(&next) => {
+                visited[next.0 as usize][next.1 as usize] = true;
+                carve(&mut walls, next, Some(cell));
+                stack.push(next);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    walls
+}
+
+fn prim(rows: u32, cols: u32, rng: &mut StdRng) -> Walls {
+    let mut walls = vec![vec![true; cols as usize]; rows as usize];
+    let (maze_rows, maze_cols) = ((rows - 1) / 2, (cols - 1) / 2);
+    let mut visited = vec![vec![false; maze_cols as usize]; maze_rows as usize];
+
+    // passages that could join a new maze cell to the maze, as (new cell, maze cell)
+    let mut frontier: Vec<(Position, Position)> = vec![((0, 0), (0, 0))];
+    while !frontier.is_empty() {
+        let i = rng.gen_range(0, frontier.len());
+        let (cell, from) = frontier.swap_remove(i);
+        if visited[cell.0 as usize][cell.1 as usize] {
+            continue;
+        }
+        visited[cell.0 as usize][cell.1 as usize] = true;
+        carve(&mut walls, cell, Some(from));
+        for next in maze_neighbors(cell, maze_rows, maze_cols) {
+            frontier.push((next, cell));
+        }
+    }
+
+    walls
+}
+
+fn caves(rows: u32, cols: u32, density: f64, rng: &mut StdRng) -> Walls {
+    let fill = 0.3 + 0.2 * density;
+    let mut walls: Walls = (0..rows)
+        .map(|_| (0..cols).map(|_| rng.gen_bool(fill)).collect())
+        .collect();
+
+    // a cell becomes wall when most of its eight surrounding cells are wall
+    for _ in 0..4 {
+        walls = (0..rows)
+            .map(|row| {
+                (0..cols)
+                    .map(|col| {
+                        let mut count = 0;
+                        for d_row in [rows - 1, 0, 1].iter() {
+                            for d_col in [cols - 1, 0, 1].iter() {
+                                let (r, c) = ((row + d_row) % rows, (col + d_col) % cols);
+                                if (*d_row, *d_col) != (0, 0) && walls[r as usize][c as usize] {
+                                    count += 1;
+                                }
+                            }
+                        }
+                        count >= 5 || (count == 4 && walls[row as usize][col as usize])
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    walls
+}
+
+fn rooms(rows: u32, cols: u32, rng: &mut StdRng) -> Walls {
+    let mut walls = vec![vec![false; cols as usize]; rows as usize];
+    let (half_rows, half_cols) = (rows / 2, cols / 2);
+
+    // lay out a quarter of the board as a 2x2 block of rooms, then mirror it
+    let mut quarter = vec![vec![false; half_cols as usize]; half_rows as usize];
+    let (room_rows, room_cols) = (half_rows / 2, half_cols / 2);
+    for split in [room_rows, half_rows - 1].iter() {
+        let door = rng.gen_range(1, half_cols - 2);
+        for (col, cell) in (0..).zip(quarter[*split as usize].iter_mut()) {
+            *cell = col < door || col > door + 1;
+        }
+    }
+    for split in [room_cols, half_cols - 1].iter() {
+        let door = rng.gen_range(1, half_rows - 2);
+        for (row, cells) in (0..).zip(quarter.iter_mut()) {
+            if row < door || row > door + 1 {
+                cells[*split as usize] = true;
+            }
+        }
+    }
+
+    for (row, cells) in (0..).zip(quarter.iter()) {
+        for (col, wall) in (0..).zip(cells.iter()) {
+            for (r, c) in [
+                (row, col),
+                (rows - 1 - row, col),
+                (row, cols - 1 - col),
+                (rows - 1 - row, cols - 1 - col),
+            ]
+            .iter()
+            {
+                walls[*r as usize][*c as usize] = *wall;
+            }
+        }
+    }
+
+    walls
+}
+
+/// Knock out walls at random, keeping about `density` of them. Mirrored layouts stay mirrored
+fn thin(walls: &mut Walls, density: f64, mirrored: bool, rng: &mut StdRng) {
+    let (rows, cols) = (walls.len(), walls[0].len());
+    let (end_row, end_col) = match mirrored {
+        true => (rows.div_ceil(2), cols.div_ceil(2)),
+        false => (rows, cols),
+    };
+    for row in 0..end_row {
+        for col in 0..end_col {
+            if !walls[row][col] || rng.gen_bool(density) {
+                continue;
+            }
+            walls[row][col] = false;
+            if mirrored {
+                walls[rows - 1 - row][col] = false;
+                walls[row][cols - 1 - col] = false;
+                walls[rows - 1 - row][cols - 1 - col] = false;
+            }
+        }
+    }
+}
+
+/// Open up the area around every default player and enemy spawn point
+fn clear_spawns(walls: &mut Walls, mirrored: bool) {
+    let (rows, cols) = (walls.len() as u32, walls[0].len() as u32);
+    let points = (0..ROYALE_SNAKES)
+        .map(|i| spawn_point(i, rows, cols))
+        .chain((0..ENEMY_POINTS).map(|i| enemy_point(i, rows, cols)));
+    for (row, col) in points {
+        for d_row in 0..=2 * SPAWN_RADIUS {
+            for d_col in 0..=2 * SPAWN_RADIUS {
+                let r = (row + rows + d_row - SPAWN_RADIUS) % rows;
+                let c = (col + cols + d_col - SPAWN_RADIUS) % cols;
+                walls[r as usize][c as usize] = false;
+                if mirrored {
+                    walls[(rows - 1 - r) as usize][c as usize] = false;
+                    walls[r as usize][(cols - 1 - c) as usize] = false;
+                    walls[(rows - 1 - r) as usize][(cols - 1 - c) as usize] = false;
+                }
+            }
+        }
+    }
+}
+
+/// Label each floor cell with the number of its connected area, walls with `None`
+fn regions(walls: &[Vec<bool>], walker: &Snake) -> (Vec<Vec<Option<usize>>>, usize) {
+    let (rows, cols) = (walls.len() as u32, walls[0].len() as u32);
+    let mut labels = vec![vec![None; cols as usize]; rows as usize];
+    let mut count = 0;
+    for row in 0..rows {
+        for col in 0..cols {
+            if walls[row as usize][col as usize] || labels[row as usize][col as usize].is_some() {
+                continue;
+            }
+            labels[row as usize][col as usize] = Some(count);
+            let mut queue = VecDeque::from(vec![(row, col)]);
+            while let Some(cell) = queue.pop_front() {
+                for (r, c) in neighbors(cell, walker, rows, cols) {
+                    let label = &mut labels[r as usize][c as usize];
+                    if !walls[r as usize][c as usize] && label.is_none() {
+                        *label = Some(count);
+                        queue.push_back((r, c));
+                    }
+                }
+            }
+            count += 1;
+        }
+    }
+
+    (labels, count)
+}
+
+/// Tunnel between separate areas of floor until they're all connected across the edges of a
+/// board joined as `topology` says
+fn connect(walls: &mut Walls, topology: Topology) {
+    let (rows, cols) = (walls.len() as u32, walls[0].len() as u32);
+    let walker = Snake::new(0, 0, None).with_topology(topology);
+    loop {
+        let (labels, count) = regions(walls, &walker);
+        if count <= 1 {
+            return;
+        }
+
+        // search outward from area 0, through walls, for the nearest other area
+        let mut came_from = vec![vec![None; cols as usize]; rows as usize];
+        let mut queue: VecDeque<Position> = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .filter(|(row, col)| labels[*row as usize][*col as usize] == Some(0))
+            .collect();
+        for (row, col) in queue.iter() {
+            came_from[*row as usize][*col as usize] = Some((*row, *col));
+        }
+        let mut end = None;
+        'search: while let Some(cell) = queue.pop_front() {
+            for next in neighbors(cell, &walker, rows, cols) {
+                if came_from[next.0 as usize][next.1 as usize].is_some() {
+                    continue;
+                }
+                came_from[next.0 as usize][next.1 as usize] = Some(cell);
+                if labels[next.0 as usize][next.1 as usize].is_some() {
+                    end = Some(next);
+                    break 'search;
+                }
+                queue.push_back(next);
+            }
+        }
+
+        let mut cell = match end {
+            Some(cell) => cell,
+            None => return,
+        };
+        while let Some(previous) = came_from[cell.0 as usize][cell.1 as usize] {
+            if previous == cell {
+                break;
+            }
+            walls[cell.0 as usize][cell.1 as usize] = false;
+            cell = previous;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_are_playable() {
+        for topology in Topology::ALL.iter().copied() {
+            for algorithm in Algorithm::ALL.iter() {
+                for seed in 0..20 {
+                    for density in [0.0, 0.5, 1.0].iter() {
+                        let map = GeneratedMapper::new(*algorithm, 36, 36)
+                            .with_topology(topology)
+                            .with_seed(seed)
+                            .with_density(*density)
+                            .load_map()
+                            .unwrap();
+                        let problems = validate(&map, 36, 36, topology);
+                        assert!(
+                            problems.is_empty(),
+                            "{:?} {:?} {}: {:?}",
+                            topology,
+                            algorithm,
+                            seed,
+                            problems
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn works_on_any_board() {
+        for (algorithm, topology) in Algorithm::ALL.iter().zip(Topology::ALL.iter().copied()) {
+            let map = GeneratedMapper::new(*algorithm, 21, 40)
+                .with_topology(topology)
+                .with_seed(7)
+                .load_map()
+                .unwrap();
+            assert_eq!(map.size, Some((21, 40)));
+            assert!(validate(&map, 21, 40, topology).is_empty());
+        }
+        assert!(GeneratedMapper::new(Algorithm::Caves, 4, 4)
+            .load_map()
+            .is_err());
+    }
+
+    #[test]
+    fn seeds_repeat() {
+        for algorithm in Algorithm::ALL.iter() {
+            let generate = |seed| {
+                GeneratedMapper::new(*algorithm, 36, 36)
+                    .with_seed(seed)
+                    .load_map()
+                    .unwrap()
+            };
+            assert_eq!(generate(1), generate(1));
+            assert_ne!(generate(1).walls, generate(2).walls);
+        }
+    }
+
+    #[test]
+    fn density_sets_wall_count() {
+        let walls = |density| {
+            GeneratedMapper::new(Algorithm::Backtracker, 36, 36)
+                .with_seed(3)
+                .with_density(density)
+                .load_map()
+                .unwrap()
+                .walls
+                .len()
+        };
+        assert!(walls(1.0) > walls(0.5));
+        assert!(walls(0.5) > walls(0.1));
+    }
+
+    #[test]
+    fn spawns_are_clear() {
+        for algorithm in Algorithm::ALL.iter() {
+            let map = GeneratedMapper::new(*algorithm, 36, 36)
+                .with_seed(5)
+                .load_map()
+                .unwrap();
+            for i in 0..ROYALE_SNAKES {
+                assert!(!map.walls.contains(&spawn_point(i, 36, 36)));
+            }
+        }
+    }
+
+    #[test]
+    fn rooms_are_symmetric() {
+        let map = GeneratedMapper::new(Algorithm::Rooms, 36, 36)
+            .with_seed(9)
+            .load_map()
+            .unwrap();
+        for (row, col) in map.walls.iter() {
+            assert!(map.walls.contains(&(35 - row, 35 - col)));
+        }
+    }
+}
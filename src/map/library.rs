@@ -0,0 +1,163 @@
+use std::env;
+
+use super::*;
+
+/// Name of the directory holding the game's bundled maps and fonts
+const RESOURCE_DIR: &str = "resource";
+
+/// Returns the directory of bundled maps
+///
+/// This is the `resource` directory beside the executable when there is one, so the game finds
+/// its maps from any working directory, else `types::MAP_DIR`.
+pub fn resource_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(RESOURCE_DIR)))
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from(types::MAP_DIR))
+}
+
+/// Returns the per-user map directory, whether or not it exists yet
+///
+/// This is `rs_snake/maps` under `%APPDATA%` on Windows, else under `$XDG_DATA_HOME` or
+/// `~/.local/share`.
+pub fn user_map_dir() -> Option<PathBuf> {
+    let data_dir = match env::var_os("APPDATA") {
+        Some(dir) if cfg!(windows) => PathBuf::from(dir),
+        _ => match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+        },
+    };
+    Some(data_dir.join("rs_snake").join("maps"))
+}
+
+/// Every level found in a set of map directories, files and packs
+#[derive(Debug, Default)]
+pub struct MapLibrary {
+    /// Loose maps, sorted within each directory, then the levels of each pack in order
+    pub levels: Vec<Level>,
+    /// Packs and directories that couldn't be read
+    pub problems: Vec<MapError>,
+}
+
+impl MapLibrary {
+    /// Search the resource directory, the user's map directory, then each of `paths`
+    ///
+    /// Missing default directories are skipped. See `MapLibrary::scan`.
+    pub fn discover(paths: &[PathBuf]) -> Self {
+        let defaults = std::iter::once(resource_dir())
+            .chain(user_map_dir())
+            .filter(|dir| dir.is_dir());
+        let paths: Vec<PathBuf> = defaults.chain(paths.iter().cloned()).collect();
+        Self::scan(&paths)
+    }
+
+    /// Gather the levels in each path, in order, skipping paths already seen
+    ///
+    /// A directory holding a `pack.toml` is a pack. Any other directory contributes its map
+    /// files, then every pack among its subdirectories and .zip files. A .zip file is a pack, and
+    /// any other file a single map.
+    pub fn scan(paths: &[PathBuf]) -> Self {
+        let mut library = MapLibrary::default();
+        let mut seen: Vec<PathBuf> = vec![];
+        for path in paths {
+            let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            if seen.contains(&canonical) {
+                continue;
+            }
+            seen.push(canonical);
+            library.add(path);
+        }
+        library
+    }
+
+    fn add(&mut self, path: &Path) {
+        let is_zip = path.extension().and_then(|ext| ext.to_str()) == Some("zip");
+        if path.join(MANIFEST).is_file() || is_zip {
+            match MapPack::open(path) {
+                Ok(pack) => self.levels.extend(pack.levels),
+                Err(e) => self.problems.push(e),
+            }
+            return;
+        }
+        if !path.is_dir() {
+            self.levels.push(Level::file(path));
+            return;
+        }
+
+        match list_maps(path) {
+            Ok(maps) => self.levels.extend(maps.into_iter().map(Level::file)),
+            Err(e) => {
+                self.problems.push(e);
+                return;
+            }
+        }
+        let mut packs: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|entry| {
+                    entry.join(MANIFEST).is_file()
+                        || entry.extension().and_then(|ext| ext.to_str()) == Some("zip")
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        packs.sort();
+        for pack in packs {
+            self.add(&pack);
+        }
+    }
+
+    /// Returns a random level, if there are any
+    pub fn random(&self) -> Option<&Level> {
+        match self.levels.len() {
+            0 => None,
+            len => self.levels.get(rand::thread_rng().gen_range(0, len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_maps_and_packs() {
+        let library = MapLibrary::scan(&[PathBuf::from("./fixture")]);
+        assert!(library.problems.is_empty(), "{:?}", library.problems);
+
+        let loose: Vec<&Level> = library.levels.iter().filter(|l| l.pack.is_none()).collect();
+        assert_eq!(
+            loose.len(),
+            list_maps(Path::new("./fixture")).unwrap().len()
+        );
+        let packed: Vec<&Level> = library.levels.iter().filter(|l| l.pack.is_some()).collect();
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[0].name.as_deref(), Some("First"));
+        // packs follow loose maps
+        assert!(library.levels.last().unwrap().pack.is_some());
+    }
+
+    #[test]
+    fn takes_files_and_skips_repeats() {
+        let paths = vec![
+            PathBuf::from("./fixture/tiny.txt"),
+            PathBuf::from("./fixture/pack"),
+            PathBuf::from("fixture/tiny.txt"),
+        ];
+        let library = MapLibrary::scan(&paths);
+        assert_eq!(library.levels.len(), 3);
+        assert_eq!(library.levels[0], Level::file("./fixture/tiny.txt"));
+        assert!(library.random().unwrap().load().is_ok());
+        assert_eq!(MapLibrary::scan(&[]).random(), None);
+    }
+
+    #[test]
+    fn reports_broken_packs() {
+        let library = MapLibrary::scan(&[PathBuf::from("./fixture/tiny.png.zip")]);
+        assert!(library.levels.is_empty());
+        assert!(matches!(library.problems[..], [MapError::Io { .. }]));
+    }
+}
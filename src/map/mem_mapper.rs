@@ -4,7 +4,7 @@ use super::*;
 pub struct MemMapper {}
 
 impl Mapper for MemMapper {
-    fn load_map(&self) -> Result<WorldMap, Box<dyn Error>> {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
         let walls = vec![
             // top
             (10, 12),
@@ -48,9 +48,6 @@ impl Mapper for MemMapper {
             (22, 8),
         ];
 
-        Ok(WorldMap {
-            color: types::WALL_COLOR,
-            walls,
-        })
+        Ok(WorldMap::new(walls, types::WALL_COLOR))
     }
 }
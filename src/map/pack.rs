@@ -0,0 +1,301 @@
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use super::*;
+
+/// Name of the file describing a map pack
+pub const MANIFEST: &str = "pack.toml";
+
+/// Where a map file lives
+#[derive(Debug, PartialEq, Clone)]
+pub enum MapSource {
+    /// A file on disk
+    File(PathBuf),
+    /// A file inside a zip archive
+    Zipped { archive: PathBuf, entry: String },
+}
+
+impl MapSource {
+    /// Returns a path naming this map, for display and error messages
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Self::File(path) => path.clone(),
+            Self::Zipped { archive, entry } => archive.join(entry),
+        }
+    }
+
+    /// Load the map
+    pub fn load(&self) -> Result<WorldMap, MapError> {
+        match self {
+            Self::File(path) => load_file(path),
+            Self::Zipped { archive, entry } => {
+                let bytes = read_zipped(archive, entry)?;
+                parse_bytes(&self.path(), &bytes)
+            }
+        }
+    }
+}
+
+/// A map to play, with details from the pack it came in
+#[derive(Debug, PartialEq, Clone)]
+pub struct Level {
+    pub source: MapSource,
+    /// Name given by the pack, ahead of the map's own
+    pub name: Option<String>,
+    /// Name of the pack, if any
+    pub pack: Option<String>,
+    /// Author credited by the pack, unless the map credits its own
+    pub author: Option<String>,
+}
+
+impl Level {
+    /// A level that is just a map file, outside of any pack
+    pub fn file<P: Into<PathBuf>>(path: P) -> Self {
+        Level {
+            source: MapSource::File(path.into()),
+            name: None,
+            pack: None,
+            author: None,
+        }
+    }
+
+    /// Load the map, with the pack's name and author filled in
+    pub fn load(&self) -> Result<WorldMap, MapError> {
+        let mut world_map = self.source.load()?;
+        if self.name.is_some() {
+            world_map.meta.name = self.name.clone();
+        }
+        if world_map.meta.author.is_none() {
+            world_map.meta.author = self.author.clone();
+        }
+        Ok(world_map)
+    }
+}
+
+/// An ordered set of levels, from a directory or zip archive holding a `pack.toml` manifest
+///
+/// ```text
+/// name = "Starter Pack"
+/// author = "someone"
+///
+/// [[level]]
+/// file = "first.txt"
+/// name = "First Steps"
+///
+/// [[level]]
+/// file = "second.snek"
+/// ```
+///
+/// Level files are relative to the manifest and may be in any map format. A level's `name` is
+/// optional and replaces the map's own.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapPack {
+    pub name: String,
+    pub author: Option<String>,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    author: Option<String>,
+    #[serde(default)]
+    level: Vec<LevelEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelEntry {
+    file: String,
+    name: Option<String>,
+}
+
+impl MapPack {
+    /// Open a pack from a directory or .zip file
+    ///
+    /// Inside a zip, the manifest may sit at the top or in a single folder.
+    pub fn open(path: &Path) -> Result<Self, MapError> {
+        if path.is_dir() {
+            let manifest = path.join(MANIFEST);
+            let content = load_to_string(&manifest)?;
+            return Self::from_manifest(&manifest, &content, |file| {
+                MapSource::File(path.join(file))
+            });
+        }
+
+        let names = zip_entries(path)?;
+        let manifest = names
+            .iter()
+            .filter(|name| *name == MANIFEST || name.ends_with(&format!("/{}", MANIFEST)))
+            .min_by_key(|name| name.len())
+            .ok_or_else(|| MapError::Invalid {
+                path: path.to_path_buf(),
+                reason: format!("no {} in the archive", MANIFEST),
+            })?;
+        let folder = &manifest[..manifest.len() - MANIFEST.len()];
+        let bytes = read_zipped(path, manifest)?;
+        let content = String::from_utf8_lossy(&bytes);
+        Self::from_manifest(&path.join(manifest), &content, |file| MapSource::Zipped {
+            archive: path.to_path_buf(),
+            entry: format!("{}{}", folder, file),
+        })
+    }
+
+    /// Read a manifest, locating each level's file with `source`
+    fn from_manifest(
+        path: &Path,
+        content: &str,
+        source: impl Fn(&str) -> MapSource,
+    ) -> Result<Self, MapError> {
+        let manifest: Manifest = toml::from_str(content).map_err(|e| {
+            let (line, column) = e.line_col().unwrap_or((0, 0));
+            MapError::Parse {
+                path: path.to_path_buf(),
+                line: line + 1,
+                column: column + 1,
+                message: e.to_string(),
+            }
+        })?;
+        if manifest.level.is_empty() {
+            return Err(MapError::Invalid {
+                path: path.to_path_buf(),
+                reason: "pack has no levels".to_string(),
+            });
+        }
+
+        let Manifest {
+            name,
+            author,
+            level,
+        } = manifest;
+        let levels = level
+            .into_iter()
+            .map(|entry| Level {
+                source: source(&entry.file),
+                name: entry.name,
+                pack: Some(name.clone()),
+                author: author.clone(),
+            })
+            .collect();
+        Ok(MapPack {
+            name,
+            author,
+            levels,
+        })
+    }
+}
+
+/// Open a zip archive, turning any failure into a `MapError`
+fn open_zip(archive: &Path) -> Result<ZipArchive<File>, MapError> {
+    let file = File::open(archive).map_err(|source| MapError::Io {
+        path: archive.to_path_buf(),
+        source,
+    })?;
+    ZipArchive::new(file).map_err(|e| MapError::Invalid {
+        path: archive.to_path_buf(),
+        reason: format!("not a zip archive: {}", e),
+    })
+}
+
+/// List the files in a zip archive
+fn zip_entries(archive: &Path) -> Result<Vec<String>, MapError> {
+    Ok(open_zip(archive)?.file_names().map(String::from).collect())
+}
+
+/// Read one file out of a zip archive
+fn read_zipped(archive: &Path, entry: &str) -> Result<Vec<u8>, MapError> {
+    let mut zip = open_zip(archive)?;
+    let mut file = zip.by_name(entry).map_err(|e| MapError::Invalid {
+        path: archive.join(entry),
+        reason: e.to_string(),
+    })?;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes)
+        .map_err(|source| MapError::Io {
+            path: archive.join(entry),
+            source,
+        })?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::{write::FileOptions, ZipWriter};
+
+    const MANIFEST_TOML: &str = "name = \"Fixture Pack\"
+author = \"someone\"
+
+[[level]]
+file = \"tiny.txt\"
+name = \"First\"
+
+[[level]]
+file = \"tiny.snek\"
+";
+
+    #[test]
+    fn opens_directory() {
+        let pack = MapPack::open(Path::new("./fixture/pack")).unwrap();
+        assert_eq!(pack.name, "Fixture Pack");
+        assert_eq!(pack.levels.len(), 2);
+
+        let first = pack.levels[0].load().unwrap();
+        assert_eq!(first.meta.name.as_deref(), Some("First"));
+        assert_eq!(first.meta.author.as_deref(), Some("someone"));
+        let second = pack.levels[1].load().unwrap();
+        assert_eq!(second.meta.name.as_deref(), Some("Fixture"));
+    }
+
+    #[test]
+    fn opens_zip() {
+        let path = std::env::temp_dir().join("rs_snake_pack.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in [
+            ("pack/pack.toml", MANIFEST_TOML.to_string()),
+            (
+                "pack/tiny.txt",
+                fs::read_to_string("./fixture/tiny.txt").unwrap(),
+            ),
+            (
+                "pack/tiny.snek",
+                fs::read_to_string("./fixture/tiny.snek").unwrap(),
+            ),
+        ]
+        .iter()
+        {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let pack = MapPack::open(&path).unwrap();
+        assert_eq!(pack.author.as_deref(), Some("someone"));
+        assert_eq!(
+            pack.levels[0].source,
+            MapSource::Zipped {
+                archive: path.clone(),
+                entry: "pack/tiny.txt".to_string(),
+            }
+        );
+        let first = pack.levels[0].load().unwrap();
+        assert_eq!(first.food_spots, vec![(2, 2)]);
+        assert!(pack.levels[1].load().is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_manifest() {
+        let path = Path::new("pack.toml");
+        let source = |file: &str| MapSource::File(PathBuf::from(file));
+        assert!(matches!(
+            MapPack::from_manifest(path, "name = \"Empty\"", source),
+            Err(MapError::Invalid { .. })
+        ));
+        assert!(matches!(
+            MapPack::from_manifest(path, "name = \"Bad\"\nlevels = 3", source),
+            Err(MapError::Parse { .. })
+        ));
+    }
+}
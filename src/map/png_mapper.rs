@@ -0,0 +1,376 @@
+use super::*;
+use crate::types::{Portal, Spawn, Tile, PORTAL_COLORS};
+
+/// What a pixel of a PNG map stands for
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Legend {
+    /// A kind of terrain
+    Tile(Tile),
+    /// Floor where a player starts
+    PlayerSpawn,
+    /// Floor where an enemy starts
+    EnemySpawn,
+    /// Floor where food always appears
+    Food,
+    /// One end of the portal pair with this number. Two pixels make a pair
+    Portal(usize),
+}
+
+/// Maps pixel colors to what they stand for
+#[derive(Debug, PartialEq, Clone)]
+pub struct Palette {
+    colors: Vec<([u8; 3], Legend)>,
+}
+
+impl Default for Palette {
+    /// White floor, black walls, grey rock, green player spawns, red enemy spawns, yellow food,
+    /// and ice, conveyors, gates, bricks and portal pairs in the colors the game shows them in
+    fn default() -> Self {
+        let mut colors = vec![
+            ([255, 255, 255], Legend::Tile(Tile::Floor)),
+            ([0, 0, 0], Legend::Tile(Tile::Wall)),
+            ([128, 128, 128], Legend::Tile(Tile::Rock)),
+            ([0, 255, 0], Legend::PlayerSpawn),
+            ([255, 0, 0], Legend::EnemySpawn),
+            ([255, 255, 0], Legend::Food),
+        ];
+        let terrain = Tile::ALL
+            .iter()
+            .filter(|tile| !matches!(tile, Tile::Floor | Tile::Wall | Tile::Rock));
+        for tile in terrain {
+            let color = tile.color();
+            colors.push(([color.r, color.g, color.b], Legend::Tile(*tile)));
+        }
+        for (pair, color) in PORTAL_COLORS.iter().enumerate() {
+            colors.push(([color.r, color.g, color.b], Legend::Portal(pair)));
+        }
+        Palette { colors }
+    }
+}
+
+impl Palette {
+    /// Make `rgb` stand for `legend`, replacing whatever it stood for before
+    pub fn with(mut self, rgb: [u8; 3], legend: Legend) -> Self {
+        self.colors.retain(|(color, _)| *color != rgb);
+        self.colors.push((rgb, legend));
+        self
+    }
+
+    /// Returns what a color stands for, if anything
+    pub fn lookup(&self, rgb: [u8; 3]) -> Option<Legend> {
+        self.colors
+            .iter()
+            .find(|(color, _)| *color == rgb)
+            .map(|(_, legend)| *legend)
+    }
+
+    /// Returns the first color standing for `legend`, if any
+    pub fn color(&self, legend: Legend) -> Option<[u8; 3]> {
+        self.colors
+            .iter()
+            .find(|(_, l)| *l == legend)
+            .map(|(color, _)| *color)
+    }
+}
+
+/// A `Mapper` implementation backed by a PNG image, one pixel per cell
+///
+/// Pixel colors are looked up in a `Palette`. Fully transparent pixels are floor. Spawns face
+/// toward the middle of the board.
+pub struct PngMapper {
+    file: PathBuf,
+    palette: Palette,
+}
+
+impl PngMapper {
+    /// Create a `PngMapper` that loads the given file with the default `Palette`
+    pub fn new<P: Into<PathBuf>>(file: P) -> Self {
+        PngMapper {
+            file: file.into(),
+            palette: Palette::default(),
+        }
+    }
+
+    /// Use a different `Palette`
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Mapper for PngMapper {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let bytes = fs::read(&self.file).map_err(|source| MapError::Io {
+            path: self.file.clone(),
+            source,
+        })?;
+        checked(&self.file, parse_png(&self.file, &bytes, &self.palette)?)
+    }
+}
+
+/// Parse the bytes of a PNG map read from `path`
+///
+/// Parse errors give the offending pixel's row as the line and its column as the column.
+pub fn parse_png(path: &Path, bytes: &[u8], palette: &Palette) -> Result<WorldMap, MapError> {
+    let invalid = |reason: String| MapError::Invalid {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| invalid(format!("not a PNG image: {}", e)))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut pixels)
+        .map_err(|e| invalid(format!("unreadable PNG image: {}", e)))?;
+
+    let channels = info.color_type.samples();
+    let (rows, cols) = (info.height, info.width);
+    let mut world_map = WorldMap::new(vec![], types::WALL_COLOR);
+    let mut portal_ends: Vec<(usize, Position)> = vec![];
+    for (row, line) in (0..rows).zip(pixels.chunks(info.line_size)) {
+        for (col, pixel) in (0..cols).zip(line.chunks(channels)) {
+            let (rgb, alpha) = match *pixel {
+                [grey] => ([grey; 3], 255),
+                [grey, alpha] => ([grey; 3], alpha),
+                [r, g, b] => ([r, g, b], 255),
+                [r, g, b, alpha] => ([r, g, b], alpha),
+                _ => return Err(invalid("unsupported PNG color type".to_string())),
+            };
+            let legend = match alpha {
+                0 => Legend::Tile(Tile::Floor),
+                _ => palette.lookup(rgb).ok_or_else(|| MapError::Parse {
+                    path: path.to_path_buf(),
+                    line: row as usize + 1,
+                    column: col as usize + 1,
+                    message: format!(
+                        "color #{:02x}{:02x}{:02x} is not in the palette",
+                        rgb[0], rgb[1], rgb[2]
+                    ),
+                })?,
+            };
+
+            let position = (row, col);
+            let spawn = Spawn {
+                position,
+                direction: facing(row, rows),
+            };
+            match legend {
+                Legend::PlayerSpawn => world_map.player_spawns.push(spawn),
+                Legend::EnemySpawn => world_map.enemy_spawns.push(spawn),
+                Legend::Food => world_map.food_spots.push(position),
+                Legend::Portal(pair) => portal_ends.push((pair, position)),
+                Legend::Tile(tile) => world_map.place(position, tile),
+            }
+        }
+    }
+
+    portal_ends.sort_by_key(|(pair, _)| *pair);
+    for ends in portal_ends.chunk_by(|a, b| a.0 == b.0) {
+        match ends {
+            [(_, a), (_, b)] => world_map.portals.push(Portal::new(*a, *b)),
+            _ => {
+                return Err(invalid(format!(
+                    "portal {} has {} ends, expected 2",
+                    ends[0].0,
+                    ends.len()
+                )))
+            }
+        }
+    }
+
+    Ok(WorldMap {
+        size: Some((rows, cols)),
+        ..world_map
+    })
+}
+
+/// Draw a `WorldMap` of `rows` x `cols` cells as a PNG, each cell `scale` pixels square
+///
+/// At a scale of 1 the image loads back as the same map, less spawn and portal exit directions.
+/// Anything the palette has no color for, such as a portal pair past the palette's last, is
+/// drawn as the terrain beneath it, or black failing that.
+pub fn to_png(
+    world_map: &WorldMap,
+    rows: u32,
+    cols: u32,
+    palette: &Palette,
+    scale: u32,
+) -> Vec<u8> {
+    let mut legends = vec![vec![Legend::Tile(Tile::Floor); cols as usize]; rows as usize];
+    let mut draw = |(row, col): Position, legend: Legend| {
+        if row < rows && col < cols {
+            legends[row as usize][col as usize] = legend;
+        }
+    };
+    for wall in world_map.walls.iter() {
+        draw(*wall, Legend::Tile(Tile::Wall));
+    }
+    for (position, tile) in world_map.tiles.iter() {
+        draw(*position, Legend::Tile(*tile));
+    }
+    for spot in world_map.food_spots.iter() {
+        draw(*spot, Legend::Food);
+    }
+    for (pair, portal) in world_map.portals.iter().enumerate() {
+        for end in portal.ends.iter() {
+            draw(end.position, Legend::Portal(pair));
+        }
+    }
+    for spawn in world_map.enemy_spawns.iter() {
+        draw(spawn.position, Legend::EnemySpawn);
+    }
+    for spawn in world_map.player_spawns.iter() {
+        draw(spawn.position, Legend::PlayerSpawn);
+    }
+
+    let mut data = vec![];
+    for (row, line) in (0..).zip(legends.iter()) {
+        let mut pixels = vec![];
+        for (col, legend) in (0..).zip(line.iter()) {
+            let rgb = palette
+                .color(*legend)
+                .or_else(|| palette.color(Legend::Tile(world_map.tile_at((row, col)))))
+                .unwrap_or([0, 0, 0]);
+            for _ in 0..scale {
+                pixels.extend_from_slice(&rgb);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&pixels);
+        }
+    }
+
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, cols * scale, rows * scale);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // writing to memory can only fail on a size mismatch, which the loops above rule out
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .expect("PNG encoding failed");
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const BLACK: [u8; 3] = [0, 0, 0];
+
+    /// Encode rows of RGB pixels as a PNG
+    fn encode(pixels: &[Vec<[u8; 3]>]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder =
+            png::Encoder::new(&mut bytes, pixels[0].len() as u32, pixels.len() as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = pixels.iter().flatten().flatten().copied().collect();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&data)
+            .unwrap();
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<WorldMap, MapError> {
+        parse_png(Path::new("test.png"), bytes, &Palette::default())
+    }
+
+    #[test]
+    fn parses_pixels() {
+        let bytes = encode(&[
+            vec![BLACK, WHITE, [0, 255, 0]],
+            vec![[128, 128, 128], [255, 255, 0], [255, 0, 0]],
+        ]);
+        let map = parse(&bytes).unwrap();
+        assert_eq!(map.size, Some((2, 3)));
+        assert_eq!(map.walls, vec![(0, 0), (1, 0)]);
+        assert_eq!(map.tiles, vec![((1, 0), Tile::Rock)]);
+        assert_eq!(map.player_spawns[0].position, (0, 2));
+        assert_eq!(map.enemy_spawns[0].position, (1, 2));
+        assert_eq!(map.food_spots, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn supports_any_shape() {
+        let bytes = encode(&vec![vec![WHITE, BLACK]; 5]);
+        let map = parse(&bytes).unwrap();
+        assert_eq!(map.size, Some((5, 2)));
+        assert_eq!(map.walls.len(), 5);
+        assert!(map.walls.iter().all(|(_, col)| *col == 1));
+    }
+
+    #[test]
+    fn rejects_unknown_colors() {
+        let bytes = encode(&[vec![WHITE, WHITE], vec![WHITE, [1, 2, 3]]]);
+        match parse(&bytes) {
+            Err(MapError::Parse {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (2, 2));
+                assert!(message.contains("#010203"), "{}", message);
+            }
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uses_custom_palette() {
+        let bytes = encode(&[vec![[1, 2, 3], WHITE]]);
+        let palette = Palette::default().with([1, 2, 3], Legend::Tile(Tile::Wall));
+        let map = parse_png(Path::new("test.png"), &bytes, &palette).unwrap();
+        assert_eq!(map.walls, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let map = PngMapper::new("./fixture/tiny.png").load_map().unwrap();
+        let bytes = to_png(&map, 3, 4, &Palette::default(), 1);
+        assert_eq!(parse(&bytes).unwrap(), map);
+
+        let scaled = parse(&to_png(&map, 3, 4, &Palette::default(), 2)).unwrap();
+        assert_eq!(scaled.size, Some((6, 8)));
+        assert_eq!(scaled.walls.len(), 8);
+    }
+
+    #[test]
+    fn pairs_portal_pixels() {
+        let blue = Palette::default().color(Legend::Portal(0)).unwrap();
+        let orange = Palette::default().color(Legend::Portal(1)).unwrap();
+        let bytes = encode(&[vec![orange, blue, WHITE], vec![WHITE, blue, orange]]);
+        let map = parse(&bytes).unwrap();
+        assert_eq!(
+            map.portals,
+            vec![Portal::new((0, 1), (1, 1)), Portal::new((0, 0), (1, 2))]
+        );
+        assert_eq!(
+            parse(&to_png(&map, 2, 3, &Palette::default(), 1)).unwrap(),
+            map
+        );
+
+        let bytes = encode(&[vec![blue, blue, blue]]);
+        assert!(matches!(parse(&bytes), Err(MapError::Invalid { .. })));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(parse(b"#..#"), Err(MapError::Invalid { .. })));
+    }
+
+    #[test]
+    fn loads_file() {
+        let map = PngMapper::new("./fixture/tiny.png").load_map().unwrap();
+        assert_eq!(map.size, Some((3, 4)));
+        assert_eq!(map.walls, vec![(1, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,863 @@
+use serde::Deserialize;
+
+use super::*;
+use crate::types::{
+    Direction, Door, Key, KeyColor, MapMeta, Motion, Obstacle, Portal, PortalEnd, Spawn, Switch,
+    SwitchWall, Tile, Zone,
+};
+
+/// Version of the .snek map format this build understands
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Line separating a .snek file's header from its grid
+const SEPARATOR: &str = "---";
+
+/// A `Mapper` implementation backed by a .snek file
+///
+/// A .snek file is a TOML header describing the map, a line holding `---`, then the map drawn
+/// one character per cell:
+///
+/// ```text
+/// version = 1
+/// name = "Tiny"
+/// rows = 2
+/// cols = 3
+///
+/// [[player]]
+/// row = 0
+/// col = 0
+/// direction = "right"
+/// ---
+/// ..#
+/// o..
+/// ```
+///
+/// Optional header fields are `author`, `par`, `[[enemy]]` spawns laid out like `[[player]]`,
+/// `[[food_zone]]` rectangles with `row`, `col`, `rows` and `cols`, and `[[portal]]` pairs:
+///
+/// ```text
+/// [[portal]]
+/// a = { row = 0, col = 1 }
+/// b = { row = 1, col = 2, exit = "up" }
+/// ```
+///
+/// A portal end's `exit` is optional. Obstacles are `[[mover]]` blocks stepping along a looping
+/// `path` every `period` ticks, `[[toggle]]` walls solid for `on` ticks then open for `off`, and
+/// `[[crusher]]`s, toggles that crush players caught beneath them. Each may start `offset` ticks
+/// into its period, and a mover may crush too:
+///
+/// ```text
+/// [[mover]]
+/// path = [{ row = 0, col = 0 }, { row = 0, col = 1 }]
+/// period = 2
+/// crush = true
+///
+/// [[crusher]]
+/// cells = [{ row = 1, col = 0 }, { row = 1, col = 1 }]
+/// on = 2
+/// off = 6
+/// offset = 3
+/// ```
+///
+/// Puzzles are built from `[[key]]`s lying at a `row` and `col`, `[[door]]`s over a list of
+/// `cells` that open for players holding a key of the same `color`, and `[[switch]]`es that open
+/// and shut every `[[switch_wall]]` sharing their `group`. Switch walls start shut unless `open`:
+///
+/// ```text
+/// [[key]]
+/// row = 0
+/// col = 1
+/// color = "red"
+///
+/// [[door]]
+/// cells = [{ row = 1, col = 1 }]
+/// color = "red"
+///
+/// [[switch]]
+/// row = 0
+/// col = 2
+/// group = 1
+///
+/// [[switch_wall]]
+/// cells = [{ row = 1, col = 2 }]
+/// group = 1
+/// ```
+///
+/// See `Tile` for the grid characters.
+pub struct SnekMapper {
+    file: PathBuf,
+}
+
+impl SnekMapper {
+    /// Create a `SnekMapper` that loads the given file
+    pub fn new<P: Into<PathBuf>>(file: P) -> Self {
+        SnekMapper { file: file.into() }
+    }
+}
+
+impl Mapper for SnekMapper {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let content = load_to_string(&self.file)?;
+        checked(&self.file, parse_snek(&self.file, &content)?)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    version: u32,
+    name: Option<String>,
+    author: Option<String>,
+    rows: u32,
+    cols: u32,
+    par: Option<usize>,
+    #[serde(default)]
+    player: Vec<SpawnEntry>,
+    #[serde(default)]
+    enemy: Vec<SpawnEntry>,
+    #[serde(default)]
+    food_zone: Vec<ZoneEntry>,
+    #[serde(default)]
+    portal: Vec<PortalEntry>,
+    #[serde(default)]
+    mover: Vec<MoverEntry>,
+    #[serde(default)]
+    toggle: Vec<TimerEntry>,
+    #[serde(default)]
+    crusher: Vec<TimerEntry>,
+    #[serde(default)]
+    key: Vec<KeyEntry>,
+    #[serde(default)]
+    door: Vec<DoorEntry>,
+    #[serde(default)]
+    switch: Vec<SwitchEntry>,
+    #[serde(default)]
+    switch_wall: Vec<SwitchWallEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpawnEntry {
+    row: u32,
+    col: u32,
+    direction: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZoneEntry {
+    row: u32,
+    col: u32,
+    rows: u32,
+    cols: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortalEntry {
+    a: PortalEndEntry,
+    b: PortalEndEntry,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortalEndEntry {
+    row: u32,
+    col: u32,
+    exit: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CellEntry {
+    row: u32,
+    col: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MoverEntry {
+    path: Vec<CellEntry>,
+    period: u32,
+    #[serde(default)]
+    offset: u32,
+    #[serde(default)]
+    crush: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimerEntry {
+    cells: Vec<CellEntry>,
+    on: u32,
+    off: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyEntry {
+    row: u32,
+    col: u32,
+    color: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DoorEntry {
+    cells: Vec<CellEntry>,
+    color: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SwitchEntry {
+    row: u32,
+    col: u32,
+    group: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SwitchWallEntry {
+    cells: Vec<CellEntry>,
+    group: u32,
+    #[serde(default)]
+    open: bool,
+}
+
+/// Parse the contents of a .snek file read from `path`
+pub fn parse_snek(path: &Path, s: &str) -> Result<WorldMap, MapError> {
+    let invalid = |reason: String| MapError::Invalid {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let lines: Vec<&str> = s.lines().collect();
+    let separator = lines
+        .iter()
+        .position(|line| line.trim() == SEPARATOR)
+        .ok_or_else(|| invalid(format!("missing '{}' before the map grid", SEPARATOR)))?;
+
+    let header: Header = toml::from_str(&lines[..separator].join("\n")).map_err(|e| {
+        let (line, column) = e.line_col().unwrap_or((0, 0));
+        MapError::Parse {
+            path: path.to_path_buf(),
+            line: line + 1,
+            column: column + 1,
+            message: e.to_string(),
+        }
+    })?;
+    if header.version != FORMAT_VERSION {
+        return Err(invalid(format!(
+            "unsupported map version {}, expected {}",
+            header.version, FORMAT_VERSION
+        )));
+    }
+
+    // grid rows begin on the line after the separator
+    let first_line = separator + 2;
+    let grid_lines: Vec<&str> = lines[separator + 1..]
+        .iter()
+        .copied()
+        .take_while(|line| !line.trim().is_empty())
+        .collect();
+    if grid_lines.len() != header.rows as usize {
+        return Err(invalid(format!(
+            "header declares {} rows, grid has {}",
+            header.rows,
+            grid_lines.len()
+        )));
+    }
+
+    let mut world_map = WorldMap::new(vec![], types::WALL_COLOR);
+    for (row, line) in (0..).zip(grid_lines.iter()) {
+        let parse_error = |column, message| MapError::Parse {
+            path: path.to_path_buf(),
+            line: first_line + row as usize,
+            column,
+            message,
+        };
+        let width = line.chars().count();
+        if width != header.cols as usize {
+            let message = format!("expected {} columns, found {}", header.cols, width);
+            return Err(parse_error(
+                std::cmp::min(width, header.cols as usize) + 1,
+                message,
+            ));
+        }
+
+        for (col, symbol) in (0..).zip(line.chars()) {
+            let tile = Tile::from_symbol(symbol).ok_or_else(|| {
+                parse_error(col as usize + 1, format!("unknown tile '{}'", symbol))
+            })?;
+            world_map.place((row, col), tile);
+        }
+    }
+
+    let spawns = |entries: &[SpawnEntry], kind: &str| {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let position = (entry.row, entry.col);
+                if entry.row >= header.rows || entry.col >= header.cols {
+                    return Err(invalid(format!("{} spawn {} is off the map", kind, i + 1)));
+                }
+                if world_map.walls.contains(&position) {
+                    return Err(invalid(format!(
+                        "{} spawn {} is inside a wall",
+                        kind,
+                        i + 1
+                    )));
+                }
+                let direction = match entry.direction.as_deref() {
+                    None => Direction::Down,
+                    Some(name) => parse_direction(name)
+                        .ok_or_else(|| invalid(format!("unknown direction '{}'", name)))?,
+                };
+                Ok(Spawn {
+                    position,
+                    direction,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let player_spawns = spawns(&header.player, "player")?;
+    let enemy_spawns = spawns(&header.enemy, "enemy")?;
+
+    let mut food_zones = vec![];
+    for (i, entry) in header.food_zone.iter().enumerate() {
+        if entry.row + entry.rows > header.rows || entry.col + entry.cols > header.cols {
+            return Err(invalid(format!("food zone {} extends off the map", i + 1)));
+        }
+        food_zones.push(Zone {
+            position: (entry.row, entry.col),
+            rows: entry.rows,
+            cols: entry.cols,
+        });
+    }
+
+    let mut portals = vec![];
+    for (i, entry) in header.portal.iter().enumerate() {
+        let mut ends = [entry.a.position(), entry.b.position()];
+        for (end, e) in ends.iter_mut().zip([&entry.a, &entry.b].iter()) {
+            if e.row >= header.rows || e.col >= header.cols {
+                return Err(invalid(format!("portal {} is off the map", i + 1)));
+            }
+            if let Some(name) = e.exit.as_deref() {
+                end.exit = Some(
+                    parse_direction(name)
+                        .ok_or_else(|| invalid(format!("unknown direction '{}'", name)))?,
+                );
+            }
+        }
+        portals.push(Portal { ends });
+    }
+
+    let checked_obstacle = |kind: &str, i: usize, obstacle: Obstacle| {
+        let off_map = |(row, col): &Position| *row >= header.rows || *col >= header.cols;
+        if obstacle.cells().is_empty() || obstacle.cells().iter().any(off_map) {
+            return Err(invalid(format!("{} {} is off the map", kind, i + 1)));
+        }
+        let stalled = match obstacle.motion {
+            Motion::Path(_) => obstacle.period == 0,
+            Motion::Timer { on, .. } => on == 0 || on == obstacle.period,
+        };
+        match stalled {
+            true => Err(invalid(format!("{} {} never moves", kind, i + 1))),
+            false => Ok(obstacle),
+        }
+    };
+    let mut obstacles = vec![];
+    for (i, entry) in header.mover.iter().enumerate() {
+        let obstacle = Obstacle {
+            crush: entry.crush,
+            ..Obstacle::mover(cells(&entry.path), entry.period).with_offset(entry.offset)
+        };
+        obstacles.push(checked_obstacle("mover", i, obstacle)?);
+    }
+    for (kind, entries) in [("toggle", &header.toggle), ("crusher", &header.crusher)] {
+        for (i, entry) in entries.iter().enumerate() {
+            let obstacle = Obstacle {
+                crush: kind == "crusher",
+                ..Obstacle::toggle(cells(&entry.cells), entry.on, entry.off)
+                    .with_offset(entry.offset)
+            };
+            obstacles.push(checked_obstacle(kind, i, obstacle)?);
+        }
+    }
+
+    let off_map = |kind: &str, i: usize, cells: &[Position]| match cells.is_empty()
+        || cells
+            .iter()
+            .any(|(row, col)| *row >= header.rows || *col >= header.cols)
+    {
+        true => Err(invalid(format!("{} {} is off the map", kind, i + 1))),
+        false => Ok(()),
+    };
+    let key_color = |name: &str| {
+        KeyColor::from_name(name).ok_or_else(|| invalid(format!("unknown key color '{}'", name)))
+    };
+    let mut keys = vec![];
+    for (i, entry) in header.key.iter().enumerate() {
+        off_map("key", i, &[(entry.row, entry.col)])?;
+        keys.push(Key {
+            position: (entry.row, entry.col),
+            color: key_color(&entry.color)?,
+        });
+    }
+    let mut doors = vec![];
+    for (i, entry) in header.door.iter().enumerate() {
+        off_map("door", i, &cells(&entry.cells))?;
+        doors.push(Door {
+            cells: cells(&entry.cells),
+            color: key_color(&entry.color)?,
+        });
+    }
+    let mut switches = vec![];
+    for (i, entry) in header.switch.iter().enumerate() {
+        off_map("switch", i, &[(entry.row, entry.col)])?;
+        if !header
+            .switch_wall
+            .iter()
+            .any(|wall| wall.group == entry.group)
+        {
+            return Err(invalid(format!("switch {} has no walls to switch", i + 1)));
+        }
+        switches.push(Switch {
+            position: (entry.row, entry.col),
+            group: entry.group,
+        });
+    }
+    let mut switch_walls = vec![];
+    for (i, entry) in header.switch_wall.iter().enumerate() {
+        off_map("switch wall", i, &cells(&entry.cells))?;
+        switch_walls.push(SwitchWall {
+            cells: cells(&entry.cells),
+            group: entry.group,
+            open: entry.open,
+        });
+    }
+
+    Ok(WorldMap {
+        size: Some((header.rows, header.cols)),
+        meta: MapMeta {
+            name: header.name,
+            author: header.author,
+            par: header.par,
+        },
+        player_spawns,
+        enemy_spawns,
+        food_zones,
+        portals,
+        obstacles,
+        keys,
+        doors,
+        switches,
+        switch_walls,
+        ..world_map
+    })
+}
+
+/// Returns the positions of a list of cells
+fn cells(entries: &[CellEntry]) -> Vec<Position> {
+    entries.iter().map(|entry| (entry.row, entry.col)).collect()
+}
+
+impl PortalEndEntry {
+    /// Returns the end without its exit direction
+    fn position(&self) -> PortalEnd {
+        PortalEnd {
+            position: (self.row, self.col),
+            exit: None,
+        }
+    }
+}
+
+/// Write a `WorldMap` as a .snek file of `rows` x `cols` cells
+///
+/// Food spots have no place in the format and are left out, as is anything outside the board.
+pub fn to_snek(world_map: &WorldMap, rows: u32, cols: u32) -> String {
+    let quoted = |s: &String| toml::Value::String(s.clone()).to_string();
+    let mut out = format!("version = {}\n", FORMAT_VERSION);
+    if let Some(name) = &world_map.meta.name {
+        out += &format!("name = {}\n", quoted(name));
+    }
+    if let Some(author) = &world_map.meta.author {
+        out += &format!("author = {}\n", quoted(author));
+    }
+    out += &format!("rows = {}\ncols = {}\n", rows, cols);
+    if let Some(par) = world_map.meta.par {
+        out += &format!("par = {}\n", par);
+    }
+
+    let in_bounds = |(row, col): Position| row < rows && col < cols;
+    let spawns = world_map
+        .player_spawns
+        .iter()
+        .map(|spawn| ("player", spawn))
+        .chain(world_map.enemy_spawns.iter().map(|spawn| ("enemy", spawn)));
+    for (kind, spawn) in spawns.filter(|(_, spawn)| in_bounds(spawn.position)) {
+        out += &format!(
+            "\n[[{}]]\nrow = {}\ncol = {}\ndirection = \"{}\"\n",
+            kind,
+            spawn.position.0,
+            spawn.position.1,
+            direction_name(spawn.direction)
+        );
+    }
+    for zone in world_map.food_zones.iter() {
+        let (row, col) = zone.position;
+        if row + zone.rows <= rows && col + zone.cols <= cols {
+            out += &format!(
+                "\n[[food_zone]]\nrow = {}\ncol = {}\nrows = {}\ncols = {}\n",
+                row, col, zone.rows, zone.cols
+            );
+        }
+    }
+
+    let ends_in_bounds = |portal: &&Portal| portal.ends.iter().all(|end| in_bounds(end.position));
+    for portal in world_map.portals.iter().filter(ends_in_bounds) {
+        out += "\n[[portal]]\n";
+        for (name, end) in ["a", "b"].iter().zip(portal.ends.iter()) {
+            let (row, col) = end.position;
+            out += &format!("{} = {{ row = {}, col = {}", name, row, col);
+            if let Some(exit) = end.exit {
+                out += &format!(", exit = \"{}\"", direction_name(exit));
+            }
+            out += " }\n";
+        }
+    }
+
+    let cell_list = |cells: &[Position]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .map(|(row, col)| format!("{{ row = {}, col = {} }}", row, col))
+            .collect();
+        format!("[{}]", cells.join(", "))
+    };
+    let obstacles = world_map
+        .obstacles
+        .iter()
+        .filter(|obstacle| obstacle.cells().iter().all(|cell| in_bounds(*cell)));
+    let (movers, timers): (Vec<&Obstacle>, Vec<&Obstacle>) =
+        obstacles.partition(|obstacle| matches!(obstacle.motion, Motion::Path(_)));
+    for mover in movers {
+        out += &format!(
+            "\n[[mover]]\npath = {}\nperiod = {}\n",
+            cell_list(mover.cells()),
+            mover.period
+        );
+        if mover.crush {
+            out += "crush = true\n";
+        }
+        if mover.offset > 0 {
+            out += &format!("offset = {}\n", mover.offset);
+        }
+    }
+    let (crushers, toggles): (Vec<&Obstacle>, Vec<&Obstacle>) =
+        timers.into_iter().partition(|timer| timer.crush);
+    let timers = toggles
+        .into_iter()
+        .map(|timer| ("toggle", timer))
+        .chain(crushers.into_iter().map(|timer| ("crusher", timer)));
+    for (kind, timer) in timers {
+        if let Motion::Timer { cells, on } = &timer.motion {
+            out += &format!(
+                "\n[[{}]]\ncells = {}\non = {}\noff = {}\n",
+                kind,
+                cell_list(cells),
+                on,
+                timer.period - on
+            );
+        }
+        if timer.offset > 0 {
+            out += &format!("offset = {}\n", timer.offset);
+        }
+    }
+
+    for key in world_map.keys.iter().filter(|key| in_bounds(key.position)) {
+        let (row, col) = key.position;
+        out += &format!(
+            "\n[[key]]\nrow = {}\ncol = {}\ncolor = \"{}\"\n",
+            row,
+            col,
+            key.color.name()
+        );
+    }
+    let all_in_bounds = |cells: &[Position]| cells.iter().all(|cell| in_bounds(*cell));
+    for door in world_map
+        .doors
+        .iter()
+        .filter(|door| all_in_bounds(&door.cells))
+    {
+        out += &format!(
+            "\n[[door]]\ncells = {}\ncolor = \"{}\"\n",
+            cell_list(&door.cells),
+            door.color.name()
+        );
+    }
+    let switches = world_map
+        .switches
+        .iter()
+        .filter(|switch| in_bounds(switch.position));
+    for switch in switches {
+        let (row, col) = switch.position;
+        out += &format!(
+            "\n[[switch]]\nrow = {}\ncol = {}\ngroup = {}\n",
+            row, col, switch.group
+        );
+    }
+    for wall in world_map
+        .switch_walls
+        .iter()
+        .filter(|wall| all_in_bounds(&wall.cells))
+    {
+        out += &format!(
+            "\n[[switch_wall]]\ncells = {}\ngroup = {}\n",
+            cell_list(&wall.cells),
+            wall.group
+        );
+        if wall.open {
+            out += "open = true\n";
+        }
+    }
+
+    out += SEPARATOR;
+    out += "\n";
+    for row in 0..rows {
+        let line: String = (0..cols)
+            .map(|col| world_map.tile_at((row, col)).symbol())
+            .collect();
+        out += &line;
+        out += "\n";
+    }
+    out
+}
+
+/// Returns the name a direction is written as in a .snek header
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TINY: &str = "version = 1
+name = \"Tiny\"
+author = \"someone\"
+rows = 2
+cols = 3
+par = 4
+
+[[player]]
+row = 0
+col = 0
+direction = \"right\"
+
+[[enemy]]
+row = 1
+col = 2
+
+[[food_zone]]
+row = 0
+col = 1
+rows = 2
+cols = 1
+---
+..#
+o..
+";
+
+    fn parse(s: &str) -> Result<WorldMap, MapError> {
+        parse_snek(Path::new("test.snek"), s)
+    }
+
+    #[test]
+    fn parses_header_and_grid() {
+        let map = parse(TINY).unwrap();
+        assert_eq!(map.meta.name.as_deref(), Some("Tiny"));
+        assert_eq!(map.meta.author.as_deref(), Some("someone"));
+        assert_eq!(map.meta.par, Some(4));
+        assert_eq!(map.size, Some((2, 3)));
+        assert_eq!(map.walls, vec![(0, 2), (1, 0)]);
+        assert_eq!(map.tiles, vec![((1, 0), Tile::Rock)]);
+        assert_eq!(
+            map.player_spawns,
+            vec![Spawn {
+                position: (0, 0),
+                direction: Direction::Right,
+            }]
+        );
+        assert_eq!(map.enemy_spawns[0].position, (1, 2));
+        assert_eq!(map.enemy_spawns[0].direction, Direction::Down);
+        assert!(map.food_zones[0].contains((1, 1)));
+    }
+
+    #[test]
+    fn reports_bad_tile_location() {
+        let s = TINY.replace("o..", "o.?");
+        match parse(&s) {
+            Err(MapError::Parse { line, column, .. }) => assert_eq!((line, column), (24, 3)),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_header_errors() {
+        let s = TINY.replace("rows = 2", "rows = two");
+        match parse(&s) {
+            Err(MapError::Parse { line, .. }) => assert_eq!(line, 4),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_dimensions() {
+        assert!(matches!(
+            parse(&TINY.replace("cols = 3", "cols = 4")),
+            Err(MapError::Parse { line: 23, .. })
+        ));
+        assert!(matches!(
+            parse(&TINY.replace("rows = 2", "rows = 3")),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let s = TINY.replace("version = 1", "version = 99");
+        assert!(matches!(parse(&s), Err(MapError::Invalid { .. })));
+    }
+
+    #[test]
+    fn rejects_spawn_in_wall() {
+        let s = TINY.replace("row = 1\ncol = 2", "row = 1\ncol = 0");
+        assert!(matches!(parse(&s), Err(MapError::Invalid { .. })));
+    }
+
+    #[test]
+    fn parses_portals() {
+        let portal =
+            "[[portal]]\na = { row = 0, col = 1 }\nb = { row = 1, col = 1, exit = \"left\" }\n";
+        let map = parse(&TINY.replace("---", &(portal.to_string() + "---"))).unwrap();
+        let mut expected = Portal::new((0, 1), (1, 1));
+        expected.ends[1].exit = Some(Direction::Left);
+        assert_eq!(map.portals, vec![expected]);
+        assert_eq!(parse(&to_snek(&map, 2, 3)).unwrap(), map);
+
+        let off_map = portal.replace("row = 1, col = 1", "row = 2, col = 1");
+        assert!(matches!(
+            parse(&TINY.replace("---", &(off_map + "---"))),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn parses_obstacles() {
+        let obstacles = "[[mover]]
+path = [{ row = 0, col = 0 }, { row = 0, col = 1 }]
+period = 2
+crush = true
+
+[[toggle]]
+cells = [{ row = 1, col = 1 }]
+on = 3
+off = 1
+
+[[crusher]]
+cells = [{ row = 1, col = 2 }]
+on = 1
+off = 4
+offset = 2
+";
+        let map = parse(&TINY.replace("---", &(obstacles.to_string() + "---"))).unwrap();
+        assert_eq!(
+            map.obstacles,
+            vec![
+                Obstacle::mover(vec![(0, 0), (0, 1)], 2).crushing(),
+                Obstacle::toggle(vec![(1, 1)], 3, 1),
+                Obstacle::toggle(vec![(1, 2)], 1, 4)
+                    .crushing()
+                    .with_offset(2),
+            ]
+        );
+        assert_eq!(parse(&to_snek(&map, 2, 3)).unwrap(), map);
+
+        let off_map = obstacles.replace("col = 1 }]\nperiod", "col = 3 }]\nperiod");
+        let stuck = obstacles.replace("off = 1", "off = 0");
+        for broken in [off_map, stuck] {
+            assert!(matches!(
+                parse(&TINY.replace("---", &(broken + "---"))),
+                Err(MapError::Invalid { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_puzzles() {
+        let puzzle = "[[key]]
+row = 0
+col = 1
+color = \"blue\"
+
+[[door]]
+cells = [{ row = 1, col = 1 }, { row = 1, col = 2 }]
+color = \"blue\"
+
+[[switch]]
+row = 0
+col = 0
+group = 2
+
+[[switch_wall]]
+cells = [{ row = 0, col = 2 }]
+group = 2
+open = true
+";
+        let map = parse(&TINY.replace("---", &(puzzle.to_string() + "---"))).unwrap();
+        assert_eq!(
+            map.keys,
+            vec![Key {
+                position: (0, 1),
+                color: KeyColor::Blue,
+            }]
+        );
+        assert_eq!(map.doors[0].cells, vec![(1, 1), (1, 2)]);
+        assert_eq!(map.switches[0].group, 2);
+        assert!(map.switch_walls[0].open);
+        assert_eq!(parse(&to_snek(&map, 2, 3)).unwrap(), map);
+
+        let unknown = puzzle.replacen("blue", "purple", 1);
+        let lonely = puzzle.replace("group = 2\nopen", "group = 3\nopen");
+        let off_map = puzzle.replace("col = 1\ncolor", "col = 3\ncolor");
+        for broken in [unknown, lonely, off_map] {
+            assert!(matches!(
+                parse(&TINY.replace("---", &(broken + "---"))),
+                Err(MapError::Invalid { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let map = parse(TINY).unwrap();
+        let written = to_snek(&map, 2, 3);
+        assert_eq!(parse(&written).unwrap(), map);
+    }
+
+    #[test]
+    fn loads_file() {
+        let map = SnekMapper::new("./fixture/tiny.snek").load_map().unwrap();
+        assert_eq!(map.size, Some((3, 4)));
+        assert_eq!(map.walls, vec![(1, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::types::{Position, WorldMap};
+
+/// Figures describing a map's layout
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stats {
+    pub rows: u32,
+    pub cols: u32,
+    /// Number of wall cells on the board
+    pub walls: usize,
+    /// Number of floor cells on the board
+    pub floor: usize,
+    pub player_spawns: usize,
+    pub enemy_spawns: usize,
+    /// Length of the longest straight, unbroken run of floor, and where it starts
+    pub longest_corridor: (usize, Position),
+}
+
+impl Stats {
+    /// Share of the board that is floor, as a percentage
+    pub fn open_percent(&self) -> f64 {
+        match self.rows * self.cols {
+            0 => 0.0,
+            cells => 100.0 * self.floor as f64 / cells as f64,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (length, (row, col)) = self.longest_corridor;
+        writeln!(f, "size:             {}x{}", self.rows, self.cols)?;
+        writeln!(f, "walls:            {}", self.walls)?;
+        writeln!(
+            f,
+            "open area:        {} ({:.1}%)",
+            self.floor,
+            self.open_percent()
+        )?;
+        writeln!(
+            f,
+            "spawns:           {} player, {} enemy",
+            self.player_spawns, self.enemy_spawns
+        )?;
+        write!(f, "longest corridor: {} from {}:{}", length, row, col)
+    }
+}
+
+/// Measure a map on a `rows` x `cols` board
+///
+/// Corridors are measured along rows and columns without wrapping at the edges.
+pub fn stats(world_map: &WorldMap, rows: u32, cols: u32) -> Stats {
+    let mut walls = vec![vec![false; cols as usize]; rows as usize];
+    for (row, col) in world_map.walls.iter() {
+        if *row < rows && *col < cols {
+            walls[*row as usize][*col as usize] = true;
+        }
+    }
+    let wall_count = walls.iter().flatten().filter(|wall| **wall).count();
+
+    let mut longest = (0, (0, 0));
+    let mut measure = |cells: &mut dyn Iterator<Item = Position>| {
+        let mut run = (0, (0, 0));
+        for (row, col) in cells {
+            match walls[row as usize][col as usize] {
+                true => run.0 = 0,
+                false if run.0 == 0 => run = (1, (row, col)),
+                false => run.0 += 1,
+            }
+            if run.0 > longest.0 {
+                longest = run;
+            }
+        }
+    };
+    for row in 0..rows {
+        measure(&mut (0..cols).map(|col| (row, col)));
+    }
+    for col in 0..cols {
+        measure(&mut (0..rows).map(|row| (row, col)));
+    }
+
+    Stats {
+        rows,
+        cols,
+        walls: wall_count,
+        floor: (rows * cols) as usize - wall_count,
+        player_spawns: world_map.player_spawns.len(),
+        enemy_spawns: world_map.enemy_spawns.len(),
+        longest_corridor: longest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WALL_COLOR;
+
+    #[test]
+    fn counts_cells() {
+        let map = WorldMap::new(vec![(0, 0), (1, 1), (9, 9)], WALL_COLOR);
+        let stats = stats(&map, 2, 4);
+        assert_eq!((stats.walls, stats.floor), (2, 6));
+        assert_eq!(stats.open_percent(), 75.0);
+    }
+
+    #[test]
+    fn finds_longest_corridor() {
+        // .#..
+        // .#..
+        // ....
+        let map = WorldMap::new(vec![(0, 1), (1, 1)], WALL_COLOR);
+        assert_eq!(stats(&map, 3, 4).longest_corridor, (4, (2, 0)));
+
+        let map = WorldMap::new(vec![(0, 1), (1, 1), (2, 1)], WALL_COLOR);
+        assert_eq!(stats(&map, 3, 4).longest_corridor, (3, (0, 0)));
+    }
+}
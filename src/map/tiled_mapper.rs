@@ -0,0 +1,702 @@
+use serde::Deserialize;
+
+use super::*;
+use crate::types::{MapMeta, Portal, PortalEnd, Spawn, Tile, Zone, MAX_BRICK_HP};
+
+/// Tiled stores flip flags in the top bits of each tile id
+const GID_MASK: u32 = 0x1fff_ffff;
+
+/// A `Mapper` implementation that imports maps made in the Tiled editor
+///
+/// Reads orthogonal maps saved as .tmx (XML, with CSV tile data) or .tmj (JSON). Every non-empty
+/// cell of a tile layer is a wall, or whatever `Tile` the layer's `tile` property names: `rock`,
+/// `ice`, a `conveyor` or `gate` facing the way the layer's `direction` property says, or a
+/// `brick` with as many hit points as its `hp` property says, 1 by default. The first layer to
+/// fill a cell wins. Objects are placed by their `type` (or `class`):
+///
+/// - `player` and `enemy`: spawns, facing the way their `direction` property says
+/// - `food_zone`: a rectangle food may appear in
+/// - `food`: a spot food always appears on
+/// - `portal`: one end of a portal, linked to the other `portal` object with the same `pair`
+///   property. An `exit` property gives the direction snakes leave this end in
+///
+/// Other objects are ignored. The map's `name`, `author` and `par` properties fill in its
+/// `MapMeta`.
+pub struct TiledMapper {
+    file: PathBuf,
+}
+
+impl TiledMapper {
+    /// Create a `TiledMapper` that loads the given .tmx or .tmj file
+    pub fn new<P: Into<PathBuf>>(file: P) -> Self {
+        TiledMapper { file: file.into() }
+    }
+}
+
+impl Mapper for TiledMapper {
+    fn load_map(&self) -> Result<WorldMap, MapError> {
+        let content = load_to_string(&self.file)?;
+        let world_map = match self.file.extension().and_then(|ext| ext.to_str()) {
+            Some("tmx") => parse_tmx(&self.file, &content)?,
+            _ => parse_tmj(&self.file, &content)?,
+        };
+        checked(&self.file, world_map)
+    }
+}
+
+/// The parts of a Tiled map the game uses, whichever format it was saved in
+struct Tiled {
+    width: u32,
+    height: u32,
+    tile_size: (f64, f64),
+    orientation: String,
+    infinite: bool,
+    properties: Vec<(String, String)>,
+    layers: Vec<TileLayer>,
+    objects: Vec<Object>,
+}
+
+struct TileLayer {
+    name: String,
+    properties: Vec<(String, String)>,
+    data: Vec<u32>,
+}
+
+struct Object {
+    kind: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    properties: Vec<(String, String)>,
+}
+
+/// Returns the value of a custom property
+fn property<'a>(properties: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    properties
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Turn a Tiled map into a `WorldMap`
+fn build(path: &Path, tiled: Tiled) -> Result<WorldMap, MapError> {
+    let invalid = |reason: String| MapError::Invalid {
+        path: path.to_path_buf(),
+        reason,
+    };
+    if tiled.orientation != "orthogonal" {
+        return Err(invalid(format!(
+            "{} maps are not supported, only orthogonal",
+            tiled.orientation
+        )));
+    }
+    if tiled.infinite {
+        return Err(invalid("infinite maps are not supported".to_string()));
+    }
+
+    let (rows, cols) = (tiled.height, tiled.width);
+    let mut world_map = WorldMap::new(vec![], types::WALL_COLOR);
+    for layer in tiled.layers.iter() {
+        if layer.data.len() != (rows * cols) as usize {
+            return Err(invalid(format!(
+                "layer '{}' has {} tiles, expected {}",
+                layer.name,
+                layer.data.len(),
+                rows * cols
+            )));
+        }
+        let direction = || match property(&layer.properties, "direction") {
+            Some(name) => parse_direction(name)
+                .ok_or_else(|| invalid(format!("unknown direction '{}'", name))),
+            None => Err(invalid(format!("layer '{}' needs a direction", layer.name))),
+        };
+        let tile = match property(&layer.properties, "tile") {
+            None | Some("wall") => Tile::Wall,
+            Some("rock") => Tile::Rock,
+            Some("floor") => Tile::Floor,
+            Some("ice") => Tile::Ice,
+            Some("conveyor") => Tile::Conveyor(direction()?),
+            Some("gate") => Tile::Gate(direction()?),
+            Some("brick") => match property(&layer.properties, "hp").map(str::parse::<u8>) {
+                None => Tile::Brick(1),
+                Some(Ok(hp)) if (1..=MAX_BRICK_HP).contains(&hp) => Tile::Brick(hp),
+                Some(_) => return Err(invalid(format!("layer '{}' has a bad hp", layer.name))),
+            },
+            Some(other) => return Err(invalid(format!("unknown tile '{}'", other))),
+        };
+        if tile == Tile::Floor {
+            continue;
+        }
+
+        for (i, gid) in (0..).zip(layer.data.iter()) {
+            let position = (i / cols, i % cols);
+            if gid & GID_MASK == 0 || world_map.tile_at(position) != Tile::Floor {
+                continue;
+            }
+            world_map.place(position, tile);
+        }
+    }
+
+    let (tile_width, tile_height) = tiled.tile_size;
+    let mut portal_ends: Vec<(&str, PortalEnd)> = vec![];
+    for object in tiled.objects.iter() {
+        let position = (
+            (object.y / tile_height).floor() as u32,
+            (object.x / tile_width).floor() as u32,
+        );
+        let (row, col) = position;
+        let off_map = || {
+            invalid(format!(
+                "{} object at {}:{} is off the map",
+                object.kind, row, col
+            ))
+        };
+        if object.x < 0.0 || object.y < 0.0 || row >= rows || col >= cols {
+            return Err(off_map());
+        }
+
+        match object.kind.as_str() {
+            "player" | "enemy" => {
+                if world_map.walls.contains(&position) {
+                    return Err(invalid(format!(
+                        "{} spawn at {}:{} is inside a wall",
+                        object.kind, row, col
+                    )));
+                }
+                let direction = match property(&object.properties, "direction") {
+                    None => facing(row, rows),
+                    Some(name) => parse_direction(name)
+                        .ok_or_else(|| invalid(format!("unknown direction '{}'", name)))?,
+                };
+                let spawn = Spawn {
+                    position,
+                    direction,
+                };
+                if object.kind == "player" {
+                    world_map.player_spawns.push(spawn);
+                } else {
+                    world_map.enemy_spawns.push(spawn);
+                }
+            }
+            "food_zone" => {
+                let zone = Zone {
+                    position,
+                    rows: ((object.height / tile_height).ceil() as u32).max(1),
+                    cols: ((object.width / tile_width).ceil() as u32).max(1),
+                };
+                if row + zone.rows > rows || col + zone.cols > cols {
+                    return Err(off_map());
+                }
+                world_map.food_zones.push(zone);
+            }
+            "food" => world_map.food_spots.push(position),
+            "portal" => {
+                let pair = property(&object.properties, "pair").ok_or_else(|| {
+                    invalid(format!("portal at {}:{} has no 'pair' property", row, col))
+                })?;
+                let exit = match property(&object.properties, "exit") {
+                    None => None,
+                    Some(name) => Some(
+                        parse_direction(name)
+                            .ok_or_else(|| invalid(format!("unknown direction '{}'", name)))?,
+                    ),
+                };
+                portal_ends.push((pair, PortalEnd { position, exit }));
+            }
+            _ => {}
+        }
+    }
+
+    // pairs are linked in the order they first appear
+    while let Some((pair, a)) = portal_ends.first().copied() {
+        let ends: Vec<PortalEnd> = portal_ends
+            .iter()
+            .filter(|(p, _)| *p == pair)
+            .map(|(_, end)| *end)
+            .collect();
+        match ends[..] {
+            [_, b] => world_map.portals.push(Portal { ends: [a, b] }),
+            _ => {
+                return Err(invalid(format!(
+                    "portal pair '{}' has {} ends, expected 2",
+                    pair,
+                    ends.len()
+                )))
+            }
+        }
+        portal_ends.retain(|(p, _)| *p != pair);
+    }
+
+    let par = match property(&tiled.properties, "par") {
+        Some(par) => Some(
+            par.parse()
+                .map_err(|_| invalid(format!("par '{}' is not a number", par)))?,
+        ),
+        None => None,
+    };
+    Ok(WorldMap {
+        size: Some((rows, cols)),
+        meta: MapMeta {
+            name: property(&tiled.properties, "name").map(str::to_string),
+            author: property(&tiled.properties, "author").map(str::to_string),
+            par,
+        },
+        ..world_map
+    })
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    width: u32,
+    height: u32,
+    tilewidth: f64,
+    tileheight: f64,
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+    #[serde(default)]
+    layers: Vec<TmjLayer>,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TmjLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    objects: Vec<TmjObject>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    width: f64,
+    #[serde(default)]
+    height: f64,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+fn tmj_properties(properties: Vec<TmjProperty>) -> Vec<(String, String)> {
+    properties
+        .into_iter()
+        .map(|property| {
+            let value = match property.value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (property.name, value)
+        })
+        .collect()
+}
+
+/// Parse a Tiled map saved as JSON, read from `path`
+pub fn parse_tmj(path: &Path, s: &str) -> Result<WorldMap, MapError> {
+    let map: TmjMap = serde_json::from_str(s).map_err(|e| MapError::Parse {
+        path: path.to_path_buf(),
+        line: e.line(),
+        column: e.column(),
+        message: e.to_string(),
+    })?;
+
+    let mut layers = vec![];
+    let mut objects = vec![];
+    for layer in map.layers {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let data = match layer
+                    .data
+                    .and_then(|data| serde_json::from_value(data).ok())
+                {
+                    Some(data) => data,
+                    None => {
+                        return Err(MapError::Invalid {
+                            path: path.to_path_buf(),
+                            reason: format!(
+                                "layer '{}' must be saved as an array of tile ids",
+                                layer.name
+                            ),
+                        })
+                    }
+                };
+                layers.push(TileLayer {
+                    name: layer.name,
+                    properties: tmj_properties(layer.properties),
+                    data,
+                });
+            }
+            "objectgroup" => {
+                objects.extend(layer.objects.into_iter().map(|object| Object {
+                    kind: if object.kind.is_empty() {
+                        object.class
+                    } else {
+                        object.kind
+                    },
+                    x: object.x,
+                    y: object.y,
+                    width: object.width,
+                    height: object.height,
+                    properties: tmj_properties(object.properties),
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    build(
+        path,
+        Tiled {
+            width: map.width,
+            height: map.height,
+            tile_size: (map.tilewidth, map.tileheight),
+            orientation: map.orientation,
+            infinite: map.infinite,
+            properties: tmj_properties(map.properties),
+            layers,
+            objects,
+        },
+    )
+}
+
+/// Parse a Tiled map saved as XML, read from `path`
+pub fn parse_tmx(path: &Path, s: &str) -> Result<WorldMap, MapError> {
+    let doc = roxmltree::Document::parse(s).map_err(|e| MapError::Parse {
+        path: path.to_path_buf(),
+        line: e.pos().row as usize,
+        column: e.pos().col as usize,
+        message: e.to_string(),
+    })?;
+    let parse_error = |node: roxmltree::Node, message: String| {
+        let position = doc.text_pos_at(node.range().start);
+        MapError::Parse {
+            path: path.to_path_buf(),
+            line: position.row as usize,
+            column: position.col as usize,
+            message,
+        }
+    };
+    let attribute = |node: roxmltree::Node, name: &str| {
+        node.attribute(name).map(str::to_string).ok_or_else(|| {
+            parse_error(
+                node,
+                format!("<{}> is missing '{}'", node.tag_name().name(), name),
+            )
+        })
+    };
+    let number = |node: roxmltree::Node, name: &str| -> Result<f64, MapError> {
+        let value = attribute(node, name)?;
+        value
+            .parse()
+            .map_err(|_| parse_error(node, format!("'{}' is not a number", value)))
+    };
+    let properties = |node: roxmltree::Node| -> Vec<(String, String)> {
+        node.children()
+            .filter(|child| child.has_tag_name("properties"))
+            .flat_map(|properties| properties.children())
+            .filter(|child| child.has_tag_name("property"))
+            .filter_map(|property| {
+                let value = property.attribute("value").or_else(|| property.text())?;
+                Some((property.attribute("name")?.to_string(), value.to_string()))
+            })
+            .collect()
+    };
+
+    let root = doc.root_element();
+    if !root.has_tag_name("map") {
+        return Err(parse_error(root, "expected a <map>".to_string()));
+    }
+
+    let mut layers = vec![];
+    let mut objects = vec![];
+    for node in root.children() {
+        if node.has_tag_name("layer") {
+            let data_node = node
+                .children()
+                .find(|child| child.has_tag_name("data"))
+                .ok_or_else(|| parse_error(node, "<layer> has no <data>".to_string()))?;
+            let data = match data_node.attribute("encoding") {
+                Some("csv") => data_node
+                    .text()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|gid| {
+                        gid.trim().parse().map_err(|_| {
+                            parse_error(data_node, format!("bad tile id '{}'", gid.trim()))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                None => data_node
+                    .children()
+                    .filter(|child| child.has_tag_name("tile"))
+                    .map(|tile| {
+                        tile.attribute("gid").map_or(Ok(0), |gid| {
+                            gid.parse()
+                                .map_err(|_| parse_error(tile, format!("bad tile id '{}'", gid)))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                Some(encoding) => {
+                    return Err(parse_error(
+                        data_node,
+                        format!("{} tile data is not supported, save as CSV", encoding),
+                    ))
+                }
+            };
+            layers.push(TileLayer {
+                name: node.attribute("name").unwrap_or_default().to_string(),
+                properties: properties(node),
+                data,
+            });
+        } else if node.has_tag_name("objectgroup") {
+            for object in node.children().filter(|child| child.has_tag_name("object")) {
+                objects.push(Object {
+                    kind: object
+                        .attribute("type")
+                        .or_else(|| object.attribute("class"))
+                        .unwrap_or_default()
+                        .to_string(),
+                    x: number(object, "x")?,
+                    y: number(object, "y")?,
+                    width: object
+                        .attribute("width")
+                        .map_or(Ok(0.0), |_| number(object, "width"))?,
+                    height: object
+                        .attribute("height")
+                        .map_or(Ok(0.0), |_| number(object, "height"))?,
+                    properties: properties(object),
+                });
+            }
+        }
+    }
+
+    build(
+        path,
+        Tiled {
+            width: number(root, "width")? as u32,
+            height: number(root, "height")? as u32,
+            tile_size: (number(root, "tilewidth")?, number(root, "tileheight")?),
+            orientation: attribute(root, "orientation")?,
+            infinite: root.attribute("infinite") == Some("1"),
+            properties: properties(root),
+            layers,
+            objects,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Direction;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="4" height="3" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="name" value="Tiled"/>
+  <property name="par" type="int" value="12"/>
+ </properties>
+ <layer id="1" name="walls" width="4" height="3">
+  <data encoding="csv">
+1,0,0,1,
+0,0,0,0,
+1,0,0,1
+</data>
+ </layer>
+ <layer id="2" name="rocks" width="4" height="3">
+  <properties>
+   <property name="tile" value="rock"/>
+  </properties>
+  <data encoding="csv">
+0,0,0,0,
+0,2,0,0,
+0,0,0,0
+</data>
+ </layer>
+ <objectgroup id="3" name="things">
+  <object id="1" type="player" x="40" y="8">
+   <properties>
+    <property name="direction" value="left"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="2" class="enemy" x="24" y="40"/>
+  <object id="3" type="food_zone" x="16" y="0" width="32" height="16"/>
+  <object id="4" type="sign" x="0" y="0"/>
+  <object id="5" type="portal" x="32" y="16">
+   <properties>
+    <property name="pair" value="a"/>
+   </properties>
+  </object>
+  <object id="6" type="portal" x="48" y="16">
+   <properties>
+    <property name="pair" value="a"/>
+    <property name="exit" value="down"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    const TMJ: &str = r#"{
+ "orientation": "orthogonal", "width": 4, "height": 3, "tilewidth": 16, "tileheight": 16,
+ "infinite": false,
+ "properties": [{"name": "name", "type": "string", "value": "Tiled"},
+                {"name": "par", "type": "int", "value": 12}],
+ "layers": [
+  {"type": "tilelayer", "name": "walls", "data": [1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1]},
+  {"type": "tilelayer", "name": "rocks", "data": [0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0],
+   "properties": [{"name": "tile", "type": "string", "value": "rock"}]},
+  {"type": "objectgroup", "name": "things", "objects": [
+   {"type": "player", "x": 40, "y": 8, "point": true,
+    "properties": [{"name": "direction", "type": "string", "value": "left"}]},
+   {"class": "enemy", "x": 24, "y": 40},
+   {"type": "food_zone", "x": 16, "y": 0, "width": 32, "height": 16},
+   {"type": "sign", "x": 0, "y": 0},
+   {"type": "portal", "x": 32, "y": 16,
+    "properties": [{"name": "pair", "type": "int", "value": 1}]},
+   {"type": "portal", "x": 48, "y": 16,
+    "properties": [{"name": "pair", "type": "int", "value": 1},
+                   {"name": "exit", "type": "string", "value": "down"}]}
+  ]}
+ ]
+}"#;
+
+    fn check(map: WorldMap) {
+        assert_eq!(map.size, Some((3, 4)));
+        assert_eq!(map.meta.name.as_deref(), Some("Tiled"));
+        assert_eq!(map.meta.par, Some(12));
+        assert_eq!(map.walls, vec![(0, 0), (0, 3), (2, 0), (2, 3), (1, 1)]);
+        assert_eq!(map.tiles, vec![((1, 1), Tile::Rock)]);
+        assert_eq!(
+            map.player_spawns,
+            vec![Spawn {
+                position: (0, 2),
+                direction: Direction::Left,
+            }]
+        );
+        assert_eq!(map.enemy_spawns[0].position, (2, 1));
+        assert_eq!(map.enemy_spawns[0].direction, Direction::Up);
+        assert_eq!(
+            map.food_zones,
+            vec![Zone {
+                position: (0, 1),
+                rows: 1,
+                cols: 2,
+            }]
+        );
+        let mut portal = Portal::new((1, 2), (1, 3));
+        portal.ends[1].exit = Some(Direction::Down);
+        assert_eq!(map.portals, vec![portal]);
+    }
+
+    #[test]
+    fn imports_tmx() {
+        check(parse_tmx(Path::new("test.tmx"), TMX).unwrap());
+    }
+
+    #[test]
+    fn imports_tmj() {
+        check(parse_tmj(Path::new("test.tmj"), TMJ).unwrap());
+    }
+
+    #[test]
+    fn reads_terrain_layers() {
+        let rock = r#"{"name": "tile", "type": "string", "value": "rock"}"#;
+        let conveyor = r#"{"name": "tile", "type": "string", "value": "conveyor"}"#;
+        let tmj = TMJ.replace(rock, conveyor);
+        assert!(matches!(
+            parse_tmj(Path::new("test.tmj"), &tmj),
+            Err(MapError::Invalid { .. })
+        ));
+
+        let direction = r#"{"name": "direction", "type": "string", "value": "up"}"#;
+        let tmj = TMJ.replace(rock, &format!("{}, {}", conveyor, direction));
+        let map = parse_tmj(Path::new("test.tmj"), &tmj).unwrap();
+        assert_eq!(map.tile_at((1, 1)), Tile::Conveyor(Direction::Up));
+        assert!(!map.walls.contains(&(1, 1)));
+
+        let brick = r#"{"name": "tile", "type": "string", "value": "brick"}"#;
+        let hp = r#"{"name": "hp", "type": "int", "value": 2}"#;
+        let tmj = TMJ.replace(rock, &format!("{}, {}", brick, hp));
+        let map = parse_tmj(Path::new("test.tmj"), &tmj).unwrap();
+        assert_eq!(map.tile_at((1, 1)), Tile::Brick(2));
+        assert!(map.walls.contains(&(1, 1)));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let tmx = TMX.replace("<layer id=\"1\"", "<layer id=1");
+        match parse_tmx(Path::new("test.tmx"), &tmx) {
+            Err(MapError::Parse { line, .. }) => assert_eq!(line, 7),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+        let tmj = TMJ.replace("\"width\": 4,", "\"width\": four,");
+        match parse_tmj(Path::new("test.tmj"), &tmj) {
+            Err(MapError::Parse { line, .. }) => assert_eq!(line, 2),
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_maps() {
+        let isometric = TMX.replace("orthogonal", "isometric");
+        assert!(matches!(
+            parse_tmx(Path::new("test.tmx"), &isometric),
+            Err(MapError::Invalid { .. })
+        ));
+        let compressed = TMX.replace("encoding=\"csv\"", "encoding=\"base64\"");
+        assert!(matches!(
+            parse_tmx(Path::new("test.tmx"), &compressed),
+            Err(MapError::Parse { .. })
+        ));
+        let short = TMJ.replace("[1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1]", "[1, 0]");
+        assert!(matches!(
+            parse_tmj(Path::new("test.tmj"), &short),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_spawn_in_wall() {
+        let tmj = TMJ.replace("\"x\": 24, \"y\": 40", "\"x\": 0, \"y\": 40");
+        assert!(matches!(
+            parse_tmj(Path::new("test.tmj"), &tmj),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unpaired_portal() {
+        let tmj = TMJ.replace("\"value\": 1}]},", "\"value\": 2}]},");
+        assert!(matches!(
+            parse_tmj(Path::new("test.tmj"), &tmj),
+            Err(MapError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_file() {
+        let map = TiledMapper::new("./fixture/tiny.tmx").load_map().unwrap();
+        assert_eq!(map.size, Some((3, 4)));
+        assert_eq!(map.walls, vec![(1, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,342 @@
+use crate::types::{
+    Direction, Door, Key, Portal, PortalEnd, Position, Spawn, Switch, SwitchWall, WorldMap, Zone,
+};
+
+/// A way of turning or mirroring a map
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Transform {
+    /// A quarter turn clockwise
+    RotateClockwise,
+    /// A quarter turn counterclockwise
+    RotateCounterClockwise,
+    /// A half turn
+    Rotate180,
+    /// Mirror left to right
+    FlipHorizontal,
+    /// Mirror top to bottom
+    FlipVertical,
+    /// Mirror across the top-left to bottom-right diagonal
+    Transpose,
+}
+
+impl Transform {
+    /// Every transform
+    pub const ALL: [Transform; 6] = [
+        Transform::RotateClockwise,
+        Transform::RotateCounterClockwise,
+        Transform::Rotate180,
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+        Transform::Transpose,
+    ];
+
+    /// Returns the name this `Transform` goes by on the command line, eg "rotate-cw"
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::RotateClockwise => "rotate-cw",
+            Self::RotateCounterClockwise => "rotate-ccw",
+            Self::Rotate180 => "rotate-180",
+            Self::FlipHorizontal => "flip-h",
+            Self::FlipVertical => "flip-v",
+            Self::Transpose => "transpose",
+        }
+    }
+
+    /// Returns the `Transform` with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|t| t.name() == name)
+    }
+
+    /// Whether this `Transform` swaps a map's rows and columns
+    fn swaps_axes(&self) -> bool {
+        matches!(
+            *self,
+            Self::RotateClockwise | Self::RotateCounterClockwise | Self::Transpose
+        )
+    }
+
+    /// Returns where a cell of a `rows` x `cols` map ends up
+    fn position(&self, (row, col): Position, rows: u32, cols: u32) -> Position {
+        match *self {
+            Self::RotateClockwise => (col, rows - 1 - row),
+            Self::RotateCounterClockwise => (cols - 1 - col, row),
+            Self::Rotate180 => (rows - 1 - row, cols - 1 - col),
+            Self::FlipHorizontal => (row, cols - 1 - col),
+            Self::FlipVertical => (rows - 1 - row, col),
+            Self::Transpose => (col, row),
+        }
+    }
+
+    /// Returns which way a `Direction` points afterward
+    fn direction(&self, direction: Direction) -> Direction {
+        match (*self, direction) {
+            (Self::RotateClockwise, d) => d.clockwise(),
+            (Self::RotateCounterClockwise, d) => d.clockwise().flip(),
+            (Self::Rotate180, d) => d.flip(),
+            (Self::FlipHorizontal, Direction::Left) | (Self::FlipHorizontal, Direction::Right) => {
+                direction.flip()
+            }
+            (Self::FlipVertical, Direction::Up) | (Self::FlipVertical, Direction::Down) => {
+                direction.flip()
+            }
+            (Self::Transpose, Direction::Up) => Direction::Left,
+            (Self::Transpose, Direction::Left) => Direction::Up,
+            (Self::Transpose, Direction::Down) => Direction::Right,
+            (Self::Transpose, Direction::Right) => Direction::Down,
+            (_, d) => d,
+        }
+    }
+}
+
+/// Turn or mirror a `rows` x `cols` map, spawns, food, portals, obstacles and puzzles included
+///
+/// Returns the new map and its dimensions. Anything outside the board is left out.
+pub fn transform(
+    world_map: &WorldMap,
+    rows: u32,
+    cols: u32,
+    transform: Transform,
+) -> (WorldMap, (u32, u32)) {
+    let size = match transform.swaps_axes() {
+        true => (cols, rows),
+        false => (rows, cols),
+    };
+    let in_bounds = |(row, col): &Position| *row < rows && *col < cols;
+    let moved = |position: Position| transform.position(position, rows, cols);
+    let all_moved = |cells: &[Position]| -> Option<Vec<Position>> {
+        cells
+            .iter()
+            .map(|p| Some(p).filter(|p| in_bounds(p)).map(|p| moved(*p)))
+            .collect()
+    };
+    let spawns = |spawns: &[Spawn]| {
+        spawns
+            .iter()
+            .filter(|spawn| in_bounds(&spawn.position))
+            .map(|spawn| Spawn {
+                position: moved(spawn.position),
+                direction: transform.direction(spawn.direction),
+            })
+            .collect()
+    };
+
+    let food_zones = world_map
+        .food_zones
+        .iter()
+        .filter(|zone| zone.position.0 + zone.rows <= rows && zone.position.1 + zone.cols <= cols)
+        .map(|zone| {
+            let (top, left) = zone.position;
+            let (a, b) = (
+                moved((top, left)),
+                moved((top + zone.rows - 1, left + zone.cols - 1)),
+            );
+            let (zone_rows, zone_cols) = match transform.swaps_axes() {
+                true => (zone.cols, zone.rows),
+                false => (zone.rows, zone.cols),
+            };
+            Zone {
+                position: (a.0.min(b.0), a.1.min(b.1)),
+                rows: zone_rows,
+                cols: zone_cols,
+            }
+        })
+        .collect();
+
+    let world_map = WorldMap {
+        walls: world_map
+            .walls
+            .iter()
+            .filter(|p| in_bounds(p))
+            .map(|p| moved(*p))
+            .collect(),
+        tiles: world_map
+            .tiles
+            .iter()
+            .filter(|(p, _)| in_bounds(p))
+            .map(|(p, tile)| {
+                let tile = tile.map_direction(|direction| transform.direction(direction));
+                (moved(*p), tile)
+            })
+            .collect(),
+        size: world_map.size.map(|_| size),
+        player_spawns: spawns(&world_map.player_spawns),
+        enemy_spawns: spawns(&world_map.enemy_spawns),
+        food_zones,
+        food_spots: world_map
+            .food_spots
+            .iter()
+            .filter(|p| in_bounds(p))
+            .map(|p| moved(*p))
+            .collect(),
+        portals: world_map
+            .portals
+            .iter()
+            .filter(|portal| portal.ends.iter().all(|end| in_bounds(&end.position)))
+            .map(|portal| Portal {
+                ends: [0, 1].map(|i| PortalEnd {
+                    position: moved(portal.ends[i].position),
+                    exit: portal.ends[i].exit.map(|exit| transform.direction(exit)),
+                }),
+            })
+            .collect(),
+        obstacles: world_map
+            .obstacles
+            .iter()
+            .filter(|obstacle| obstacle.cells().iter().all(in_bounds))
+            .map(|obstacle| obstacle.map_cells(moved))
+            .collect(),
+        keys: world_map
+            .keys
+            .iter()
+            .filter(|key| in_bounds(&key.position))
+            .map(|key| Key {
+                position: moved(key.position),
+                ..*key
+            })
+            .collect(),
+        doors: world_map
+            .doors
+            .iter()
+            .filter_map(|door| {
+                Some(Door {
+                    cells: all_moved(&door.cells)?,
+                    color: door.color,
+                })
+            })
+            .collect(),
+        switches: world_map
+            .switches
+            .iter()
+            .filter(|switch| in_bounds(&switch.position))
+            .map(|switch| Switch {
+                position: moved(switch.position),
+                ..*switch
+            })
+            .collect(),
+        switch_walls: world_map
+            .switch_walls
+            .iter()
+            .filter_map(|wall| {
+                Some(SwitchWall {
+                    cells: all_moved(&wall.cells)?,
+                    ..*wall
+                })
+            })
+            .collect(),
+        ..world_map.clone()
+    };
+    (world_map, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::to_ascii,
+        types::{KeyColor, Obstacle, Tile, WALL_COLOR},
+    };
+
+    fn sample() -> WorldMap {
+        let mut portal = Portal::new((0, 2), (1, 1));
+        portal.ends[0].exit = Some(Direction::Up);
+        WorldMap {
+            portals: vec![portal],
+            player_spawns: vec![Spawn {
+                position: (0, 1),
+                direction: Direction::Right,
+            }],
+            food_zones: vec![Zone {
+                position: (1, 1),
+                rows: 1,
+                cols: 2,
+            }],
+            ..WorldMap::new(vec![(0, 0), (1, 0)], WALL_COLOR)
+        }
+    }
+
+    #[test]
+    fn rotates() {
+        let (map, size) = transform(&sample(), 2, 3, Transform::RotateClockwise);
+        assert_eq!(size, (3, 2));
+        assert_eq!(to_ascii(&map, 3, 2), "##\n0P\n.0\n");
+        assert_eq!(map.player_spawns[0].direction, Direction::Down);
+        assert_eq!(map.portals[0].ends[0].position, (2, 1));
+        assert_eq!(map.portals[0].ends[0].exit, Some(Direction::Right));
+        assert_eq!(
+            map.food_zones[0],
+            Zone {
+                position: (1, 0),
+                rows: 2,
+                cols: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn flips() {
+        let (map, _) = transform(&sample(), 2, 3, Transform::FlipHorizontal);
+        assert_eq!(to_ascii(&map, 2, 3), "0P#\n.0#\n");
+        assert_eq!(map.player_spawns[0].direction, Direction::Left);
+
+        let (map, _) = transform(&sample(), 2, 3, Transform::FlipVertical);
+        assert_eq!(to_ascii(&map, 2, 3), "#0.\n#P0\n");
+        assert_eq!(map.player_spawns[0].direction, Direction::Right);
+    }
+
+    #[test]
+    fn turns_terrain() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.place((0, 1), Tile::Conveyor(Direction::Right));
+        map.place((0, 0), Tile::Gate(Direction::Up));
+        let (map, _) = transform(&map, 1, 2, Transform::RotateClockwise);
+        assert_eq!(to_ascii(&map, 2, 1), "r\nv\n");
+    }
+
+    #[test]
+    fn moves_obstacles() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.obstacles.push(Obstacle::mover(vec![(0, 0), (0, 1)], 3));
+        let (map, _) = transform(&map, 1, 2, Transform::FlipHorizontal);
+        assert_eq!(map.obstacles[0].cells(), &[(0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn moves_puzzles() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.keys.push(Key {
+            position: (0, 0),
+            color: KeyColor::Red,
+        });
+        map.doors.push(Door {
+            cells: vec![(0, 1), (0, 2)],
+            color: KeyColor::Red,
+        });
+        let (map, _) = transform(&map, 1, 3, Transform::FlipHorizontal);
+        assert_eq!(map.keys[0].position, (0, 2));
+        assert_eq!(map.doors[0].cells, vec![(0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn undoes_itself() {
+        let map = sample();
+        for t in Transform::ALL.iter() {
+            let (once, (rows, cols)) = transform(&map, 2, 3, *t);
+            let inverse = match t {
+                Transform::RotateClockwise => Transform::RotateCounterClockwise,
+                Transform::RotateCounterClockwise => Transform::RotateClockwise,
+                _ => *t,
+            };
+            let (twice, size) = transform(&once, rows, cols, inverse);
+            assert_eq!(size, (2, 3));
+            assert_eq!(twice, map, "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for t in Transform::ALL.iter() {
+            assert_eq!(Transform::from_name(t.name()), Some(*t));
+        }
+        assert_eq!(Transform::from_name("spin"), None);
+    }
+}
@@ -0,0 +1,537 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    snake::Snake,
+    types::{Direction, Position, Tile, Topology, WorldMap},
+};
+
+/// Something that makes a map unplayable
+#[derive(Debug, PartialEq, Clone)]
+pub enum Problem {
+    /// The map was drawn for a different size of board
+    WrongSize {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// A wall lies outside the board
+    WallOutOfBounds(Position),
+    /// A spawn lies outside the board
+    SpawnOutOfBounds(Position),
+    /// A spawn lies inside a wall
+    SpawnBlocked(Position),
+    /// Two spawns share a cell
+    SpawnOverlap(Position),
+    /// A food zone or spot lies outside the board
+    FoodOutOfBounds(Position),
+    /// A food spot lies inside a wall
+    FoodBlocked(Position),
+    /// A portal end lies outside the board
+    PortalOutOfBounds(Position),
+    /// A portal end lies inside a wall, or on other terrain, a spawn, a food spot or another
+    /// portal end
+    PortalBlocked(Position),
+    /// An obstacle's cell lies outside the board
+    ObstacleOutOfBounds(Position),
+    /// An obstacle's cell lies inside a wall, or on a spawn or portal end
+    ObstacleBlocked(Position),
+    /// A key, door, switch or switch wall lies outside the board
+    PuzzleOutOfBounds(Position),
+    /// A key, door, switch or switch wall lies inside a wall, or on a spawn, portal end, obstacle
+    /// or another piece of the puzzle
+    PuzzleBlocked(Position),
+    /// No key on the map opens a door
+    NoKey(Position),
+    /// The map has no floor at all
+    NoFloor,
+    /// Some floor can't be reached from the rest, eg a walled-in pocket
+    Unreachable { cells: usize, example: Position },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WrongSize { expected, actual } => write!(
+                f,
+                "map is {}x{}, the board is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Self::WallOutOfBounds((row, col)) => {
+                write!(f, "wall at {}:{} is off the map", row, col)
+            }
+            Self::SpawnOutOfBounds((row, col)) => {
+                write!(f, "spawn at {}:{} is off the map", row, col)
+            }
+            Self::SpawnBlocked((row, col)) => {
+                write!(f, "spawn at {}:{} is inside a wall", row, col)
+            }
+            Self::SpawnOverlap((row, col)) => write!(f, "spawns overlap at {}:{}", row, col),
+            Self::FoodOutOfBounds((row, col)) => {
+                write!(f, "food at {}:{} is off the map", row, col)
+            }
+            Self::FoodBlocked((row, col)) => write!(f, "food at {}:{} is inside a wall", row, col),
+            Self::PortalOutOfBounds((row, col)) => {
+                write!(f, "portal at {}:{} is off the map", row, col)
+            }
+            Self::PortalBlocked((row, col)) => {
+                write!(f, "portal at {}:{} is blocked", row, col)
+            }
+            Self::ObstacleOutOfBounds((row, col)) => {
+                write!(f, "obstacle at {}:{} is off the map", row, col)
+            }
+            Self::ObstacleBlocked((row, col)) => {
+                write!(f, "obstacle at {}:{} is blocked", row, col)
+            }
+            Self::PuzzleOutOfBounds((row, col)) => {
+                write!(f, "puzzle piece at {}:{} is off the map", row, col)
+            }
+            Self::PuzzleBlocked((row, col)) => {
+                write!(f, "puzzle piece at {}:{} is blocked", row, col)
+            }
+            Self::NoKey((row, col)) => write!(f, "door at {}:{} has no key", row, col),
+            Self::NoFloor => write!(f, "map has no floor"),
+            Self::Unreachable { cells, example } => write!(
+                f,
+                "{} floor cells, eg {}:{}, are cut off from the rest",
+                cells, example.0, example.1
+            ),
+        }
+    }
+}
+
+/// Check that a map is playable on a `rows` x `cols` board whose edges join as `topology` says
+///
+/// Every floor cell must be reachable from every other, moving through the edges of the board,
+/// through portals and through one-way gates the way snakes do. Returns every problem found, an
+/// empty list for a good map.
+pub fn validate(world_map: &WorldMap, rows: u32, cols: u32, topology: Topology) -> Vec<Problem> {
+    let mut problems = vec![];
+    if let Some(actual) = world_map.size {
+        if actual != (rows, cols) {
+            problems.push(Problem::WrongSize {
+                expected: (rows, cols),
+                actual,
+            });
+        }
+    }
+
+    let in_bounds = |(row, col): Position| row < rows && col < cols;
+    let mut walls = vec![vec![false; cols as usize]; rows as usize];
+    for wall in world_map.walls.iter() {
+        match in_bounds(*wall) {
+            true => walls[wall.0 as usize][wall.1 as usize] = true,
+            false => problems.push(Problem::WallOutOfBounds(*wall)),
+        }
+    }
+    let is_wall = |(row, col): Position| walls[row as usize][col as usize];
+
+    let mut spawned: Vec<Position> = vec![];
+    let spawns = world_map
+        .player_spawns
+        .iter()
+        .chain(world_map.enemy_spawns.iter());
+    for spawn in spawns {
+        let position = spawn.position;
+        if !in_bounds(position) {
+            problems.push(Problem::SpawnOutOfBounds(position));
+        } else if is_wall(position) {
+            problems.push(Problem::SpawnBlocked(position));
+        } else if spawned.contains(&position) {
+            problems.push(Problem::SpawnOverlap(position));
+        }
+        spawned.push(position);
+    }
+
+    for zone in world_map.food_zones.iter() {
+        let (row, col) = zone.position;
+        if row + zone.rows > rows || col + zone.cols > cols {
+            problems.push(Problem::FoodOutOfBounds(zone.position));
+        }
+    }
+    for spot in world_map.food_spots.iter() {
+        if !in_bounds(*spot) {
+            problems.push(Problem::FoodOutOfBounds(*spot));
+        } else if is_wall(*spot) {
+            problems.push(Problem::FoodBlocked(*spot));
+        }
+    }
+
+    let mut portals: Vec<Position> = vec![];
+    let ends = world_map
+        .portals
+        .iter()
+        .flat_map(|portal| portal.ends.iter());
+    for end in ends {
+        let position = end.position;
+        let taken = spawned.contains(&position) || world_map.food_spots.contains(&position);
+        if !in_bounds(position) {
+            problems.push(Problem::PortalOutOfBounds(position));
+        } else if world_map.tile_at(position) != Tile::Floor || taken || portals.contains(&position)
+        {
+            problems.push(Problem::PortalBlocked(position));
+        }
+        portals.push(position);
+    }
+    let obstacles: Vec<Position> = world_map
+        .obstacles
+        .iter()
+        .flat_map(|obstacle| obstacle.cells().iter().copied())
+        .collect();
+    for cell in obstacles.iter().copied() {
+        if !in_bounds(cell) {
+            problems.push(Problem::ObstacleOutOfBounds(cell));
+        } else if is_wall(cell) || spawned.contains(&cell) || portals.contains(&cell) {
+            problems.push(Problem::ObstacleBlocked(cell));
+        }
+    }
+
+    let mut pieces: Vec<Position> = vec![];
+    let cells = world_map
+        .keys
+        .iter()
+        .map(|key| key.position)
+        .chain(
+            world_map
+                .doors
+                .iter()
+                .flat_map(|door| door.cells.iter().copied()),
+        )
+        .chain(world_map.switches.iter().map(|switch| switch.position))
+        .chain(
+            world_map
+                .switch_walls
+                .iter()
+                .flat_map(|wall| wall.cells.iter().copied()),
+        );
+    for cell in cells {
+        if !in_bounds(cell) {
+            problems.push(Problem::PuzzleOutOfBounds(cell));
+        } else if is_wall(cell)
+            || spawned.contains(&cell)
+            || portals.contains(&cell)
+            || obstacles.contains(&cell)
+            || pieces.contains(&cell)
+        {
+            problems.push(Problem::PuzzleBlocked(cell));
+        }
+        pieces.push(cell);
+    }
+    // a door without cells blocks nothing, so it needs no key
+    for door in world_map.doors.iter() {
+        if let Some(cell) = door.cells.first() {
+            if !world_map.keys.iter().any(|key| key.color == door.color) {
+                problems.push(Problem::NoKey(*cell));
+            }
+        }
+    }
+
+    // snakes pass through portals without ever standing on them, while obstacles, doors and
+    // switch walls come and go
+    let is_open = |position: Position| !is_wall(position) && !portals.contains(&position);
+
+    let floor: Vec<Position> = (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (row, col)))
+        .filter(|position| is_open(*position))
+        .collect();
+    let start = match floor.first() {
+        Some(start) => *start,
+        None => {
+            problems.push(Problem::NoFloor);
+            return problems;
+        }
+    };
+
+    // every move a snake can make from each floor cell, crossing the edges as the topology
+    // says, jumping through portals and only entering gates the way they open. A step that goes
+    // nowhere runs into the edge of a bounded board
+    let snake = Snake::new(0, 0, None).with_topology(topology);
+    let step = |position: Position, direction: Direction| {
+        let (next, heading) = snake.step(position, &direction, rows as i32, cols as i32);
+        Some((next, heading)).filter(|(next, _)| *next != position)
+    };
+    let moves = |position: Position, direction: Direction| {
+        let (mut next, mut heading) = step(position, direction)?;
+        if let Some(end) = world_map.portal_partner(next).filter(|_| in_bounds(next)) {
+            if !in_bounds(end.position) {
+                return None;
+            }
+            let (exit, exit_heading) = step(end.position, end.exit.unwrap_or(heading))?;
+            next = exit;
+            heading = exit_heading;
+        }
+        Some(next).filter(|next| is_open(*next) && world_map.tile_at(*next).admits(heading))
+    };
+    let mut forward = vec![vec![vec![]; cols as usize]; rows as usize];
+    let mut backward = vec![vec![vec![]; cols as usize]; rows as usize];
+    for position in floor.iter().copied() {
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        for next in directions.iter().filter_map(|d| moves(position, *d)) {
+            forward[position.0 as usize][position.1 as usize].push(next);
+            backward[next.0 as usize][next.1 as usize].push(position);
+        }
+    }
+
+    // gates make some moves one way, so the floor must both reach and be reached from one cell
+    let reached = flood(start, &forward);
+    let returned = flood(start, &backward);
+    let cut_off: Vec<Position> = floor
+        .into_iter()
+        .filter(|(row, col)| {
+            let (row, col) = (*row as usize, *col as usize);
+            !reached[row][col] || !returned[row][col]
+        })
+        .collect();
+    if let Some(example) = cut_off.first() {
+        problems.push(Problem::Unreachable {
+            cells: cut_off.len(),
+            example: *example,
+        });
+    }
+
+    problems
+}
+
+/// Returns which cells can be reached from `start`, following each cell's `links` to others
+fn flood(start: Position, links: &[Vec<Vec<Position>>]) -> Vec<Vec<bool>> {
+    let mut reached: Vec<Vec<bool>> = links.iter().map(|row| vec![false; row.len()]).collect();
+    reached[start.0 as usize][start.1 as usize] = true;
+    let mut queue = VecDeque::from(vec![start]);
+    while let Some((row, col)) = queue.pop_front() {
+        for next in links[row as usize][col as usize].iter() {
+            if !reached[next.0 as usize][next.1 as usize] {
+                reached[next.0 as usize][next.1 as usize] = true;
+                queue.push_back(*next);
+            }
+        }
+    }
+
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        Door, Key, KeyColor, Obstacle, Portal, Spawn, Switch, SwitchWall, Zone, WALL_COLOR,
+    };
+
+    fn spawn(position: Position) -> Spawn {
+        Spawn {
+            position,
+            direction: Direction::Down,
+        }
+    }
+
+    #[test]
+    fn accepts_open_map() {
+        let map = WorldMap::new(vec![(1, 1), (1, 2)], WALL_COLOR);
+        assert_eq!(validate(&map, 4, 4, Topology::Torus), vec![]);
+    }
+
+    #[test]
+    fn checks_dimensions() {
+        let map = WorldMap {
+            size: Some((4, 5)),
+            ..WorldMap::new(vec![(3, 4), (4, 0)], WALL_COLOR)
+        };
+        assert_eq!(
+            validate(&map, 4, 4, Topology::Torus),
+            vec![
+                Problem::WrongSize {
+                    expected: (4, 4),
+                    actual: (4, 5),
+                },
+                Problem::WallOutOfBounds((3, 4)),
+                Problem::WallOutOfBounds((4, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_spawns_and_food() {
+        let map = WorldMap {
+            player_spawns: vec![spawn((0, 0)), spawn((1, 1))],
+            enemy_spawns: vec![spawn((0, 0)), spawn((9, 9))],
+            food_zones: vec![Zone {
+                position: (2, 2),
+                rows: 3,
+                cols: 1,
+            }],
+            food_spots: vec![(1, 1)],
+            ..WorldMap::new(vec![(1, 1)], WALL_COLOR)
+        };
+        assert_eq!(
+            validate(&map, 4, 4, Topology::Torus),
+            vec![
+                Problem::SpawnBlocked((1, 1)),
+                Problem::SpawnOverlap((0, 0)),
+                Problem::SpawnOutOfBounds((9, 9)),
+                Problem::FoodOutOfBounds((2, 2)),
+                Problem::FoodBlocked((1, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_pockets() {
+        // a ring of wall around (2, 2)
+        let walls = vec![(1, 2), (3, 2), (2, 1), (2, 3)];
+        let map = WorldMap::new(walls, WALL_COLOR);
+        assert_eq!(
+            validate(&map, 5, 5, Topology::Torus),
+            vec![Problem::Unreachable {
+                cells: 1,
+                example: (2, 2),
+            }]
+        );
+    }
+
+    #[test]
+    fn reaches_through_edges() {
+        // a full-width wall splits the board, but the board wraps top to bottom
+        let walls: Vec<Position> = (0..5).map(|col| (2, col)).collect();
+        let map = WorldMap::new(walls, WALL_COLOR);
+        assert_eq!(validate(&map, 5, 5, Topology::Torus), vec![]);
+        assert_eq!(validate(&map, 5, 5, Topology::Klein), vec![]);
+        // a bounded board doesn't wrap
+        assert!(matches!(
+            validate(&map, 5, 5, Topology::Bounded)[..],
+            [Problem::Unreachable { cells: 10, .. }]
+        ));
+
+        // a second wall closes the wrap
+        let walls: Vec<Position> = (0..5).flat_map(|col| vec![(0, col), (2, col)]).collect();
+        let map = WorldMap::new(walls, WALL_COLOR);
+        assert!(matches!(
+            validate(&map, 5, 5, Topology::Torus)[..],
+            [Problem::Unreachable { cells: 10, .. }]
+        ));
+    }
+
+    #[test]
+    fn reaches_through_portals() {
+        // a walled-in pocket at 2:2 with a portal in its wall
+        let walls = vec![(1, 2), (3, 2), (2, 1)];
+        let mut map = WorldMap::new(walls, WALL_COLOR);
+        map.portals.push(Portal::new((2, 3), (0, 0)));
+        assert_eq!(validate(&map, 5, 5, Topology::Torus), vec![]);
+    }
+
+    #[test]
+    fn gates_pass_one_way() {
+        // a wall splits the board, and the wrap is closed
+        let mut walls: Vec<Position> = (0..4).flat_map(|row| vec![(row, 0), (row, 2)]).collect();
+        walls.retain(|wall| *wall != (1, 2));
+        let mut map = WorldMap::new(walls, WALL_COLOR);
+
+        // a gate in the gap lets snakes through one way, but never back
+        map.place((1, 2), Tile::Gate(Direction::Right));
+        assert!(matches!(
+            validate(&map, 4, 4, Topology::Torus)[..],
+            [Problem::Unreachable { cells: 4, .. }]
+        ));
+
+        // a second gate in the other wall leads on round the wrap, back to the start
+        map.walls.retain(|wall| *wall != (3, 0));
+        map.place((3, 0), Tile::Gate(Direction::Right));
+        assert_eq!(validate(&map, 4, 4, Topology::Torus), vec![]);
+    }
+
+    #[test]
+    fn checks_obstacles() {
+        let map = WorldMap {
+            player_spawns: vec![spawn((0, 0))],
+            obstacles: vec![
+                Obstacle::mover(vec![(0, 1), (0, 0), (1, 1)], 1),
+                Obstacle::toggle(vec![(2, 2), (4, 4)], 1, 1),
+            ],
+            ..WorldMap::new(vec![(1, 1)], WALL_COLOR)
+        };
+        assert_eq!(
+            validate(&map, 4, 4, Topology::Torus),
+            vec![
+                Problem::ObstacleBlocked((0, 0)),
+                Problem::ObstacleBlocked((1, 1)),
+                Problem::ObstacleOutOfBounds((4, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_puzzles() {
+        let map = WorldMap {
+            player_spawns: vec![spawn((0, 0))],
+            obstacles: vec![Obstacle::toggle(vec![(3, 3)], 1, 1)],
+            keys: vec![Key {
+                position: (0, 0),
+                color: KeyColor::Red,
+            }],
+            doors: vec![
+                Door {
+                    cells: vec![(2, 0), (2, 1)],
+                    color: KeyColor::Red,
+                },
+                Door {
+                    cells: vec![(2, 2)],
+                    color: KeyColor::Blue,
+                },
+                Door {
+                    cells: vec![],
+                    color: KeyColor::Green,
+                },
+            ],
+            switches: vec![Switch {
+                position: (2, 1),
+                group: 0,
+            }],
+            switch_walls: vec![SwitchWall {
+                cells: vec![(3, 3), (1, 1), (5, 0)],
+                group: 0,
+                open: false,
+            }],
+            ..WorldMap::new(vec![(1, 1)], WALL_COLOR)
+        };
+        assert_eq!(
+            validate(&map, 4, 4, Topology::Torus),
+            vec![
+                Problem::PuzzleBlocked((0, 0)),
+                Problem::PuzzleBlocked((2, 1)),
+                Problem::PuzzleBlocked((3, 3)),
+                Problem::PuzzleBlocked((1, 1)),
+                Problem::PuzzleOutOfBounds((5, 0)),
+                Problem::NoKey((2, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_portals() {
+        let map = WorldMap {
+            player_spawns: vec![spawn((0, 0))],
+            portals: vec![Portal::new((0, 0), (1, 1)), Portal::new((1, 1), (7, 7))],
+            ..WorldMap::new(vec![(1, 1)], WALL_COLOR)
+        };
+        assert_eq!(
+            validate(&map, 4, 4, Topology::Torus),
+            vec![
+                Problem::PortalBlocked((0, 0)),
+                Problem::PortalBlocked((1, 1)),
+                Problem::PortalBlocked((1, 1)),
+                Problem::PortalOutOfBounds((7, 7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_solid_map() {
+        let map = WorldMap::new(vec![(0, 0)], WALL_COLOR);
+        assert_eq!(
+            validate(&map, 1, 1, Topology::Torus),
+            vec![Problem::NoFloor]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use super::*;
+
+/// How often a `MapWatcher` looks at its file, unless told otherwise
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches a level's file, reloading the map whenever the file changes
+///
+/// Changes are found by polling the file's modification time, at most once per interval. A
+/// level packed in a zip is reloaded when the archive changes.
+pub struct MapWatcher {
+    level: Level,
+    interval: Duration,
+    modified: Option<SystemTime>,
+    last_poll: Option<Instant>,
+}
+
+impl MapWatcher {
+    /// Start watching a level's file as it is now
+    pub fn new(level: Level) -> Self {
+        let mut watcher = MapWatcher {
+            level,
+            interval: POLL_INTERVAL,
+            modified: None,
+            last_poll: None,
+        };
+        watcher.modified = watcher.modified();
+        watcher
+    }
+
+    /// Look at the file no more often than `interval`
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the watched level
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    /// Check whether the file changed since the last look, and reload it if so
+    ///
+    /// Returns `None` while the file is unchanged, or when it's too soon to look again.
+    pub fn poll(&mut self) -> Option<Result<WorldMap, MapError>> {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| last_poll.elapsed() < self.interval)
+        {
+            return None;
+        }
+        self.last_poll = Some(Instant::now());
+
+        let modified = self.modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.level.load())
+    }
+
+    /// Returns when the watched file was last changed, if it can be read
+    fn modified(&self) -> Option<SystemTime> {
+        let path = match &self.level.source {
+            MapSource::File(path) => path,
+            MapSource::Zipped { archive, .. } => archive,
+        };
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Rewrite a file, moving its modification time on so the change is seen at once
+    fn rewrite(path: &Path, content: &str, age: u64) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(age))
+            .unwrap();
+    }
+
+    #[test]
+    fn reloads_changed_file() {
+        let path = std::env::temp_dir().join("rs_snake_watch.txt");
+        rewrite(&path, "#..\n...\n", 1);
+        let mut watcher = MapWatcher::new(Level::file(&path)).with_interval(Duration::from_secs(0));
+        assert!(watcher.poll().is_none());
+
+        rewrite(&path, "##.\n...\n", 2);
+        let world_map = watcher.poll().unwrap().unwrap();
+        assert_eq!(world_map.walls, vec![(0, 0), (0, 1)]);
+        assert!(watcher.poll().is_none());
+
+        // a broken edit is reported, and fixing it reloads again
+        rewrite(&path, "#.\n...\n", 3);
+        assert!(matches!(watcher.poll(), Some(Err(_))));
+        rewrite(&path, "...\n...\n", 4);
+        assert!(watcher.poll().unwrap().unwrap().walls.is_empty());
+    }
+
+    #[test]
+    fn waits_between_looks() {
+        let path = std::env::temp_dir().join("rs_snake_watch_wait.txt");
+        rewrite(&path, "#..\n...\n", 1);
+        let mut watcher =
+            MapWatcher::new(Level::file(&path)).with_interval(Duration::from_secs(60));
+        assert!(watcher.poll().is_none());
+        rewrite(&path, "##.\n...\n", 2);
+        assert!(watcher.poll().is_none());
+    }
+}
@@ -1,24 +1,54 @@
-use crate::types::GameMode;
+use crate::{map::Algorithm, types::Ruleset};
 
+mod custom_menu;
 mod main_menu;
+mod map_menu;
+pub use custom_menu::custom_menu;
 pub use main_menu::main_menu;
+pub use map_menu::map_menu;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum MenuEvent {
-    Start(GameMode),
+    /// Start a game played by the given rules
+    Start(Ruleset),
+    /// Open the custom game menu
+    Custom,
+    /// Select the next evil twin behavior
+    CycleBehavior,
+    /// Select the next enemy difficulty
+    CycleDifficulty,
+    /// Select the next number of enemies
+    CycleEnemies,
+    /// Select the next board topology
+    CycleTopology,
+    /// Toggle playing on a map's walls
+    CycleWalls,
+    /// Select the next speed curve
+    CycleSpeed,
+    /// Select the next goal
+    CycleGoal,
+    /// Play the map with the given index, or a random map
+    SelectMap(Option<usize>),
+    /// Play a freshly generated map with the given seed
+    Generate(Algorithm, u64),
+    /// Open the map editor
+    Edit,
     Quit,
 }
 
 /// A selectable item in a `Menu`
 #[derive(Debug, PartialEq)]
 pub struct MenuItem {
-    pub label: &'static str,
+    pub label: String,
     pub event: MenuEvent,
 }
 
 impl MenuItem {
-    pub fn new(label: &'static str, event: MenuEvent) -> Self {
-        MenuItem { label, event }
+    pub fn new(label: &str, event: MenuEvent) -> Self {
+        MenuItem {
+            label: label.to_string(),
+            event,
+        }
     }
 }
 
@@ -48,7 +78,7 @@ impl Menu {
         let (min, max) = (0, self.menu_items.len() - 1);
         let mut i = change + self.selection as i32;
         i = std::cmp::min(i, max as i32);
-        i = std::cmp::max(i, min as i32);
+        i = std::cmp::max(i, min);
         self.selection = i as usize;
     }
 
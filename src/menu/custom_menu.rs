@@ -0,0 +1,103 @@
+use sdl2::{event::Event, keyboard::Keycode, render::Canvas, ttf, video::Window, EventPump};
+
+use super::*;
+use crate::{
+    gfx,
+    types::{Behavior, EvilConfig, Ruleset},
+};
+
+/// Update the labels of the custom game settings to reflect `rules` and `config`
+fn label_settings(menu: &mut Menu, rules: &Ruleset, config: &EvilConfig) {
+    for item in menu.menu_items.iter_mut() {
+        match item.event {
+            MenuEvent::CycleTopology => item.label = format!("Edges: {}", rules.topology.name()),
+            MenuEvent::CycleWalls => {
+                let walls = if rules.labyrinth { "Labyrinth" } else { "Open" };
+                item.label = format!("Walls: {}", walls)
+            }
+            MenuEvent::CycleSpeed => item.label = format!("Speed: {}", rules.speed.name()),
+            MenuEvent::CycleBehavior => {
+                let behavior = if rules.enemies {
+                    config.behavior.name()
+                } else {
+                    "Off"
+                };
+                item.label = format!("Evil Twin: {}", behavior)
+            }
+            MenuEvent::CycleGoal => item.label = format!("Goal: {}", rules.goal.name()),
+            _ => (),
+        }
+    }
+}
+
+/// Mix the rules of a game. Each setting cycles on enter, and `rules` and `config` keep the
+/// choices for next time
+///
+/// Returns `Start` with the chosen rules, or `Quit` if the user backs out.
+pub fn custom_menu(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    font: &ttf::Font,
+    rules: &mut Ruleset,
+    config: &mut EvilConfig,
+) -> MenuEvent {
+    let mut custom_menu = Menu::new(vec![
+        MenuItem::new("Start Game", MenuEvent::Start(*rules)),
+        MenuItem::new("Edges", MenuEvent::CycleTopology),
+        MenuItem::new("Walls", MenuEvent::CycleWalls),
+        MenuItem::new("Speed", MenuEvent::CycleSpeed),
+        MenuItem::new("Evil Twin", MenuEvent::CycleBehavior),
+        MenuItem::new("Goal", MenuEvent::CycleGoal),
+    ]);
+
+    'menu: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                // back out on escape key
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'menu MenuEvent::Quit,
+
+                // movement keys
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                } => custom_menu.inc_selection(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                } => custom_menu.dec_selection(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => match custom_menu.select_item() {
+                    MenuEvent::CycleTopology => rules.topology = rules.topology.next(),
+                    MenuEvent::CycleWalls => rules.labyrinth = !rules.labyrinth,
+                    MenuEvent::CycleSpeed => rules.speed = rules.speed.next(),
+                    // off, then each behavior in turn
+                    MenuEvent::CycleBehavior => {
+                        if !rules.enemies {
+                            rules.enemies = true;
+                            config.behavior = Behavior::ALL[0];
+                        } else if config.behavior.next() == Behavior::ALL[0] {
+                            rules.enemies = false;
+                        } else {
+                            config.behavior = config.behavior.next();
+                        }
+                    }
+                    MenuEvent::CycleGoal => rules.goal = rules.goal.next(),
+                    _ => return MenuEvent::Start(*rules),
+                },
+
+                _ => continue 'menu,
+            }
+        }
+
+        // display frame
+        label_settings(&mut custom_menu, rules, config);
+        gfx::render_menu(canvas, font, &custom_menu);
+        gfx::display_frame(canvas);
+    }
+}
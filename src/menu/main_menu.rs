@@ -1,17 +1,52 @@
 use sdl2::{event::Event, keyboard::Keycode, render::Canvas, ttf, video::Window, EventPump};
 
 use super::*;
-use crate::{gfx, types::GameMode};
+use crate::{
+    gfx,
+    types::{EvilConfig, GameMode, Ruleset, Topology},
+};
 
+/// Update the labels of the enemy and topology settings to reflect `config` and `topology`
+fn label_settings(menu: &mut Menu, config: &EvilConfig, topology: Option<Topology>) {
+    for item in menu.menu_items.iter_mut() {
+        match item.event {
+            MenuEvent::CycleBehavior => {
+                item.label = format!("Evil Twin: {}", config.behavior.name())
+            }
+            MenuEvent::CycleDifficulty => {
+                item.label = format!("Difficulty: {}", config.difficulty.name())
+            }
+            MenuEvent::CycleEnemies => item.label = format!("Enemies: {}", config.count),
+            MenuEvent::CycleTopology => {
+                let name = topology.map_or("Mode Default", |topology| topology.name());
+                item.label = format!("Edges: {}", name)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Show the main menu. The preset modes are played on `topology` if one is chosen, and the
+/// Edges setting keeps the choice in it for next time
 pub fn main_menu(
     canvas: &mut Canvas<Window>,
     event_pump: &mut EventPump,
     font: &ttf::Font,
+    config: &mut EvilConfig,
+    topology: &mut Option<Topology>,
 ) -> MenuEvent {
+    let preset = |mode: GameMode| MenuEvent::Start(Ruleset::from(mode));
     let mut main_menu = Menu::new(vec![
-        MenuItem::new("Normal Mode", MenuEvent::Start(GameMode::Normal)),
-        MenuItem::new("Tal'ke Challenge", MenuEvent::Start(GameMode::Tal)),
-        MenuItem::new("Labyrinth Mode", MenuEvent::Start(GameMode::Map)),
+        MenuItem::new("Normal Mode", preset(GameMode::Normal)),
+        MenuItem::new("Tal'ke Challenge", preset(GameMode::Tal)),
+        MenuItem::new("Labyrinth Mode", preset(GameMode::Map)),
+        MenuItem::new("Battle Royale", preset(GameMode::Royale)),
+        MenuItem::new("Custom Game", MenuEvent::Custom),
+        MenuItem::new("Map Editor", MenuEvent::Edit),
+        MenuItem::new("Evil Twin", MenuEvent::CycleBehavior),
+        MenuItem::new("Difficulty", MenuEvent::CycleDifficulty),
+        MenuItem::new("Enemies", MenuEvent::CycleEnemies),
+        MenuItem::new("Edges", MenuEvent::CycleTopology),
         MenuItem::new("Quit", MenuEvent::Quit),
     ]);
 
@@ -37,13 +72,33 @@ pub fn main_menu(
                 Event::KeyDown {
                     keycode: Some(Keycode::Return),
                     ..
-                } => return main_menu.select_item().clone(),
+                } => match main_menu.select_item().clone() {
+                    MenuEvent::CycleBehavior => config.behavior = config.behavior.next(),
+                    MenuEvent::CycleDifficulty => config.difficulty = config.difficulty.next(),
+                    MenuEvent::CycleEnemies => config.count = config.next_count(),
+                    // cycle through every topology, then back to the mode's own
+                    MenuEvent::CycleTopology => {
+                        *topology = match *topology {
+                            None => Some(Topology::ALL[0]),
+                            Some(t) if t.next() == Topology::ALL[0] => None,
+                            Some(t) => Some(t.next()),
+                        }
+                    }
+                    MenuEvent::Start(rules) => {
+                        return MenuEvent::Start(Ruleset {
+                            topology: topology.unwrap_or(rules.topology),
+                            ..rules
+                        })
+                    }
+                    evt => return evt,
+                },
 
                 _ => continue 'menu,
             }
         }
 
         // display frame
+        label_settings(&mut main_menu, config, *topology);
         gfx::render_menu(canvas, font, &main_menu);
         gfx::display_frame(canvas);
     }
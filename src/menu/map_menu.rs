@@ -0,0 +1,121 @@
+use sdl2::{event::Event, keyboard::Keycode, render::Canvas, ttf, video::Window, EventPump};
+
+use super::*;
+use crate::{
+    gfx,
+    map::{self, Algorithm, GeneratedMapper, Level, Mapper},
+    types::{Grid, Topology, WorldMap},
+};
+
+/// Pixel size of the square a map preview is fitted into
+const PREVIEW_SIZE: u32 = 144;
+
+/// Browse the levels in `levels`, previewing each on the board it declares, or a `rows` x `cols`
+/// board if it doesn't say. Generated labyrinths are connected across edges joined as `topology`
+/// says
+///
+/// Returns `SelectMap` with the index of the chosen level, or `None` for a random one, or `Generate`
+/// with the algorithm and seed of the previewed labyrinth. Returns `Quit` if the user backs out.
+pub fn map_menu(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    font: &ttf::Font,
+    levels: &[Level],
+    (rows, cols): (u32, u32),
+    topology: Topology,
+) -> MenuEvent {
+    let loaded: Vec<Option<WorldMap>> = levels.iter().map(|level| level.load().ok()).collect();
+    let previews: Vec<Option<Grid>> = loaded
+        .iter()
+        .map(|world_map| {
+            world_map.as_ref().map(|world_map| {
+                let (rows, cols) = world_map.size.unwrap_or((rows, cols));
+                world_map.render(rows, cols)
+            })
+        })
+        .collect();
+
+    // generated labyrinths follow the loaded maps
+    let seeds: Vec<u64> = Algorithm::ALL.iter().map(|_| rand::random()).collect();
+    let generated = Algorithm::ALL
+        .iter()
+        .zip(seeds.iter())
+        .map(|(algorithm, seed)| {
+            GeneratedMapper::new(*algorithm, rows, cols)
+                .with_topology(topology)
+                .with_seed(*seed)
+                .load_map()
+                .ok()
+                .map(|world_map| world_map.render(rows, cols))
+        });
+    let previews: Vec<Option<Grid>> = previews.into_iter().chain(generated).collect();
+
+    let mut items = vec![MenuItem::new("Random", MenuEvent::SelectMap(None))];
+    for (i, (level, world_map)) in levels.iter().zip(loaded.iter()).enumerate() {
+        let file_name = map::map_name(&level.source.path());
+        let name = match world_map {
+            Some(world_map) => world_map.meta.name.clone().unwrap_or(file_name),
+            None => format!("{} (unreadable)", file_name),
+        };
+        let label = match &level.pack {
+            Some(pack) => format!("{}: {}", pack, name),
+            None => name,
+        };
+        items.push(MenuItem::new(&label, MenuEvent::SelectMap(Some(i))));
+    }
+    for (algorithm, seed) in Algorithm::ALL.iter().zip(seeds.iter()) {
+        let label = format!("New {}", algorithm.name());
+        items.push(MenuItem::new(
+            &label,
+            MenuEvent::Generate(*algorithm, *seed),
+        ));
+    }
+    let mut map_menu = Menu::new(items);
+
+    'menu: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                // back out on escape key
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'menu MenuEvent::Quit,
+
+                // movement keys
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                } => map_menu.inc_selection(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                } => map_menu.dec_selection(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => return map_menu.select_item().clone(),
+
+                _ => (),
+            }
+        }
+
+        // display frame
+        gfx::render_menu(canvas, font, &map_menu);
+        let preview = match map_menu.select_item() {
+            MenuEvent::SelectMap(Some(i)) => previews[*i].as_ref(),
+            MenuEvent::Generate(..) => previews[map_menu.selection() - 1].as_ref(),
+            _ => None,
+        };
+        if let Some(preview) = preview {
+            let (width, height) = canvas.window().size();
+            let (preview_rows, preview_cols) = (preview.len() as u32, preview[0].len() as u32);
+            let (cell_width, _) =
+                gfx::board_layout((PREVIEW_SIZE, PREVIEW_SIZE), preview_rows, preview_cols);
+            let x = (width - preview_cols * cell_width) / 2;
+            let y = height - preview_rows * cell_width - 2 * font.height() as u32;
+            gfx::render_preview(canvas, preview, cell_width, (x as i32, y as i32));
+        }
+        gfx::display_frame(canvas);
+    }
+}
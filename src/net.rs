@@ -0,0 +1,49 @@
+//! Networked multiplayer. Peers run identical `Gamestate` simulations in lockstep, exchanging
+//! only per-tick inputs over TCP. Spectators receive a read-only snapshot of every tick
+use std::{error::Error, fmt, io};
+
+mod message;
+mod session;
+mod spectate;
+
+pub use message::{Message, TickInput};
+pub use session::{apply_inputs, join, tick_input, Host, Session};
+pub use spectate::{Snapshot, Spectator, SpectatorServer};
+
+/// Version of the lockstep protocol. Peers must agree on it exactly
+pub const PROTOCOL_VERSION: u32 = 11;
+
+/// Maximum number of players in a networked game, including the host
+pub const MAX_PLAYERS: usize = 5;
+
+/// Errors that end a networked game
+#[derive(Debug)]
+pub enum NetError {
+    /// Underlying socket failure
+    Io(io::Error),
+    /// A peer sent something unexpected
+    Protocol(String),
+    /// Peer simulations diverged at the given tick
+    Desync(u64),
+    /// The remote peer left the game
+    Disconnected,
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Network error: {}", e),
+            Self::Protocol(msg) => write!(f, "Protocol error: {}", msg),
+            Self::Desync(tick) => write!(f, "Game desynchronized at tick {}", tick),
+            Self::Disconnected => write!(f, "Peer disconnected"),
+        }
+    }
+}
+
+impl Error for NetError {}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,585 @@
+use std::{fmt, str::FromStr};
+
+use super::{NetError, Snapshot};
+use crate::types::{
+    self, Behavior, Cell, Difficulty, Direction, Door, EnemySpawn, Goal, Key, KeyColor, Motion,
+    Obstacle, Portal, PortalEnd, Position, Ruleset, Spawn, Speed, Switch, SwitchWall, Tile,
+    Topology, WorldMap, Zone,
+};
+
+/// A single player's contribution to one simulation step
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TickInput {
+    /// No input this tick
+    Idle,
+    /// Change direction of travel
+    Turn(Direction),
+    /// Toggle the pause state
+    Pause,
+    /// The player has left the game
+    Left,
+}
+
+/// Messages exchanged between lockstep peers. One message per line of text
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    /// Client greeting, carrying the client's protocol version
+    Hello { version: u32 },
+    /// Host reply describing the game the client is joining
+    Start {
+        seed: u64,
+        player_id: usize,
+        players: usize,
+        rules: Ruleset,
+        size: (u32, u32),
+        enemies: Vec<EnemySpawn>,
+        /// Layout of the board. Empty outside labyrinth games
+        map: Box<WorldMap>,
+    },
+    /// Client input for a tick, with the client's state hash at the start of that tick
+    Input {
+        tick: u64,
+        input: TickInput,
+        hash: u64,
+    },
+    /// Every player's input for a tick, with the host's state hash at the start of that tick
+    Frame {
+        tick: u64,
+        inputs: Vec<TickInput>,
+        hash: u64,
+    },
+    /// Simulations diverged at the given tick
+    Desync { tick: u64 },
+    /// Picture of the game world sent to spectators
+    Snapshot(Snapshot),
+    /// Peer is leaving
+    Bye,
+}
+
+impl fmt::Display for TickInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let token = match self {
+            Self::Idle => "-",
+            Self::Turn(Direction::Up) => "U",
+            Self::Turn(Direction::Down) => "D",
+            Self::Turn(Direction::Left) => "L",
+            Self::Turn(Direction::Right) => "R",
+            Self::Pause => "P",
+            Self::Left => "X",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+impl FromStr for TickInput {
+    type Err = NetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "-" => Ok(Self::Idle),
+            "U" => Ok(Self::Turn(Direction::Up)),
+            "D" => Ok(Self::Turn(Direction::Down)),
+            "L" => Ok(Self::Turn(Direction::Left)),
+            "R" => Ok(Self::Turn(Direction::Right)),
+            "P" => Ok(Self::Pause),
+            "X" => Ok(Self::Left),
+            _ => Err(NetError::Protocol(format!("Unknown input '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Hello { version } => write!(f, "HELLO {}", version),
+            Self::Start {
+                seed,
+                player_id,
+                players,
+                rules,
+                size: (rows, cols),
+                enemies,
+                map,
+            } => {
+                write!(
+                    f,
+                    "START {} {} {} {:?}/{}/{:?}/{}/{:?} {} {} {}",
+                    seed,
+                    player_id,
+                    players,
+                    rules.topology,
+                    rules.labyrinth as u8,
+                    rules.speed,
+                    rules.enemies as u8,
+                    rules.goal,
+                    rows,
+                    cols,
+                    enemies.len()
+                )?;
+                for spawn in enemies {
+                    write!(f, " {}/", spawn.at_score)?;
+                    match spawn.position {
+                        Some((row, col)) => write!(f, "{}:{}", row, col)?,
+                        None => write!(f, "-")?,
+                    }
+                    let Cell { r, g, b, .. } = spawn.color;
+                    write!(
+                        f,
+                        "/{:?}/{:?}/{:02x}{:02x}{:02x}",
+                        spawn.behavior, spawn.difficulty, r, g, b
+                    )?;
+                }
+                for (row, col) in map.walls.iter() {
+                    write!(f, " {}:{}", row, col)?;
+                }
+                for ((row, col), tile) in map.tiles.iter() {
+                    write!(f, " t={}:{}:{}", row, col, tile.symbol())?;
+                }
+                for (tag, spawns) in [("p", &map.player_spawns), ("e", &map.enemy_spawns)] {
+                    for spawn in spawns.iter() {
+                        let (row, col) = spawn.position;
+                        let direction = TickInput::Turn(spawn.direction);
+                        write!(f, " {}={}:{}:{}", tag, row, col, direction)?;
+                    }
+                }
+                for zone in map.food_zones.iter() {
+                    let (row, col) = zone.position;
+                    write!(f, " z={}:{}:{}:{}", row, col, zone.rows, zone.cols)?;
+                }
+                for (row, col) in map.food_spots.iter() {
+                    write!(f, " f={}:{}", row, col)?;
+                }
+                for portal in map.portals.iter() {
+                    let [a, b] = portal.ends.map(|end| {
+                        let exit = end.exit.map_or(TickInput::Idle, TickInput::Turn);
+                        format!("{}:{}:{}", end.position.0, end.position.1, exit)
+                    });
+                    write!(f, " g={}:{}", a, b)?;
+                }
+                for obstacle in map.obstacles.iter() {
+                    let on = match obstacle.motion {
+                        Motion::Path(_) => "-".to_string(),
+                        Motion::Timer { on, .. } => on.to_string(),
+                    };
+                    write!(
+                        f,
+                        " o={}:{}:{}:{}",
+                        obstacle.period, obstacle.offset, obstacle.crush as u8, on
+                    )?;
+                    for (row, col) in obstacle.cells() {
+                        write!(f, ":{}:{}", row, col)?;
+                    }
+                }
+                for key in map.keys.iter() {
+                    let (row, col) = key.position;
+                    write!(f, " k={}:{}:{}", row, col, key.color.name())?;
+                }
+                for door in map.doors.iter() {
+                    write!(f, " d={}", door.color.name())?;
+                    for (row, col) in door.cells.iter() {
+                        write!(f, ":{}:{}", row, col)?;
+                    }
+                }
+                for switch in map.switches.iter() {
+                    let (row, col) = switch.position;
+                    write!(f, " s={}:{}:{}", row, col, switch.group)?;
+                }
+                for wall in map.switch_walls.iter() {
+                    write!(f, " w={}:{}", wall.group, wall.open as u8)?;
+                    for (row, col) in wall.cells.iter() {
+                        write!(f, ":{}:{}", row, col)?;
+                    }
+                }
+                if let Some(par) = map.meta.par {
+                    write!(f, " par={}", par)?;
+                }
+                Ok(())
+            }
+            Self::Input { tick, input, hash } => write!(f, "INPUT {} {} {}", tick, input, hash),
+            Self::Frame { tick, inputs, hash } => {
+                write!(f, "FRAME {} {}", tick, hash)?;
+                for input in inputs {
+                    write!(f, " {}", input)?;
+                }
+                Ok(())
+            }
+            Self::Desync { tick } => write!(f, "DESYNC {}", tick),
+            Self::Snapshot(snapshot) => write!(f, "SNAPSHOT {}", snapshot.to_tokens()),
+            Self::Bye => write!(f, "BYE"),
+        }
+    }
+}
+
+/// Parse a single whitespace separated token
+fn parse_token<T: FromStr>(token: Option<&str>) -> Result<T, NetError> {
+    let token = token.ok_or_else(|| NetError::Protocol("Truncated message".to_string()))?;
+    token
+        .parse()
+        .map_err(|_| NetError::Protocol(format!("Malformed token '{}'", token)))
+}
+
+/// Find the value in `all` with the `Debug` name `name`
+fn parse_named<T: Copy + fmt::Debug>(all: &[T], name: Option<&str>) -> Option<T> {
+    all.iter()
+        .copied()
+        .find(|value| Some(format!("{:?}", value).as_str()) == name)
+}
+
+/// Parse a `topology/labyrinth/speed/enemies/goal` ruleset, with enums by their `Debug` names
+fn parse_rules(token: Option<&str>) -> Result<Ruleset, NetError> {
+    let unknown = || NetError::Protocol(format!("Unknown ruleset {:?}", token));
+    let mut parts = token.unwrap_or_default().split('/');
+
+    Ok(Ruleset {
+        topology: parse_named(&Topology::ALL, parts.next()).ok_or_else(unknown)?,
+        labyrinth: parse_token::<u8>(parts.next())? != 0,
+        speed: parse_named(&Speed::ALL, parts.next()).ok_or_else(unknown)?,
+        enemies: parse_token::<u8>(parts.next())? != 0,
+        goal: parse_named(&Goal::ALL, parts.next()).ok_or_else(unknown)?,
+    })
+}
+
+/// Parse a `score/row:col/Behavior/Difficulty/rrggbb` enemy spawn. The position may be `-`
+fn parse_spawn(token: &str) -> Result<EnemySpawn, NetError> {
+    let unknown = || NetError::Protocol(format!("Unknown enemy '{}'", token));
+    let mut parts = token.split('/');
+    let at_score = parse_token(parts.next())?;
+    let position = match parts.next() {
+        Some("-") => None,
+        Some(position) => Some(parse_position(position)?),
+        None => return Err(unknown()),
+    };
+    let behavior = match parts.next() {
+        Some("Mirror") => Behavior::Mirror,
+        Some("Chaser") => Behavior::Chaser,
+        Some("Thief") => Behavior::Thief,
+        Some("Patroller") => Behavior::Patroller,
+        _ => return Err(unknown()),
+    };
+    let difficulty = match parts.next() {
+        Some("Easy") => Difficulty::Easy,
+        Some("Normal") => Difficulty::Normal,
+        Some("Hard") => Difficulty::Hard,
+        _ => return Err(unknown()),
+    };
+    let rgb = parts
+        .next()
+        .and_then(|color| u32::from_str_radix(color, 16).ok())
+        .ok_or_else(unknown)?;
+
+    Ok(EnemySpawn {
+        at_score,
+        position,
+        color: Cell::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8),
+        behavior,
+        difficulty,
+    })
+}
+
+/// Parse a `row:col` wall position
+fn parse_position(token: &str) -> Result<Position, NetError> {
+    let mut parts = token.split(':');
+    let row = parse_token(parts.next())?;
+    let col = parse_token(parts.next())?;
+    Ok((row, col))
+}
+
+/// Parse a list of `row:col` pairs run together, eg `1:2:1:3`
+fn parse_cells<'a, I: Iterator<Item = &'a str>>(parts: I) -> Result<Vec<Position>, NetError> {
+    let numbers = parts
+        .map(|part| parse_token(Some(part)))
+        .collect::<Result<Vec<u32>, _>>()?;
+    numbers
+        .chunks(2)
+        .map(|cell| match cell {
+            [row, col] => Ok((*row, *col)),
+            _ => Err(NetError::Protocol("Unpaired cell coordinate".to_string())),
+        })
+        .collect()
+}
+
+/// Parse the map tokens ending a START message: `row:col` walls, then tagged `t=` tiles, `p=` and
+/// `e=` spawns, `z=` food zones, `f=` food spots, `g=` portals, `o=` obstacles, `k=` keys, `d=`
+/// doors, `s=` switches, `w=` switch walls and `par=`
+fn parse_map<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Result<WorldMap, NetError> {
+    let mut map = WorldMap::new(vec![], types::WALL_COLOR);
+    for token in tokens {
+        let malformed = || NetError::Protocol(format!("Malformed token '{}'", token));
+        let key_color =
+            |name: Option<&str>| name.and_then(KeyColor::from_name).ok_or_else(malformed);
+        let (tag, value) = match token.split_once('=') {
+            Some((tag, value)) => (tag, value),
+            None => {
+                map.walls.push(parse_position(token)?);
+                continue;
+            }
+        };
+        let mut parts = value.split(':');
+        match tag {
+            "t" => {
+                let position = (parse_token(parts.next())?, parse_token(parts.next())?);
+                let tile = parts
+                    .next()
+                    .and_then(|symbol| symbol.chars().next())
+                    .and_then(Tile::from_symbol)
+                    .ok_or_else(malformed)?;
+                map.tiles.push((position, tile));
+            }
+            "p" | "e" => {
+                let position = (parse_token(parts.next())?, parse_token(parts.next())?);
+                let direction = match parse_token(parts.next())? {
+                    TickInput::Turn(direction) => direction,
+                    _ => return Err(malformed()),
+                };
+                let spawn = Spawn {
+                    position,
+                    direction,
+                };
+                if tag == "p" {
+                    map.player_spawns.push(spawn);
+                } else {
+                    map.enemy_spawns.push(spawn);
+                }
+            }
+            "z" => map.food_zones.push(Zone {
+                position: (parse_token(parts.next())?, parse_token(parts.next())?),
+                rows: parse_token(parts.next())?,
+                cols: parse_token(parts.next())?,
+            }),
+            "f" => map
+                .food_spots
+                .push((parse_token(parts.next())?, parse_token(parts.next())?)),
+            "g" => {
+                let mut end = || -> Result<PortalEnd, NetError> {
+                    let position = (parse_token(parts.next())?, parse_token(parts.next())?);
+                    let exit = match parse_token(parts.next())? {
+                        TickInput::Turn(direction) => Some(direction),
+                        TickInput::Idle => None,
+                        _ => return Err(malformed()),
+                    };
+                    Ok(PortalEnd { position, exit })
+                };
+                map.portals.push(Portal {
+                    ends: [end()?, end()?],
+                });
+            }
+            "o" => {
+                let period = parse_token(parts.next())?;
+                let offset = parse_token(parts.next())?;
+                let crush = parse_token::<u8>(parts.next())? == 1;
+                let on = parts.next();
+                let cells = parse_cells(parts)?;
+                let motion = match on {
+                    Some("-") => Motion::Path(cells),
+                    on => Motion::Timer {
+                        cells,
+                        on: parse_token(on)?,
+                    },
+                };
+                map.obstacles.push(Obstacle {
+                    motion,
+                    period,
+                    offset,
+                    crush,
+                });
+            }
+            "k" => map.keys.push(Key {
+                position: (parse_token(parts.next())?, parse_token(parts.next())?),
+                color: key_color(parts.next())?,
+            }),
+            "d" => map.doors.push(Door {
+                color: key_color(parts.next())?,
+                cells: parse_cells(parts)?,
+            }),
+            "s" => map.switches.push(Switch {
+                position: (parse_token(parts.next())?, parse_token(parts.next())?),
+                group: parse_token(parts.next())?,
+            }),
+            "w" => map.switch_walls.push(SwitchWall {
+                group: parse_token(parts.next())?,
+                open: parse_token::<u8>(parts.next())? == 1,
+                cells: parse_cells(parts)?,
+            }),
+            "par" => map.meta.par = Some(parse_token(Some(value))?),
+            _ => return Err(malformed()),
+        }
+    }
+
+    Ok(map)
+}
+
+impl FromStr for Message {
+    type Err = NetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        match tokens.next() {
+            Some("HELLO") => Ok(Self::Hello {
+                version: parse_token(tokens.next())?,
+            }),
+            Some("START") => {
+                let seed = parse_token(tokens.next())?;
+                let player_id = parse_token(tokens.next())?;
+                let players = parse_token(tokens.next())?;
+                let rules = parse_rules(tokens.next())?;
+                let size = (parse_token(tokens.next())?, parse_token(tokens.next())?);
+                let count: usize = parse_token(tokens.next())?;
+                let enemies = (0..count)
+                    .map(|_| parse_spawn(tokens.next().unwrap_or_default()))
+                    .collect::<Result<_, _>>()?;
+                Ok(Self::Start {
+                    seed,
+                    player_id,
+                    players,
+                    rules,
+                    size,
+                    enemies,
+                    map: Box::new(parse_map(tokens)?),
+                })
+            }
+            Some("INPUT") => Ok(Self::Input {
+                tick: parse_token(tokens.next())?,
+                input: parse_token(tokens.next())?,
+                hash: parse_token(tokens.next())?,
+            }),
+            Some("FRAME") => Ok(Self::Frame {
+                tick: parse_token(tokens.next())?,
+                hash: parse_token(tokens.next())?,
+                inputs: tokens.map(str::parse).collect::<Result<_, _>>()?,
+            }),
+            Some("DESYNC") => Ok(Self::Desync {
+                tick: parse_token(tokens.next())?,
+            }),
+            Some("SNAPSHOT") => Ok(Self::Snapshot(Snapshot::from_tokens(tokens)?)),
+            Some("BYE") => Ok(Self::Bye),
+            _ => Err(NetError::Protocol(format!("Unknown message '{}'", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::GameMode;
+
+    fn round_trip(msg: Message) {
+        let actual: Message = msg.to_string().parse().unwrap();
+        assert_eq!(actual, msg);
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        round_trip(Message::Hello { version: 1 });
+        round_trip(Message::Start {
+            seed: 42,
+            player_id: 1,
+            players: 2,
+            rules: Ruleset {
+                topology: Topology::Klein,
+                speed: Speed::Accelerating,
+                ..GameMode::Map.into()
+            },
+            size: (36, 20),
+            enemies: vec![
+                EnemySpawn::new(Behavior::Thief, Difficulty::Hard),
+                EnemySpawn {
+                    at_score: 5,
+                    position: Some((3, 4)),
+                    color: Cell::RGB(1, 2, 255),
+                    ..EnemySpawn::new(Behavior::Patroller, Difficulty::Easy)
+                },
+            ],
+            map: Box::new(WorldMap {
+                tiles: vec![((0, 1), Tile::Rock)],
+                player_spawns: vec![Spawn {
+                    position: (4, 5),
+                    direction: Direction::Left,
+                }],
+                enemy_spawns: vec![Spawn {
+                    position: (20, 5),
+                    direction: Direction::Up,
+                }],
+                food_zones: vec![Zone {
+                    position: (10, 10),
+                    rows: 3,
+                    cols: 4,
+                }],
+                food_spots: vec![(7, 7)],
+                portals: vec![Portal {
+                    ends: [
+                        PortalEnd {
+                            position: (2, 3),
+                            exit: Some(Direction::Down),
+                        },
+                        PortalEnd {
+                            position: (30, 9),
+                            exit: None,
+                        },
+                    ],
+                }],
+                obstacles: vec![
+                    Obstacle::mover(vec![(4, 4), (4, 5)], 3).with_offset(1),
+                    Obstacle::toggle(vec![(6, 6), (6, 7)], 2, 5).crushing(),
+                ],
+                keys: vec![Key {
+                    position: (8, 8),
+                    color: KeyColor::Yellow,
+                }],
+                doors: vec![Door {
+                    cells: vec![(9, 8), (9, 9)],
+                    color: KeyColor::Yellow,
+                }],
+                switches: vec![Switch {
+                    position: (11, 1),
+                    group: 4,
+                }],
+                switch_walls: vec![SwitchWall {
+                    cells: vec![(12, 1)],
+                    group: 4,
+                    open: true,
+                }],
+                ..WorldMap::new(vec![(0, 1), (35, 2)], types::WALL_COLOR)
+            }),
+        });
+        round_trip(Message::Start {
+            seed: 1,
+            player_id: 2,
+            players: 3,
+            rules: GameMode::Royale.into(),
+            size: (36, 36),
+            enemies: vec![],
+            map: Box::new(WorldMap::new(vec![], types::WALL_COLOR)),
+        });
+        round_trip(Message::Input {
+            tick: 3,
+            input: TickInput::Turn(Direction::Left),
+            hash: u64::MAX,
+        });
+        round_trip(Message::Frame {
+            tick: 3,
+            inputs: vec![TickInput::Idle, TickInput::Pause, TickInput::Left],
+            hash: 9,
+        });
+        round_trip(Message::Desync { tick: 10 });
+        round_trip(Message::Bye);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!("NOPE".parse::<Message>().is_err());
+        assert!("INPUT 1 Q 2".parse::<Message>().is_err());
+        assert!("FRAME".parse::<Message>().is_err());
+        assert!("START 1 1 2 Normal 36 36 1 0/-/Sneaky/Hard/ffffff"
+            .parse::<Message>()
+            .is_err());
+        assert!("START 1 1 2 Normal 36 36 2 0/-/Chaser/Hard/ffffff"
+            .parse::<Message>()
+            .is_err());
+        assert!("START 1 1 2 Map 36 36 0 p=1:2:P"
+            .parse::<Message>()
+            .is_err());
+        assert!("START 1 1 2 Map 36 36 0 t=1:2:?"
+            .parse::<Message>()
+            .is_err());
+    }
+}
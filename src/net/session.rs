@@ -0,0 +1,552 @@
+use std::{
+    io::{self, prelude::*, BufReader},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use super::{Message, NetError, TickInput, MAX_PLAYERS, PROTOCOL_VERSION};
+use crate::{
+    types::{self, GameEvent, Goal, SnakeEvent, WorldMap},
+    world::{Controller, Gamestate, ROYALE_SNAKES},
+};
+
+/// How long to wait on a silent peer before treating it as disconnected
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connected remote player
+struct Peer {
+    player_id: usize,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    connected: bool,
+}
+
+impl Peer {
+    fn new(stream: TcpStream, player_id: usize) -> Result<Self, NetError> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        Ok(Peer {
+            player_id,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            connected: true,
+        })
+    }
+
+    /// Send a single message
+    fn send(&mut self, msg: &Message) -> Result<(), NetError> {
+        writeln!(self.writer, "{}", msg)?;
+        Ok(())
+    }
+
+    /// Block until a single message arrives
+    fn recv(&mut self) -> Result<Message, NetError> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(NetError::Disconnected),
+            Ok(_) => line.trim().parse(),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Err(NetError::Disconnected),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => Err(NetError::Disconnected),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A connection that has yet to introduce itself
+struct Pending {
+    stream: TcpStream,
+    /// Bytes of the greeting received so far
+    greeting: Vec<u8>,
+    since: Instant,
+}
+
+impl Pending {
+    fn new(stream: TcpStream) -> Result<Self, NetError> {
+        stream.set_nonblocking(true)?;
+        Ok(Pending {
+            stream,
+            greeting: vec![],
+            since: Instant::now(),
+        })
+    }
+
+    /// Read whatever has arrived without blocking. Returns the greeting once a whole line is in
+    fn poll(&mut self) -> Result<Option<Message>, NetError> {
+        let mut buf = [0; 64];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(n) => self.greeting.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match self.greeting.iter().position(|b| *b == b'\n') {
+            Some(end) => Ok(Some(
+                String::from_utf8_lossy(&self.greeting[..end])
+                    .trim()
+                    .parse()?,
+            )),
+            None if self.since.elapsed() > PEER_TIMEOUT => Err(NetError::Disconnected),
+            None => Ok(None),
+        }
+    }
+
+    /// Turn the connection away
+    fn refuse(mut self) {
+        let _ = writeln!(self.stream, "{}", Message::Bye);
+    }
+}
+
+/// A game waiting for remote players to join
+pub struct Host {
+    listener: TcpListener,
+    pending: Vec<Pending>,
+    peers: Vec<Peer>,
+}
+
+impl Host {
+    /// Listen for remote players on the given address
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, NetError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Host {
+            listener,
+            pending: vec![],
+            peers: vec![],
+        })
+    }
+
+    /// Returns the address the host is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept any pending connections and greetings without blocking. Returns the number of
+    /// joined players
+    ///
+    /// Connections are turned away once the game is full, and a connection that misbehaves is
+    /// dropped without disturbing the others.
+    pub fn poll_accept(&mut self) -> usize {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match Pending::new(stream) {
+                    Ok(pending) => self.pending.push(pending),
+                    Err(e) => println!("Dropping connection from {}: {}", addr, e),
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Unable to accept connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        for mut pending in std::mem::take(&mut self.pending) {
+            let full = self.peers.len() + 1 >= MAX_PLAYERS;
+            match pending.poll() {
+                Ok(None) if !full => self.pending.push(pending),
+                Ok(Some(Message::Hello { version })) if version == PROTOCOL_VERSION && !full => {
+                    match Peer::new(pending.stream, self.peers.len() + 1) {
+                        Ok(peer) => self.peers.push(peer),
+                        Err(e) => println!("Dropping joining player: {}", e),
+                    }
+                }
+                // full, or not a compatible client
+                Ok(_) => pending.refuse(),
+                Err(e) => {
+                    println!("Dropping joining player: {}", e);
+                    pending.refuse();
+                }
+            }
+        }
+
+        self.peers.len()
+    }
+
+    /// Begin the game, sending every joined player a description of `state`
+    pub fn start(mut self, state: Gamestate) -> Result<(Session, Gamestate), NetError> {
+        let seed = rand::random();
+        let mut state = state.with_seed(seed);
+        let players = self.peers.len() + 1;
+        add_players(&mut state, players);
+
+        let map = state
+            .world_map()
+            .cloned()
+            .unwrap_or_else(|| WorldMap::new(vec![], types::WALL_COLOR));
+        for peer in self.peers.iter_mut() {
+            peer.send(&Message::Start {
+                seed,
+                player_id: peer.player_id,
+                players,
+                rules: state.rules(),
+                size: state.world_size,
+                enemies: state.enemy_spawns().to_vec(),
+                map: Box::new(map.clone()),
+            })?;
+        }
+
+        let session = Session {
+            role: Role::Host(self.peers),
+            player_id: 0,
+            players,
+        };
+        Ok((session, state))
+    }
+}
+
+/// Join a hosted game. Blocks until the host starts the game
+pub fn join<A: ToSocketAddrs>(addr: A) -> Result<(Session, Gamestate), NetError> {
+    let mut peer = Peer::new(TcpStream::connect(addr)?, 0)?;
+    peer.send(&Message::Hello {
+        version: PROTOCOL_VERSION,
+    })?;
+    // the host may take a while gathering other players
+    peer.reader.get_ref().set_read_timeout(None)?;
+    let start = peer.recv()?;
+    peer.reader.get_ref().set_read_timeout(Some(PEER_TIMEOUT))?;
+
+    match start {
+        Message::Start {
+            seed,
+            player_id,
+            players,
+            rules,
+            size: (rows, cols),
+            enemies,
+            map,
+        } => {
+            let mut state = Gamestate::new(rows, cols, rules, Some(*map))
+                .with_seed(seed)
+                .with_enemies(enemies);
+            add_players(&mut state, players);
+
+            let session = Session {
+                role: Role::Client(peer),
+                player_id,
+                players,
+            };
+            Ok((session, state))
+        }
+        Message::Bye => Err(NetError::Disconnected),
+        msg => Err(NetError::Protocol(format!("Expected START, got {}", msg))),
+    }
+}
+
+/// Add a snake for every remote player, and any bots the ruleset calls for
+///
+/// Host and clients must populate the board identically.
+fn add_players(state: &mut Gamestate, players: usize) {
+    for _ in 1..players {
+        state.add_player(Controller::Human);
+    }
+    if state.rules().goal == Goal::LastStanding {
+        state.fill_with_bots(ROYALE_SNAKES);
+    }
+}
+
+/// Which end of the connection this session is
+enum Role {
+    Host(Vec<Peer>),
+    Client(Peer),
+}
+
+/// A running networked game
+pub struct Session {
+    role: Role,
+    player_id: usize,
+    players: usize,
+}
+
+impl Session {
+    /// Returns the id of the locally controlled player
+    pub fn player_id(&self) -> usize {
+        self.player_id
+    }
+
+    /// Trade the local input for every player's input for the next tick of `state`
+    ///
+    /// `state` must not have been simulated past the previous exchange; its hash is compared with
+    /// the other peers' to detect diverging simulations.
+    pub fn exchange(
+        &mut self,
+        input: TickInput,
+        state: &Gamestate,
+    ) -> Result<Vec<TickInput>, NetError> {
+        let tick = state.tick;
+        let hash = state.state_hash();
+
+        match &mut self.role {
+            Role::Host(peers) => {
+                let mut inputs = vec![TickInput::Idle; self.players];
+                inputs[0] = input;
+
+                for peer in peers.iter_mut().filter(|peer| peer.connected) {
+                    match peer.recv() {
+                        Ok(Message::Input {
+                            tick: peer_tick,
+                            hash: peer_hash,
+                            ..
+                        }) if peer_tick != tick || peer_hash != hash => {
+                            broadcast(peers, &Message::Desync { tick });
+                            return Err(NetError::Desync(tick));
+                        }
+                        Ok(Message::Input { input, .. }) => inputs[peer.player_id] = input,
+                        Ok(Message::Desync { .. }) => {
+                            broadcast(peers, &Message::Desync { tick });
+                            return Err(NetError::Desync(tick));
+                        }
+                        _ => {
+                            peer.connected = false;
+                            inputs[peer.player_id] = TickInput::Left;
+                        }
+                    }
+                }
+
+                let frame = Message::Frame {
+                    tick,
+                    inputs: inputs.clone(),
+                    hash,
+                };
+                broadcast(peers, &frame);
+
+                Ok(inputs)
+            }
+            Role::Client(host) => {
+                host.send(&Message::Input { tick, input, hash })?;
+                match host.recv()? {
+                    Message::Frame {
+                        tick: host_tick,
+                        hash: host_hash,
+                        ..
+                    } if host_tick != tick || host_hash != hash => {
+                        let _ = host.send(&Message::Desync { tick });
+                        Err(NetError::Desync(tick))
+                    }
+                    Message::Frame { inputs, .. } if inputs.len() == self.players => Ok(inputs),
+                    Message::Desync { tick } => Err(NetError::Desync(tick)),
+                    Message::Bye => Err(NetError::Disconnected),
+                    msg => Err(NetError::Protocol(format!("Expected FRAME, got {}", msg))),
+                }
+            }
+        }
+    }
+
+    /// Leave the game, notifying the other peers
+    pub fn close(mut self) {
+        match &mut self.role {
+            Role::Host(peers) => broadcast(peers, &Message::Bye),
+            Role::Client(host) => {
+                let _ = host.send(&Message::Bye);
+            }
+        }
+    }
+}
+
+/// Send a message to every connected peer, dropping any that fail
+fn broadcast(peers: &mut [Peer], msg: &Message) {
+    for peer in peers.iter_mut().filter(|peer| peer.connected) {
+        if peer.send(msg).is_err() {
+            peer.connected = false;
+        }
+    }
+}
+
+/// Convert a local input event to its networked form
+pub fn tick_input(evt: Option<SnakeEvent>) -> TickInput {
+    match evt {
+        Some(SnakeEvent::Input(d)) => TickInput::Turn(d),
+        Some(SnakeEvent::Game(GameEvent::Pause)) => TickInput::Pause,
+        _ => TickInput::Idle,
+    }
+}
+
+/// Apply every player's input for one tick to `state`
+pub fn apply_inputs(state: &mut Gamestate, inputs: &[TickInput]) {
+    for (player, input) in inputs.iter().enumerate() {
+        match input {
+            TickInput::Idle => (),
+            TickInput::Turn(d) => state.handle_player_input(player, Some(SnakeEvent::Input(*d))),
+            TickInput::Pause => {
+                state.handle_player_input(player, Some(SnakeEvent::Game(GameEvent::Pause)))
+            }
+            TickInput::Left => state.remove_player(player),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::types::{Behavior, Difficulty, Direction, EvilConfig, GameMode};
+
+    /// A game with a blundering chaser, which exercises the shared rng
+    fn chaser_game() -> Gamestate {
+        Gamestate::new(36, 36, GameMode::Normal, None).with_evil(EvilConfig {
+            behavior: Behavior::Chaser,
+            difficulty: Difficulty::Easy,
+            count: 2,
+        })
+    }
+
+    /// Host `state` on loopback with a single remote player running `client` on another thread
+    fn host_with_client<F, T>(
+        state: Gamestate,
+        client: F,
+    ) -> (Session, Gamestate, thread::JoinHandle<T>)
+    where
+        F: FnOnce(Session, Gamestate) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut host = Host::bind("127.0.0.1:0").unwrap();
+        let addr = host.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (session, state) = join(addr).unwrap();
+            client(session, state)
+        });
+
+        while host.poll_accept() < 1 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let (session, state) = host.start(state).unwrap();
+
+        (session, state, handle)
+    }
+
+    /// Connect to `host` and send a raw greeting line, if any
+    fn greet(host: &Host, greeting: Option<&str>) -> BufReader<TcpStream> {
+        let mut stream = TcpStream::connect(host.local_addr().unwrap()).unwrap();
+        stream.set_read_timeout(Some(PEER_TIMEOUT)).unwrap();
+        if let Some(greeting) = greeting {
+            writeln!(stream, "{}", greeting).unwrap();
+        }
+        BufReader::new(stream)
+    }
+
+    /// Poll `host` until `joined` players are in, or give up after a while
+    fn accept_until(host: &mut Host, joined: usize) -> usize {
+        let started = Instant::now();
+        while host.poll_accept() < joined && started.elapsed() < PEER_TIMEOUT {
+            thread::sleep(Duration::from_millis(5));
+        }
+        host.poll_accept()
+    }
+
+    /// Run a single lockstep tick
+    fn step(
+        session: &mut Session,
+        state: &mut Gamestate,
+        input: TickInput,
+    ) -> Result<(), NetError> {
+        let inputs = session.exchange(input, state)?;
+        apply_inputs(state, &inputs);
+        state.refresh_grid();
+        state.simulate(1);
+        Ok(())
+    }
+
+    #[test]
+    fn lobby_polls_greetings() {
+        let mut host = Host::bind("127.0.0.1:0").unwrap();
+        // a silent connection doesn't hold up the lobby
+        let _silent = greet(&host, None);
+        let started = Instant::now();
+        assert_eq!(host.poll_accept(), 0);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // nor does a garbled one, which is turned away
+        let mut garbled = greet(&host, Some("HELO"));
+        let hello = format!("HELLO {}", PROTOCOL_VERSION);
+        let _player = greet(&host, Some(&hello));
+        assert_eq!(accept_until(&mut host, 1), 1);
+        let mut line = String::new();
+        garbled.read_line(&mut line).unwrap();
+        assert_eq!(line.trim(), "BYE");
+    }
+
+    #[test]
+    fn lobby_turns_away_extra_players() {
+        let mut host = Host::bind("127.0.0.1:0").unwrap();
+        let hello = format!("HELLO {}", PROTOCOL_VERSION);
+        let _players: Vec<_> = (1..MAX_PLAYERS)
+            .map(|_| greet(&host, Some(&hello)))
+            .collect();
+        assert_eq!(accept_until(&mut host, MAX_PLAYERS - 1), MAX_PLAYERS - 1);
+
+        let mut extra = greet(&host, Some(&hello));
+        let mut line = String::new();
+        while line.is_empty() {
+            host.poll_accept();
+            thread::sleep(Duration::from_millis(5));
+            let _ = extra.read_line(&mut line);
+        }
+        assert_eq!(line.trim(), "BYE");
+        assert_eq!(host.poll_accept(), MAX_PLAYERS - 1);
+    }
+
+    #[test]
+    fn royale_peers_seat_the_same_snakes() {
+        let state = Gamestate::new(36, 36, GameMode::Royale, None);
+        let (_, state, handle) =
+            host_with_client(state, |_, state| (state.players.len(), state.state_hash()));
+        let (players, hash) = handle.join().unwrap();
+        assert_eq!(state.players.len(), ROYALE_SNAKES);
+        assert_eq!(state.players[1].controller, Controller::Human);
+        assert_eq!(players, ROYALE_SNAKES);
+        assert_eq!(hash, state.state_hash());
+    }
+
+    #[test]
+    fn peers_stay_in_sync() {
+        let turns = [Direction::Right, Direction::Down, Direction::Left];
+        let (mut session, mut state, handle) =
+            host_with_client(chaser_game(), move |mut session, mut state| {
+                assert_eq!(session.player_id(), 1);
+                for i in 0..30 {
+                    let input = TickInput::Turn(turns[i % 3]);
+                    step(&mut session, &mut state, input).unwrap();
+                }
+                state.state_hash()
+            });
+
+        for i in 0..30 {
+            let input = TickInput::Turn(turns[(i + 1) % 3]);
+            step(&mut session, &mut state, input).unwrap();
+        }
+
+        assert_eq!(state.players.len(), 2);
+        assert_eq!(handle.join().unwrap(), state.state_hash());
+    }
+
+    #[test]
+    fn host_survives_client_leaving() {
+        let (mut session, mut state, handle) =
+            host_with_client(chaser_game(), |session, _| session.close());
+        handle.join().unwrap();
+
+        let inputs = session.exchange(TickInput::Idle, &state).unwrap();
+        assert_eq!(inputs, vec![TickInput::Idle, TickInput::Left]);
+        apply_inputs(&mut state, &inputs);
+        assert!(!state.players[1].alive);
+    }
+
+    #[test]
+    fn detects_desync() {
+        let (mut session, mut state, handle) =
+            host_with_client(chaser_game(), |mut session, mut state| {
+                state.players[0].score += 1;
+                step(&mut session, &mut state, TickInput::Idle)
+            });
+
+        let result = step(&mut session, &mut state, TickInput::Idle);
+        assert!(matches!(result, Err(NetError::Desync(0))));
+        assert!(matches!(handle.join().unwrap(), Err(NetError::Desync(0))));
+    }
+}
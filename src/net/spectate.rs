@@ -0,0 +1,332 @@
+use std::{
+    io::{self, prelude::*},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use super::{Message, NetError};
+use crate::{
+    types::{self, Cell, Grid, Position},
+    world::Gamestate,
+};
+
+/// How long a slow spectator may hold up the game before being dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A read-only picture of the game world at one tick
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub world_size: (u32, u32),
+    /// Every player's score, the local player's first
+    pub scores: Vec<usize>,
+    /// Colored groups of cells, painted in order over the background
+    pub layers: Vec<(Cell, Vec<Position>)>,
+}
+
+impl Snapshot {
+    /// Capture the visible state of `state`
+    pub fn from_state(state: &Gamestate) -> Self {
+        // terrain, grouped by color
+        let mut layers: Vec<(Cell, Vec<Position>)> = vec![];
+        for (row, cells) in (0..).zip(state.grid_init()) {
+            for (col, cell) in (0..).zip(cells) {
+                if cell == types::BG_COLOR {
+                    continue;
+                }
+                match layers.iter_mut().find(|(color, _)| *color == cell) {
+                    Some((_, positions)) => positions.push((row, col)),
+                    None => layers.push((cell, vec![(row, col)])),
+                }
+            }
+        }
+
+        for player in state.players.iter().filter(|player| player.alive) {
+            layers.push((
+                player.snake.cell,
+                player.snake.body.iter().copied().collect(),
+            ));
+        }
+        for evil in state.enemies.iter() {
+            layers.push((evil.snake.cell, evil.snake.body.iter().copied().collect()));
+        }
+        layers.push((state.food.cell, state.food.body.iter().copied().collect()));
+        layers.push((types::FOOD_COLOR, state.scraps.clone()));
+        layers.push((types::DRILL_COLOR, state.drill.into_iter().collect()));
+        let scores = state.scores();
+
+        Snapshot {
+            tick: state.tick,
+            world_size: state.world_size,
+            scores,
+            layers,
+        }
+    }
+
+    /// Paint the snapshot onto a fresh `Grid`
+    pub fn to_grid(&self) -> Grid {
+        let (rows, cols) = self.world_size;
+        let mut grid = vec![vec![types::BG_COLOR; cols as usize]; rows as usize];
+        for (cell, positions) in self.layers.iter() {
+            for (row, col) in positions.iter().filter(|(r, c)| *r < rows && *c < cols) {
+                grid[*row as usize][*col as usize] = *cell;
+            }
+        }
+
+        grid
+    }
+
+    /// Encode as whitespace separated tokens
+    pub(super) fn to_tokens(&self) -> String {
+        let (rows, cols) = self.world_size;
+        let scores: Vec<_> = self.scores.iter().map(usize::to_string).collect();
+        let mut s = format!("{} {} {} {}", self.tick, rows, cols, scores.join(","));
+        for (cell, positions) in self.layers.iter() {
+            let positions: Vec<_> = positions
+                .iter()
+                .map(|(row, col)| format!("{}:{}", row, col))
+                .collect();
+            s += &format!(
+                " {:02x}{:02x}{:02x}={}",
+                cell.r,
+                cell.g,
+                cell.b,
+                positions.join(",")
+            );
+        }
+
+        s
+    }
+
+    /// Decode from whitespace separated tokens
+    pub(super) fn from_tokens<'a, I: Iterator<Item = &'a str>>(
+        mut tokens: I,
+    ) -> Result<Self, NetError> {
+        let malformed = |token: &str| NetError::Protocol(format!("Malformed token '{}'", token));
+        let mut number = |name: &str| -> Result<u64, NetError> {
+            let token = tokens
+                .next()
+                .ok_or_else(|| NetError::Protocol(format!("Missing {}", name)))?;
+            token.parse().map_err(|_| malformed(token))
+        };
+
+        let tick = number("tick")?;
+        let world_size = (number("rows")? as u32, number("cols")? as u32);
+        let scores = match tokens.next() {
+            Some(token) => token
+                .split(',')
+                .map(|score| score.parse().map_err(|_| malformed(token)))
+                .collect::<Result<_, _>>()?,
+            None => return Err(NetError::Protocol("Missing scores".to_string())),
+        };
+
+        let mut layers = vec![];
+        for token in tokens {
+            let (color, positions) = token.split_once('=').ok_or_else(|| malformed(token))?;
+            let rgb = u32::from_str_radix(color, 16).map_err(|_| malformed(token))?;
+            let cell = Cell::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+            let positions = positions
+                .split(',')
+                .filter(|position| !position.is_empty())
+                .map(|position| {
+                    let (row, col) = position.split_once(':').ok_or_else(|| malformed(token))?;
+                    Ok((
+                        row.parse().map_err(|_| malformed(token))?,
+                        col.parse().map_err(|_| malformed(token))?,
+                    ))
+                })
+                .collect::<Result<_, NetError>>()?;
+            layers.push((cell, positions));
+        }
+
+        Ok(Snapshot {
+            tick,
+            world_size,
+            scores,
+            layers,
+        })
+    }
+}
+
+/// Streams every tick of a game to any number of watching processes
+pub struct SpectatorServer {
+    listener: TcpListener,
+    spectators: Vec<TcpStream>,
+}
+
+impl SpectatorServer {
+    /// Listen for spectators on the given address
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, NetError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(SpectatorServer {
+            listener,
+            spectators: vec![],
+        })
+    }
+
+    /// Returns the address the server is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns the number of connected spectators
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Accept any waiting spectators, then send them the current state of the game
+    pub fn broadcast(&mut self, state: &Gamestate) -> Result<(), NetError> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    self.spectators.push(stream);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let line = format!("{}\n", Message::Snapshot(Snapshot::from_state(state)));
+        // a spectator going away never interrupts the game
+        self.spectators
+            .retain(|mut stream| stream.write_all(line.as_bytes()).is_ok());
+
+        Ok(())
+    }
+}
+
+/// A read-only connection to a `SpectatorServer`
+pub struct Spectator {
+    stream: TcpStream,
+    /// Received bytes not yet forming a complete message
+    buffer: Vec<u8>,
+}
+
+impl Spectator {
+    /// Connect to a game's spectator stream
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self, NetError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Spectator {
+            stream,
+            buffer: vec![],
+        })
+    }
+
+    /// Read whatever has arrived without blocking. Returns the most recent complete snapshot
+    pub fn poll(&mut self) -> Result<Option<Snapshot>, NetError> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut latest = None;
+        while let Some(i) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=i).collect();
+            match String::from_utf8_lossy(&line).trim().parse()? {
+                Message::Snapshot(snapshot) => latest = Some(snapshot),
+                Message::Bye => return Err(NetError::Disconnected),
+                msg => {
+                    return Err(NetError::Protocol(format!(
+                        "Expected SNAPSHOT, got {}",
+                        msg
+                    )))
+                }
+            }
+        }
+
+        Ok(latest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{Mapper, MemMapper},
+        types::GameMode,
+        world::Controller,
+    };
+
+    fn state() -> Gamestate {
+        let mut state = Gamestate::new(36, 36, GameMode::Map, MemMapper {}.load_map().ok());
+        state.add_player(Controller::Human);
+        state.refresh_grid();
+        state
+    }
+
+    #[test]
+    fn snapshot_matches_grid() {
+        let mut state = state();
+        assert_eq!(Snapshot::from_state(&state).to_grid(), state.grid);
+
+        // a drill lying on an empty cell
+        let empty = (0..36)
+            .flat_map(|row| (0..36).map(move |col| (row, col)))
+            .find(|(row, col)| state.grid[*row as usize][*col as usize] == types::BG_COLOR);
+        state.drill = empty;
+        state.refresh_grid();
+        let snapshot = Snapshot::from_state(&state);
+        assert!(snapshot
+            .layers
+            .contains(&(types::DRILL_COLOR, vec![empty.unwrap()])));
+        assert_eq!(snapshot.to_grid(), state.grid);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = Snapshot::from_state(&state());
+        let msg = Message::Snapshot(snapshot.clone());
+        assert_eq!(msg.to_string().parse::<Message>().unwrap(), msg);
+    }
+
+    #[test]
+    fn streams_to_spectators() {
+        let mut state = state();
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let mut spectator = Spectator::connect(server.local_addr().unwrap()).unwrap();
+
+        while server.spectators() == 0 {
+            server.broadcast(&state).unwrap();
+        }
+        state.simulate(1);
+        server.broadcast(&state).unwrap();
+
+        let mut snapshot = None;
+        while snapshot
+            .as_ref()
+            .is_none_or(|s: &Snapshot| s.tick < state.tick)
+        {
+            snapshot = spectator.poll().unwrap().or(snapshot);
+        }
+        assert_eq!(snapshot, Some(Snapshot::from_state(&state)));
+    }
+
+    #[test]
+    fn server_drops_departed_spectators() {
+        let state = state();
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let spectator = Spectator::connect(server.local_addr().unwrap()).unwrap();
+        while server.spectators() == 0 {
+            server.broadcast(&state).unwrap();
+        }
+
+        drop(spectator);
+        // the first write after a close may still succeed, the following ones fail
+        for _ in 0..10 {
+            server.broadcast(&state).unwrap();
+        }
+        assert_eq!(server.spectators(), 0);
+    }
+}
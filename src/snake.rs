@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-use crate::types::{Cell, Direction, GameMode, Grid, Position, SNAKE_COLOR};
+use crate::types::{Cell, Direction, Grid, Portal, Position, Topology, SNAKE_COLOR};
 
 /// Snake represents the player.
 #[derive(Debug)]
@@ -11,62 +11,141 @@ pub struct Snake {
     /// List of positions that comprise the Snake's body
     pub body: VecDeque<Position>,
 
-    /// Current game mode, which affects Snake behavior
-    mode: GameMode,
+    /// How the board's edges join, deciding where this `Snake` goes when it leaves the board
+    pub topology: Topology,
+
+    /// Portals this `Snake` travels through
+    pub portals: Vec<Portal>,
 }
 
 impl Snake {
     /// Creates a new instance of `Snake`
-    pub fn new(row: u32, col: u32, mut cell: Option<Cell>, mut mode: Option<GameMode>) -> Self {
+    pub fn new(row: u32, col: u32, mut cell: Option<Cell>) -> Self {
         if cell.is_none() {
             cell = Some(SNAKE_COLOR);
         }
-        if mode.is_none() {
-            mode = Some(GameMode::Normal);
-        }
 
         let mut body = VecDeque::new();
         body.push_front((row, col));
 
         Self {
             cell: cell.unwrap(),
-            mode: mode.unwrap(),
+            topology: Topology::Torus,
             body,
+            portals: vec![],
         }
     }
 
+    /// Let this `Snake` travel through `portals`
+    pub fn with_portals(mut self, portals: Vec<Portal>) -> Self {
+        self.portals = portals;
+        self
+    }
+
+    /// Let this `Snake` travel on `topology`
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
     /// Returns this `Snake`'s head location
     pub fn position(&self) -> &Position {
         self.body.front().unwrap()
     }
 
-    /// Calculates a new position with direction values and the position from `Snake`
-    pub fn next_position(&self, direction: &Direction, height: i32, width: i32) -> Position {
-        let (dy, dx) = direction.value();
-        let (row, col) = *self.position();
-        let mut row = dy + row as i32;
-        let mut col = dx + col as i32;
-
-        // boundary checks
-        if let Some(y) = wrap_index(0, height, row) {
-            row = y;
-            if self.mode == GameMode::Tal {
-                col = mirror_index(0, width, col);
+    /// Calculates the position one step from `position` in `direction`, applying edge rules and
+    /// passing through any portal
+    pub fn step_from(
+        &self,
+        position: Position,
+        direction: &Direction,
+        height: i32,
+        width: i32,
+    ) -> Position {
+        self.step(position, direction, height, width).0
+    }
+
+    /// Calculates the position one step from `position` in `direction`, and the direction of
+    /// travel after it
+    ///
+    /// Stepping into a portal lands on the cell beyond its other end, heading in that end's exit
+    /// direction if it has one.
+    pub fn step(
+        &self,
+        position: Position,
+        direction: &Direction,
+        height: i32,
+        width: i32,
+    ) -> (Position, Direction) {
+        let (next, heading) = self.edge_step(position, direction, height, width);
+        match self.portals.iter().find_map(|portal| portal.partner(next)) {
+            Some(end) => {
+                let direction = end.exit.unwrap_or(heading);
+                self.edge_step(end.position, &direction, height, width)
             }
+            None => (next, heading),
         }
-        if let Some(x) = wrap_index(0, width, col) {
-            col = x;
-            if self.mode == GameMode::Tal {
-                row = mirror_index(0, height, row);
-            }
+    }
+
+    /// Calculates the position one step from `position` in `direction`, and the direction of
+    /// travel after it, applying the edge rules of this `Snake`'s topology
+    ///
+    /// On a bounded board, stepping off an edge leaves the position unchanged.
+    fn edge_step(
+        &self,
+        position: Position,
+        direction: &Direction,
+        height: i32,
+        width: i32,
+    ) -> (Position, Direction) {
+        let (dy, dx) = direction.value();
+        let (row, col) = (position.0 as i32 + dy, position.1 as i32 + dx);
+        let across_rows = wrap_index(0, height, row);
+        let across_cols = wrap_index(0, width, col);
+        if across_rows.is_none() && across_cols.is_none() {
+            return ((row as u32, col as u32), *direction);
         }
 
+        let (row, col, heading) = match (self.topology, across_rows, across_cols) {
+            (Topology::Bounded, _, _) => return (position, *direction),
+            (Topology::Sphere, _, _) => {
+                let (row, col, heading) = sphere_seam(row, col, direction, height, width);
+                // the corners where seams meet are the poles. Round them rather than stand still
+                if (row as u32, col as u32) == position {
+                    let (dy, dx) = heading.value();
+                    (row + dy, col + dx, heading)
+                } else {
+                    (row, col, heading)
+                }
+            }
+            (Topology::Klein, Some(y), _) | (Topology::Projective, Some(y), _) => {
+                (y, mirror_index(0, width, col), *direction)
+            }
+            (Topology::Projective, _, Some(x)) => (mirror_index(0, height, row), x, *direction),
+            (_, y, x) => (y.unwrap_or(row), x.unwrap_or(col), *direction),
+        };
+
+        ((row as u32, col as u32), heading)
+    }
+
+    /// Calculates a new position with direction values and the position from `Snake`
+    pub fn next_position(&self, direction: &Direction, height: i32, width: i32) -> Position {
+        self.next_move(direction, height, width).0
+    }
+
+    /// Calculates the head's next position and the direction it will be travelling in there
+    pub fn next_move(
+        &self,
+        direction: &Direction,
+        height: i32,
+        width: i32,
+    ) -> (Position, Direction) {
+        let (position, heading) = self.step(*self.position(), direction, height, width);
+
         // dont let snake turn back on itself
         match self.body.get(1) {
-            Some(x) if *x == (row as u32, col as u32) => {
-                self.next_position(&direction.flip(), height, width)
-            }
-            _ => (row as u32, col as u32),
+            Some(x) if *x == position => self.next_move(&direction.flip(), height, width),
+            _ => (position, heading),
         }
     }
 
@@ -82,7 +161,7 @@ impl Snake {
 
     /// Update the position of this `Snake` while extending its length
     pub fn grow(&mut self, direction: &Direction, width: i32, height: i32) -> &Self {
-        let position = self.next_position(direction, width, height);
+        let position = self.next_position(direction, height, width);
         self.body.push_front(position);
 
         self
@@ -107,6 +186,25 @@ fn wrap_index(lower: i32, upper: i32, i: i32) -> Option<i32> {
     }
 }
 
+/// Cross the seam of a sphere from just beyond an edge at `row`, `col`, heading in `direction`
+///
+/// The top edge joins the left, and the bottom edge the right, so the snake comes out turned a
+/// quarter turn.
+fn sphere_seam(
+    row: i32,
+    col: i32,
+    direction: &Direction,
+    height: i32,
+    width: i32,
+) -> (i32, i32, Direction) {
+    match direction {
+        Direction::Up => (col * height / width, 0, Direction::Right),
+        Direction::Left => (0, row * width / height, Direction::Down),
+        Direction::Down => (col * height / width, width - 1, Direction::Left),
+        Direction::Right => (height - 1, row * width / height, Direction::Up),
+    }
+}
+
 /// mirror an index across the middle of the range  `lower` (inclusive) and `upper` (exclusive)
 fn mirror_index(lower: i32, upper: i32, i: i32) -> i32 {
     let offset = (upper - 1) - i;
@@ -121,32 +219,33 @@ mod tests {
     fn snake_renders() {
         let grid = vec![vec![Cell::RGB(0, 0, 0)]];
         let expected = vec![vec![SNAKE_COLOR]];
-        let actual = Snake::new(0, 0, None, None).render(grid);
+        let actual = Snake::new(0, 0, None).render(grid);
         assert_eq!(actual, expected);
     }
 
     #[test]
     fn snake_default_color() {
         let expected = SNAKE_COLOR;
-        let actual = Snake::new(0, 0, None, None).cell;
+        let actual = Snake::new(0, 0, None).cell;
         assert_eq!(actual, expected);
     }
 
     #[test]
     fn snake_specified_color() {
         let expected = Cell::RGB(1, 2, 2);
-        let actual = Snake::new(0, 0, Some(Cell::RGB(1, 2, 2)), None).cell;
+        let actual = Snake::new(0, 0, Some(Cell::RGB(1, 2, 2))).cell;
         assert_eq!(actual, expected);
     }
 
     #[test]
     fn snake_position() {
         let expected = (10, 20);
-        let actual = *Snake::new(10, 20, None, None).position();
+        let actual = *Snake::new(10, 20, None).position();
         assert_eq!(actual, expected);
     }
 
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn wraps_index_upper() {
         let expected = Some(0);
         let actual = wrap_index(expected.unwrap(), 10, 11);
@@ -166,4 +265,273 @@ mod tests {
         let actual = mirror_index(0, 4, 2);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn wraps_non_square_board() {
+        // 5 rows by 9 columns
+        let snake = Snake::new(0, 8, None);
+        assert_eq!(snake.step_from((0, 8), &Direction::Right, 5, 9), (0, 0));
+        assert_eq!(snake.step_from((0, 8), &Direction::Up, 5, 9), (4, 8));
+        assert_eq!(snake.step_from((4, 0), &Direction::Down, 5, 9), (0, 0));
+        assert_eq!(snake.step_from((4, 0), &Direction::Left, 5, 9), (4, 8));
+    }
+
+    #[test]
+    fn mirrors_non_square_board() {
+        let snake = Snake::new(0, 2, None).with_topology(Topology::Projective);
+        // leaving through the top mirrors the column across the board's width
+        assert_eq!(snake.step_from((0, 2), &Direction::Up, 5, 9), (4, 6));
+        // leaving through the side mirrors the row across the board's height
+        assert_eq!(snake.step_from((1, 0), &Direction::Left, 5, 9), (3, 8));
+    }
+
+    #[test]
+    fn grows_across_non_square_board() {
+        // 3 rows by 9 columns, passed as width then height
+        let mut snake = Snake::new(0, 7, None);
+        snake.grow(&Direction::Right, 9, 3);
+        assert_eq!(*snake.position(), (0, 8));
+        snake.grow(&Direction::Right, 9, 3);
+        assert_eq!(*snake.position(), (0, 0));
+        snake.grow(&Direction::Down, 9, 3);
+        snake.update_position(&Direction::Down, 9, 3);
+        assert_eq!(*snake.position(), (2, 0));
+        assert_eq!(snake.body.len(), 4);
+    }
+
+    #[test]
+    fn steps_through_portal() {
+        let mut portal = Portal::new((0, 2), (3, 6));
+        let snake = Snake::new(0, 1, None).with_portals(vec![portal]);
+        // out the far side of the partner, keeping direction
+        assert_eq!(
+            snake.step((0, 1), &Direction::Right, 5, 9),
+            ((3, 7), Direction::Right)
+        );
+        // and back again, wrapping past the edge
+        assert_eq!(
+            snake.step((4, 6), &Direction::Up, 5, 9),
+            ((4, 2), Direction::Up)
+        );
+
+        portal.ends[1].exit = Some(Direction::Down);
+        let snake = snake.with_portals(vec![portal]);
+        assert_eq!(
+            snake.step((0, 1), &Direction::Right, 5, 9),
+            ((4, 6), Direction::Down)
+        );
+    }
+
+    #[test]
+    fn body_follows_through_portal() {
+        let portal = Portal::new((0, 3), (2, 0));
+        let mut snake = Snake::new(0, 1, None).with_portals(vec![portal]);
+        snake.grow(&Direction::Right, 9, 5);
+        snake.grow(&Direction::Right, 9, 5);
+        assert_eq!(*snake.position(), (2, 1));
+        snake.update_position(&Direction::Right, 9, 5);
+        snake.update_position(&Direction::Right, 9, 5);
+        let body: Vec<Position> = snake.body.iter().copied().collect();
+        assert_eq!(body, vec![(2, 3), (2, 2), (2, 1)]);
+    }
+
+    /// Every cell on the rim of a `height` x `width` board, with each direction leading off it
+    fn exits(height: u32, width: u32) -> Vec<(Position, Direction)> {
+        let mut exits = vec![];
+        for col in 0..width {
+            exits.push(((0, col), Direction::Up));
+            exits.push(((height - 1, col), Direction::Down));
+        }
+        for row in 0..height {
+            exits.push(((row, 0), Direction::Left));
+            exits.push(((row, width - 1), Direction::Right));
+        }
+        exits
+    }
+
+    fn on(topology: Topology) -> Snake {
+        Snake::new(0, 0, None).with_topology(topology)
+    }
+
+    #[test]
+    fn topology_defaults_to_torus() {
+        assert_eq!(Snake::new(0, 0, None).topology, Topology::Torus);
+        let snake = Snake::new(0, 0, None).with_topology(Topology::Projective);
+        assert_eq!(snake.topology, Topology::Projective);
+    }
+
+    #[test]
+    fn steps_inside_board_ignore_topology() {
+        for topology in Topology::ALL.iter().copied() {
+            let snake = on(topology);
+            for direction in Direction::ALL.iter() {
+                let (dy, dx) = direction.value();
+                let expected = ((2 + dy) as u32, (4 + dx) as u32);
+                assert_eq!(
+                    snake.step((2, 4), direction, 5, 9),
+                    (expected, *direction),
+                    "{:?}",
+                    topology
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn torus_edges() {
+        let snake = on(Topology::Torus);
+        for (height, width) in [(5, 9), (9, 5), (6, 6)].iter().copied() {
+            for ((row, col), direction) in exits(height, width) {
+                let expected = match direction {
+                    Direction::Up => (height - 1, col),
+                    Direction::Down => (0, col),
+                    Direction::Left => (row, width - 1),
+                    Direction::Right => (row, 0),
+                };
+                assert_eq!(
+                    snake.step((row, col), &direction, height as i32, width as i32),
+                    (expected, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_edges() {
+        let snake = on(Topology::Bounded);
+        for (height, width) in [(5, 9), (9, 5), (6, 6)].iter().copied() {
+            for (position, direction) in exits(height, width) {
+                assert_eq!(
+                    snake.step(position, &direction, height as i32, width as i32),
+                    (position, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn klein_edges() {
+        let snake = on(Topology::Klein);
+        for (height, width) in [(5, 9), (9, 5), (6, 6)].iter().copied() {
+            for ((row, col), direction) in exits(height, width) {
+                let expected = match direction {
+                    Direction::Up => (height - 1, width - 1 - col),
+                    Direction::Down => (0, width - 1 - col),
+                    Direction::Left => (row, width - 1),
+                    Direction::Right => (row, 0),
+                };
+                assert_eq!(
+                    snake.step((row, col), &direction, height as i32, width as i32),
+                    (expected, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn projective_edges() {
+        let snake = on(Topology::Projective);
+        for (height, width) in [(5, 9), (9, 5), (6, 6)].iter().copied() {
+            for ((row, col), direction) in exits(height, width) {
+                let expected = match direction {
+                    Direction::Up => (height - 1, width - 1 - col),
+                    Direction::Down => (0, width - 1 - col),
+                    Direction::Left => (height - 1 - row, width - 1),
+                    Direction::Right => (height - 1 - row, 0),
+                };
+                assert_eq!(
+                    snake.step((row, col), &direction, height as i32, width as i32),
+                    (expected, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_edges() {
+        let snake = on(Topology::Sphere);
+        for ((row, col), direction) in exits(6, 6) {
+            let expected = match direction {
+                // the poles, where two seams meet, are rounded
+                Direction::Up if col == 0 => ((0, 1), Direction::Right),
+                Direction::Left if row == 0 => ((1, 0), Direction::Down),
+                Direction::Down if col == 5 => ((5, 4), Direction::Left),
+                Direction::Right if row == 5 => ((4, 5), Direction::Up),
+                Direction::Up => ((col, 0), Direction::Right),
+                Direction::Left => ((0, row), Direction::Down),
+                Direction::Down => ((col, 5), Direction::Left),
+                Direction::Right => ((5, row), Direction::Up),
+            };
+            assert_eq!(snake.step((row, col), &direction, 6, 6), expected);
+        }
+    }
+
+    #[test]
+    fn sphere_scales_non_square_board() {
+        let snake = on(Topology::Sphere);
+        // 5 rows by 10 columns: the top edge's columns squeeze onto the left edge's rows
+        assert_eq!(
+            snake.step((0, 8), &Direction::Up, 5, 10),
+            ((4, 0), Direction::Right)
+        );
+        assert_eq!(
+            snake.step((4, 0), &Direction::Left, 5, 10),
+            ((0, 8), Direction::Down)
+        );
+        assert_eq!(
+            snake.step((4, 3), &Direction::Down, 5, 10),
+            ((1, 9), Direction::Left)
+        );
+        for (position, direction) in exits(5, 10) {
+            let ((row, col), _) = snake.step(position, &direction, 5, 10);
+            assert!(row < 5 && col < 10);
+        }
+    }
+
+    #[test]
+    fn edges_retrace() {
+        // crossing an edge and turning back returns the snake to where it was, headed back
+        let boards: [(u32, u32); 3] = [(5, 9), (9, 5), (6, 6)];
+        for topology in [Topology::Torus, Topology::Klein, Topology::Projective].iter() {
+            let snake = on(*topology);
+            for (rows, cols) in boards.iter().copied() {
+                let (height, width) = (rows as i32, cols as i32);
+                for (position, direction) in exits(rows, cols) {
+                    let (next, heading) = snake.step(position, &direction, height, width);
+                    assert_eq!(
+                        snake.step(next, &heading.flip(), height, width),
+                        (position, direction.flip()),
+                        "{:?} from {:?} {:?}",
+                        topology,
+                        position,
+                        direction
+                    );
+                }
+            }
+        }
+
+        let snake = on(Topology::Sphere);
+        let poles = [(0, 0), (5, 5)];
+        for (position, direction) in exits(6, 6) {
+            if poles.contains(&position) {
+                continue;
+            }
+            let (next, heading) = snake.step(position, &direction, 6, 6);
+            assert_eq!(
+                snake.step(next, &heading.flip(), 6, 6),
+                (position, direction.flip())
+            );
+        }
+    }
+
+    #[test]
+    fn grows_across_topology() {
+        let mut snake = Snake::new(0, 1, None).with_topology(Topology::Sphere);
+        snake.grow(&Direction::Up, 6, 6);
+        assert_eq!(*snake.position(), (1, 0));
+        // turning back out through the seam would run into the neck, so the snake carries on
+        snake.update_position(&Direction::Left, 6, 6);
+        assert_eq!(*snake.position(), (1, 1));
+        assert_eq!(snake.body.len(), 2);
+    }
 }
@@ -1,10 +1,21 @@
 use sdl2::pixels::Color;
 
+mod behavior;
 mod direction;
+mod ruleset;
+mod tile;
+mod topology;
 mod worldmap;
 
+pub use behavior::{Behavior, Difficulty, EnemySpawn, EvilConfig, MAX_ENEMIES};
 pub use direction::Direction;
-pub use worldmap::WorldMap;
+pub use ruleset::{Goal, Ruleset, Speed};
+pub use tile::{Tile, MAX_BRICK_HP};
+pub use topology::Topology;
+pub use worldmap::{
+    Door, Key, KeyColor, MapMeta, Motion, Obstacle, Portal, PortalEnd, PuzzleState, Spawn, Switch,
+    SwitchWall, WorldMap, Zone,
+};
 
 pub const GAME_NAME: &str = "rs-snek";
 
@@ -24,13 +35,82 @@ pub const FOOD_COLOR: Cell = Cell::RGB(188, 13, 36);
 pub const SNAKE_COLOR: Cell = Cell::RGB(141, 141, 139);
 pub const BG_COLOR: Cell = Cell::RGB(42, 42, 42);
 pub const WALL_COLOR: Cell = Cell::RGB(0, 102, 102);
+pub const ROCK_COLOR: Cell = Cell::RGB(110, 98, 84);
+pub const ICE_COLOR: Cell = Cell::RGB(165, 214, 232);
+/// Conveyor colors, by the direction they push in: up, right, down, left
+pub const CONVEYOR_COLORS: [Cell; 4] = [
+    Cell::RGB(92, 84, 52),
+    Cell::RGB(94, 84, 52),
+    Cell::RGB(96, 84, 52),
+    Cell::RGB(98, 84, 52),
+];
+/// Gate colors, by the direction they open in: up, right, down, left
+pub const GATE_COLORS: [Cell; 4] = [
+    Cell::RGB(52, 120, 70),
+    Cell::RGB(54, 120, 70),
+    Cell::RGB(56, 120, 70),
+    Cell::RGB(58, 120, 70),
+];
+/// Brick colors, by hit points: 1, 2, 3
+pub const BRICK_COLORS: [Cell; 3] = [
+    Cell::RGB(196, 120, 84),
+    Cell::RGB(168, 96, 64),
+    Cell::RGB(140, 72, 48),
+];
+pub const DRILL_COLOR: Cell = Cell::RGB(120, 235, 235);
+pub const OBSTACLE_COLOR: Cell = Cell::RGB(176, 128, 64);
+pub const CRUSHER_COLOR: Cell = Cell::RGB(160, 165, 180);
+/// Key colors, by `KeyColor`: red, yellow, green, blue
+pub const KEY_COLORS: [Cell; 4] = [
+    Cell::RGB(235, 90, 90),
+    Cell::RGB(240, 215, 60),
+    Cell::RGB(90, 220, 110),
+    Cell::RGB(90, 150, 250),
+];
+/// Door colors, by the `KeyColor` that opens them
+pub const DOOR_COLORS: [Cell; 4] = [
+    Cell::RGB(150, 45, 45),
+    Cell::RGB(160, 140, 30),
+    Cell::RGB(45, 140, 60),
+    Cell::RGB(40, 80, 170),
+];
+pub const SWITCH_COLOR: Cell = Cell::RGB(205, 180, 130);
+pub const SWITCH_WALL_COLOR: Cell = Cell::RGB(95, 75, 115);
 pub const EVIL_COLOR: Cell = Cell::RGB(255 - 141, 255 - 141, 255 - 139);
 // Rgba([max - rgba[0], max - rgba[1], max - rgba[2], rgba[3]])
+/// Colors for additional player snakes
+pub const RIVAL_COLORS: [Cell; 7] = [
+    Cell::RGB(214, 162, 37),
+    Cell::RGB(64, 145, 214),
+    Cell::RGB(145, 84, 190),
+    Cell::RGB(97, 181, 72),
+    Cell::RGB(222, 110, 160),
+    Cell::RGB(230, 126, 34),
+    Cell::RGB(26, 188, 156),
+];
+
+/// Colors for enemy snakes, in spawn order
+pub const ENEMY_COLORS: [Cell; 4] = [
+    EVIL_COLOR,
+    Cell::RGB(120, 20, 20),
+    Cell::RGB(90, 40, 120),
+    Cell::RGB(200, 200, 60),
+];
+
+/// Colors for portal pairs, so linked ends match
+pub const PORTAL_COLORS: [Cell; 4] = [
+    Cell::RGB(60, 120, 255),
+    Cell::RGB(255, 150, 30),
+    Cell::RGB(200, 80, 230),
+    Cell::RGB(240, 240, 240),
+];
 
 pub const TEXT_COLOR: Cell = Cell::RGB(225, 225, 225);
 pub const TEXT_SELECTED: Cell = FOOD_COLOR;
 pub const FONT_PATH: &str = "./resource/NotoSans-Regular.ttf";
 // pub const FONT_PATH: &str = std::path::Path::new("../resource/NotoSans-Regular.ttf").to_str().unwrap();
+/// Directory searched for map files
+pub const MAP_DIR: &str = "./resource";
 pub const FONT_SIZE_SM: u16 = 12;
 pub const FONT_SIZE_MD: u16 = 18;
 
@@ -38,6 +118,8 @@ pub const FONT_SIZE_MD: u16 = 18;
 pub enum GameEvent {
     Pause,
     Menu,
+    /// The round is over, won by the given player id (if anyone survived)
+    Finish(Option<usize>),
 }
 
 /// Events that may affect the player
@@ -47,18 +129,22 @@ pub enum SnakeEvent {
     Food,
     /// Player death event
     Death,
+    /// Player rammed a wall that a drill could break
+    Ram,
     /// Player input/control event
     Input(Direction),
-    ///
+    /// Game control event
     Game(GameEvent),
 }
 
-/// Available modes of play
+/// Available modes of play, each a preset `Ruleset`
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum GameMode {
     Normal,
     Tal,
     Map,
+    /// Many snakes in a shrinking arena, last one alive wins
+    Royale,
 }
 
 #[cfg(test)]
@@ -0,0 +1,217 @@
+use super::{Cell, Position, ENEMY_COLORS};
+
+/// Most enemy snakes selectable for a game
+pub const MAX_ENEMIES: usize = 4;
+
+/// Player score between the arrival of each enemy after the first
+const WAVE_SCORE: usize = 5;
+
+/// How an enemy snake moves about the board
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Behavior {
+    /// Moves opposite the player, growing whenever the player eats
+    Mirror,
+    /// Hunts down the player's head
+    Chaser,
+    /// Races the player to the food, and eats it
+    Thief,
+    /// Walks a square beat, charging the player when they come close
+    Patroller,
+}
+
+impl Behavior {
+    /// Every available behavior, in menu order
+    pub const ALL: [Behavior; 4] = [
+        Behavior::Mirror,
+        Behavior::Chaser,
+        Behavior::Thief,
+        Behavior::Patroller,
+    ];
+
+    /// Returns the display name of this `Behavior`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Mirror => "Mirror",
+            Self::Chaser => "Chaser",
+            Self::Thief => "Food Thief",
+            Self::Patroller => "Patroller",
+        }
+    }
+
+    /// Returns the `Behavior` following this one, wrapping around
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|b| b == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// How capable enemy snakes are
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Every available difficulty, in menu order
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    /// Returns the display name of this `Difficulty`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+
+    /// Returns the `Difficulty` following this one, wrapping around
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|d| d == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Percent chance each tick that an enemy ignores its plan and wanders
+    pub fn blunder(&self) -> u32 {
+        match *self {
+            Self::Easy => 30,
+            Self::Normal => 10,
+            Self::Hard => 0,
+        }
+    }
+
+    /// How far away, in cells, an enemy notices its target
+    pub fn sight(&self) -> u32 {
+        match *self {
+            Self::Easy => 6,
+            Self::Normal => 12,
+            Self::Hard => 72,
+        }
+    }
+
+    /// How close, in cells, the player must come before a patroller gives chase
+    pub fn alert(&self) -> u32 {
+        match *self {
+            Self::Easy => 3,
+            Self::Normal => 5,
+            Self::Hard => 8,
+        }
+    }
+
+    /// Length of each side of a patroller's beat
+    pub fn patrol_leg(&self) -> u32 {
+        match *self {
+            Self::Easy => 6,
+            Self::Normal => 10,
+            Self::Hard => 14,
+        }
+    }
+}
+
+/// Enemy selection for a game
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EvilConfig {
+    pub behavior: Behavior,
+    pub difficulty: Difficulty,
+    /// Number of enemies that will appear over the course of a game
+    pub count: usize,
+}
+
+impl EvilConfig {
+    /// Returns the enemy count following this one, wrapping around
+    pub fn next_count(&self) -> usize {
+        (self.count + 1) % (MAX_ENEMIES + 1)
+    }
+}
+
+impl Default for EvilConfig {
+    fn default() -> Self {
+        EvilConfig {
+            behavior: Behavior::Mirror,
+            difficulty: Difficulty::Normal,
+            count: 1,
+        }
+    }
+}
+
+/// An enemy snake waiting to enter the game
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EnemySpawn {
+    /// Player score at which the enemy appears
+    pub at_score: usize,
+    /// Preferred starting location. Picked by the game when `None`
+    pub position: Option<Position>,
+    pub color: Cell,
+    pub behavior: Behavior,
+    pub difficulty: Difficulty,
+}
+
+impl EnemySpawn {
+    /// Create a new `EnemySpawn` that appears at the start of a game
+    pub fn new(behavior: Behavior, difficulty: Difficulty) -> Self {
+        EnemySpawn {
+            at_score: 0,
+            position: None,
+            color: ENEMY_COLORS[0],
+            behavior,
+            difficulty,
+        }
+    }
+
+    /// A schedule of `config.count` enemies. The first appears immediately, and another each time
+    /// the player scores a few more points
+    pub fn waves(config: &EvilConfig) -> Vec<EnemySpawn> {
+        (0..config.count)
+            .map(|i| EnemySpawn {
+                at_score: i * WAVE_SCORE,
+                color: ENEMY_COLORS[i % ENEMY_COLORS.len()],
+                ..EnemySpawn::new(config.behavior, config.difficulty)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn behavior_cycles() {
+        let mut behavior = Behavior::Mirror;
+        for _ in 0..Behavior::ALL.len() {
+            behavior = behavior.next();
+        }
+        assert_eq!(behavior, Behavior::Mirror);
+        assert_eq!(Behavior::Mirror.next(), Behavior::Chaser);
+    }
+
+    #[test]
+    fn difficulty_cycles() {
+        assert_eq!(Difficulty::Hard.next(), Difficulty::Easy);
+        assert_eq!(Difficulty::Easy.next(), Difficulty::Normal);
+    }
+
+    #[test]
+    fn count_cycles() {
+        let config = EvilConfig {
+            count: MAX_ENEMIES,
+            ..EvilConfig::default()
+        };
+        assert_eq!(config.next_count(), 0);
+        assert_eq!(EvilConfig::default().next_count(), 2);
+    }
+
+    #[test]
+    fn waves_arrive_in_turn() {
+        let config = EvilConfig {
+            count: 3,
+            ..EvilConfig::default()
+        };
+        let scores: Vec<_> = EnemySpawn::waves(&config)
+            .iter()
+            .map(|spawn| spawn.at_score)
+            .collect();
+        assert_eq!(scores, vec![0, WAVE_SCORE, 2 * WAVE_SCORE]);
+    }
+}
@@ -1,5 +1,5 @@
 /// Valid directions of travel
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Direction {
     Left,
     Right,
@@ -8,6 +8,9 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Every direction of travel
+    pub const ALL: [Direction; 4] = [Self::Up, Self::Right, Self::Down, Self::Left];
+
     /// Returns a tuple of `(row, col)` values representing change in position for a `Direction`
     pub fn value(&self) -> (i32, i32) {
         match *self {
@@ -27,6 +30,16 @@ impl Direction {
             Self::Down => Self::Up,
         }
     }
+
+    /// Returns the `Direction` a quarter turn clockwise from this `Direction`
+    pub fn clockwise(&self) -> Self {
+        match *self {
+            Self::Left => Self::Up,
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -40,4 +53,12 @@ mod tests {
         assert_eq!(Direction::Right.flip(), Direction::Left);
         assert_eq!(Direction::Left.flip(), Direction::Right);
     }
+
+    #[test]
+    fn direction_clockwise() {
+        assert_eq!(Direction::Up.clockwise(), Direction::Right);
+        assert_eq!(Direction::Right.clockwise(), Direction::Down);
+        assert_eq!(Direction::Down.clockwise(), Direction::Left);
+        assert_eq!(Direction::Left.clockwise(), Direction::Up);
+    }
 }
@@ -0,0 +1,121 @@
+use super::{GameMode, Topology};
+
+/// How the simulation speed changes over a game
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Speed {
+    /// The game keeps the same pace throughout
+    Steady,
+    /// The game speeds up a little every time the player eats
+    Accelerating,
+}
+
+impl Speed {
+    /// Every speed curve, in menu order
+    pub const ALL: [Speed; 2] = [Speed::Steady, Speed::Accelerating];
+
+    /// Returns the display name of this `Speed`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Steady => "Steady",
+            Self::Accelerating => "Accelerating",
+        }
+    }
+
+    /// Returns the `Speed` following this one, wrapping around
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|s| s == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// How a game is won or lost
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Goal {
+    /// Score as much as possible. The game ends when the player dies
+    HighScore,
+    /// Bots fill the board and the arena shrinks. The last snake alive wins
+    LastStanding,
+}
+
+impl Goal {
+    /// Every goal, in menu order
+    pub const ALL: [Goal; 2] = [Goal::HighScore, Goal::LastStanding];
+
+    /// Returns the display name of this `Goal`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::HighScore => "High Score",
+            Self::LastStanding => "Last Snake Standing",
+        }
+    }
+
+    /// Returns the `Goal` following this one, wrapping around
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|g| g == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// The rules a game is played by. Each `GameMode` is a preset combination
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ruleset {
+    /// How the board's edges join
+    pub topology: Topology,
+    /// Whether the game is played on a map's walls
+    pub labyrinth: bool,
+    /// How the game speeds up
+    pub speed: Speed,
+    /// Whether evil twins share the board
+    pub enemies: bool,
+    /// How the game is won
+    pub goal: Goal,
+}
+
+impl From<GameMode> for Ruleset {
+    fn from(mode: GameMode) -> Self {
+        let normal = Ruleset {
+            topology: Topology::for_mode(mode),
+            labyrinth: false,
+            speed: Speed::Steady,
+            enemies: true,
+            goal: Goal::HighScore,
+        };
+        match mode {
+            GameMode::Normal => normal,
+            GameMode::Tal => Ruleset {
+                speed: Speed::Accelerating,
+                ..normal
+            },
+            GameMode::Map => Ruleset {
+                labyrinth: true,
+                ..normal
+            },
+            GameMode::Royale => Ruleset {
+                enemies: false,
+                goal: Goal::LastStanding,
+                ..normal
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_are_presets() {
+        let tal = Ruleset::from(GameMode::Tal);
+        assert_eq!(tal.topology, Topology::Projective);
+        assert_eq!(tal.speed, Speed::Accelerating);
+        assert!(!tal.labyrinth);
+
+        let map = Ruleset::from(GameMode::Map);
+        assert_eq!(map.topology, Topology::Torus);
+        assert!(map.labyrinth && map.enemies);
+
+        let royale = Ruleset::from(GameMode::Royale);
+        assert_eq!(royale.goal, Goal::LastStanding);
+        assert!(!royale.enemies);
+    }
+}
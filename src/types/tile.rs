@@ -0,0 +1,196 @@
+use super::{
+    Cell, Direction, BG_COLOR, BRICK_COLORS, CONVEYOR_COLORS, GATE_COLORS, ICE_COLOR, ROCK_COLOR,
+    WALL_COLOR,
+};
+
+/// Most hit points a `Tile::Brick` can have
+pub const MAX_BRICK_HP: u8 = 3;
+
+/// A kind of terrain a map can lay out
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Tile {
+    /// Open ground
+    Floor,
+    /// Impassable wall
+    Wall,
+    /// Impassable boulder, drawn apart from the walls
+    Rock,
+    /// Slippery ground. A snake ignores its controls while its head is on ice
+    Ice,
+    /// Moving ground that pushes a snake one extra cell in its `Direction`
+    Conveyor(Direction),
+    /// Gate a snake may only enter while heading in its `Direction`
+    Gate(Direction),
+    /// Wall a snake holding a drill can break through, after ramming it as many times as its hit
+    /// points, from 1 to `MAX_BRICK_HP`
+    Brick(u8),
+}
+
+impl Tile {
+    /// Every tile type
+    pub const ALL: [Tile; 15] = [
+        Tile::Floor,
+        Tile::Wall,
+        Tile::Rock,
+        Tile::Ice,
+        Tile::Conveyor(Direction::Up),
+        Tile::Conveyor(Direction::Right),
+        Tile::Conveyor(Direction::Down),
+        Tile::Conveyor(Direction::Left),
+        Tile::Gate(Direction::Up),
+        Tile::Gate(Direction::Right),
+        Tile::Gate(Direction::Down),
+        Tile::Gate(Direction::Left),
+        Tile::Brick(1),
+        Tile::Brick(2),
+        Tile::Brick(3),
+    ];
+
+    /// Returns the `Tile` drawn with the given character in a map grid
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' | ' ' => Some(Self::Floor),
+            _ => Self::ALL
+                .iter()
+                .copied()
+                .find(|tile| tile.symbol() == symbol),
+        }
+    }
+
+    /// Returns the `Tile` displayed in the given color, if any
+    pub fn from_color(cell: Cell) -> Option<Self> {
+        Self::ALL.iter().copied().find(|tile| tile.color() == cell)
+    }
+
+    /// Returns the character this `Tile` is drawn with in a map grid
+    pub fn symbol(&self) -> char {
+        match *self {
+            Self::Floor => '.',
+            Self::Wall => '#',
+            Self::Rock => 'o',
+            Self::Ice => '~',
+            Self::Conveyor(Direction::Up) => '^',
+            Self::Conveyor(Direction::Right) => '>',
+            Self::Conveyor(Direction::Down) => 'v',
+            Self::Conveyor(Direction::Left) => '<',
+            Self::Gate(Direction::Up) => 'u',
+            Self::Gate(Direction::Right) => 'r',
+            Self::Gate(Direction::Down) => 'd',
+            Self::Gate(Direction::Left) => 'l',
+            Self::Brick(1) => '+',
+            Self::Brick(2) => '*',
+            Self::Brick(_) => '@',
+        }
+    }
+
+    /// Returns the color this `Tile` is displayed in
+    ///
+    /// Every tile has its own color, so conveyors and gates are shaded by direction and bricks by
+    /// hit points.
+    pub fn color(&self) -> Cell {
+        let shade = |colors: &[Cell; 4], direction: Direction| match direction {
+            Direction::Up => colors[0],
+            Direction::Right => colors[1],
+            Direction::Down => colors[2],
+            Direction::Left => colors[3],
+        };
+        match *self {
+            Self::Floor => BG_COLOR,
+            Self::Wall => WALL_COLOR,
+            Self::Rock => ROCK_COLOR,
+            Self::Ice => ICE_COLOR,
+            Self::Conveyor(direction) => shade(&CONVEYOR_COLORS, direction),
+            Self::Gate(direction) => shade(&GATE_COLORS, direction),
+            Self::Brick(hp) => BRICK_COLORS[hp.clamp(1, MAX_BRICK_HP) as usize - 1],
+        }
+    }
+
+    /// Returns the direction a conveyor pushes or a gate opens in
+    pub fn direction(&self) -> Option<Direction> {
+        match *self {
+            Self::Conveyor(direction) | Self::Gate(direction) => Some(direction),
+            _ => None,
+        }
+    }
+
+    /// Returns this `Tile` with its direction, if it has one, changed by `f`
+    pub fn map_direction<F: Fn(Direction) -> Direction>(&self, f: F) -> Self {
+        match *self {
+            Self::Conveyor(direction) => Self::Conveyor(f(direction)),
+            Self::Gate(direction) => Self::Gate(f(direction)),
+            tile => tile,
+        }
+    }
+
+    /// Whether snakes collide with this `Tile`
+    pub fn is_solid(&self) -> bool {
+        matches!(*self, Self::Wall | Self::Rock | Self::Brick(_))
+    }
+
+    /// Whether a snake holding a drill can break this `Tile`
+    pub fn is_destructible(&self) -> bool {
+        matches!(*self, Self::Brick(_))
+    }
+
+    /// Returns what this `Tile` becomes after a snake with a drill rams it. A brick loses a hit
+    /// point, turning to floor once it has none left
+    pub fn chipped(&self) -> Self {
+        match *self {
+            Self::Brick(hp) if hp > 1 => Self::Brick(hp - 1),
+            Self::Brick(_) => Self::Floor,
+            tile => tile,
+        }
+    }
+
+    /// Whether a snake heading in `direction` may move onto this `Tile`
+    pub fn admits(&self, direction: Direction) -> bool {
+        match *self {
+            Self::Gate(open) => open == direction,
+            tile => !tile.is_solid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_round_trip() {
+        for tile in Tile::ALL.iter() {
+            assert_eq!(Tile::from_symbol(tile.symbol()), Some(*tile));
+        }
+        assert_eq!(Tile::from_symbol(' '), Some(Tile::Floor));
+        assert_eq!(Tile::from_symbol('?'), None);
+    }
+
+    #[test]
+    fn colors_round_trip() {
+        for tile in Tile::ALL.iter() {
+            assert_eq!(Tile::from_color(tile.color()), Some(*tile));
+        }
+        assert_eq!(Tile::from_color(Cell::RGB(1, 2, 3)), None);
+    }
+
+    #[test]
+    fn gates_open_one_way() {
+        let gate = Tile::Gate(Direction::Right);
+        assert!(gate.admits(Direction::Right));
+        assert!(!gate.admits(Direction::Left));
+        assert!(!gate.admits(Direction::Up));
+        assert!(Tile::Ice.admits(Direction::Up));
+        assert!(Tile::Conveyor(Direction::Down).admits(Direction::Up));
+        assert!(!Tile::Rock.admits(Direction::Up));
+    }
+
+    #[test]
+    fn bricks_crumble() {
+        let brick = Tile::Brick(2);
+        assert!(brick.is_solid() && brick.is_destructible());
+        assert!(!brick.admits(Direction::Up));
+        assert!(!Tile::Wall.is_destructible());
+        assert_eq!(brick.chipped(), Tile::Brick(1));
+        assert_eq!(brick.chipped().chipped(), Tile::Floor);
+        assert_eq!(Tile::Wall.chipped(), Tile::Wall);
+    }
+}
@@ -0,0 +1,133 @@
+use super::{GameMode, Position};
+
+/// How the edges of the board join up, deciding where a snake goes when it leaves the board
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Topology {
+    /// Opposite edges join, so a snake leaving one side comes back in on the other
+    Torus,
+    /// The edges are walls. A snake leaving the board dies
+    Bounded,
+    /// Left and right edges join as on a torus, while crossing the top or bottom edge mirrors
+    /// the snake's column
+    Klein,
+    /// Crossing any edge comes back in on the opposite side, mirrored along that edge
+    Projective,
+    /// The top edge joins the left edge and the bottom edge joins the right edge, turning the
+    /// snake as it crosses
+    Sphere,
+}
+
+impl Topology {
+    /// Every topology, in menu order
+    pub const ALL: [Topology; 5] = [
+        Topology::Torus,
+        Topology::Bounded,
+        Topology::Klein,
+        Topology::Projective,
+        Topology::Sphere,
+    ];
+
+    /// Returns the topology `mode` is played on unless another is chosen
+    pub fn for_mode(mode: GameMode) -> Self {
+        match mode {
+            GameMode::Tal => Self::Projective,
+            _ => Self::Torus,
+        }
+    }
+
+    /// Returns the `Topology` written as `name` on the command line, eg "klein"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "torus" => Some(Self::Torus),
+            "bounded" => Some(Self::Bounded),
+            "klein" => Some(Self::Klein),
+            "projective" => Some(Self::Projective),
+            "sphere" => Some(Self::Sphere),
+            _ => None,
+        }
+    }
+
+    /// Returns the display name of this `Topology`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Torus => "Torus",
+            Self::Bounded => "Bounded",
+            Self::Klein => "Klein Bottle",
+            Self::Projective => "Projective Plane",
+            Self::Sphere => "Sphere",
+        }
+    }
+
+    /// Returns the `Topology` following this one, wrapping around
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Estimated number of steps between two positions, ignoring anything in the way
+    ///
+    /// Considers the direct route and a route across each pair of joined edges.
+    pub fn distance(&self, a: &Position, b: &Position, height: u32, width: u32) -> u32 {
+        let (h, w) = (height as i32, width as i32);
+        let (ar, ac, br, bc) = (a.0 as i32, a.1 as i32, b.0 as i32, b.1 as i32);
+        let (dy, dx) = ((ar - br).abs(), (ac - bc).abs());
+        // column and row distances to `b` reflected across the board
+        let (mx, my) = ((ac - (w - 1 - bc)).abs(), (ar - (h - 1 - br)).abs());
+
+        let steps = match *self {
+            Self::Torus => dy.min(h - dy) + dx.min(w - dx),
+            Self::Bounded => dy + dx,
+            Self::Klein => (dy + dx.min(w - dx)).min(h - dy + mx.min(w - mx)),
+            Self::Projective => (dy + dx).min(h - dy + mx).min(w - dx + my),
+            Self::Sphere => {
+                let direct = dy + dx;
+                // leave through one edge, cross the seam, then walk from the joined edge
+                let manhattan = |(r, c): (i32, i32)| (r - br).abs() + (c - bc).abs();
+                let top = ar + 1 + manhattan((scale(ac, w, h), 0));
+                let left = ac + 1 + manhattan((0, scale(ar, h, w)));
+                let bottom = h - ar + manhattan((scale(ac, w, h), w - 1));
+                let right = w - ac + manhattan((h - 1, scale(ar, h, w)));
+                direct.min(top).min(left).min(bottom).min(right)
+            }
+        };
+
+        steps as u32
+    }
+}
+
+/// Scale an index `i` along an edge `from` cells long onto an edge `to` cells long
+fn scale(i: i32, from: i32, to: i32) -> i32 {
+    i * to / from
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_topologies() {
+        let mut topology = Topology::Torus;
+        for _ in 0..Topology::ALL.len() {
+            topology = topology.next();
+        }
+        assert_eq!(topology, Topology::Torus);
+        assert_eq!(Topology::for_mode(GameMode::Tal), Topology::Projective);
+        assert_eq!(Topology::for_mode(GameMode::Map), Topology::Torus);
+        assert_eq!(Topology::from_name("klein"), Some(Topology::Klein));
+        assert_eq!(Topology::from_name("donut"), None);
+    }
+
+    #[test]
+    fn measures_distance() {
+        assert_eq!(Topology::Torus.distance(&(0, 0), &(9, 9), 10, 10), 2);
+        assert_eq!(Topology::Torus.distance(&(2, 3), &(4, 1), 10, 10), 4);
+        assert_eq!(Topology::Bounded.distance(&(0, 0), &(9, 9), 10, 10), 18);
+        // over the top edge lands in the mirrored column
+        assert_eq!(Topology::Klein.distance(&(0, 0), &(9, 9), 10, 10), 1);
+        assert_eq!(Topology::Klein.distance(&(0, 0), &(9, 0), 10, 10), 2);
+        assert_eq!(Topology::Projective.distance(&(5, 0), &(4, 9), 10, 10), 1);
+        // the top left corner's neighbours across the seam
+        assert_eq!(Topology::Sphere.distance(&(0, 3), &(3, 0), 10, 10), 1);
+        assert_eq!(Topology::Sphere.distance(&(9, 3), &(3, 9), 10, 10), 1);
+    }
+}
@@ -1,10 +1,601 @@
-use super::{Cell, Position};
+use super::{
+    Cell, Direction, Grid, Position, Tile, BG_COLOR, CRUSHER_COLOR, DOOR_COLORS, KEY_COLORS,
+    OBSTACLE_COLOR, PORTAL_COLORS, SWITCH_COLOR, SWITCH_WALL_COLOR,
+};
 
 /// World map that lays out impassable terrain
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct WorldMap {
     /// List of wall `Position`s
     pub walls: Vec<Position>,
     /// Wall color
     pub color: Cell,
+    /// Cells holding some `Tile` other than floor or plain wall. Solid ones are also `walls`
+    pub tiles: Vec<(Position, Tile)>,
+    /// Board dimensions (rows, cols) the map was drawn for, if known
+    pub size: Option<(u32, u32)>,
+    /// Descriptive details
+    pub meta: MapMeta,
+    /// Starting points for players, in player order
+    pub player_spawns: Vec<Spawn>,
+    /// Starting points for enemies, in order of arrival
+    pub enemy_spawns: Vec<Spawn>,
+    /// Areas food may appear in. Anywhere when empty
+    pub food_zones: Vec<Zone>,
+    /// Fixed places food appears, used ahead of `food_zones`
+    pub food_spots: Vec<Position>,
+    /// Linked pairs of cells snakes can jump between
+    pub portals: Vec<Portal>,
+    /// Walls that move or come and go as the game runs
+    pub obstacles: Vec<Obstacle>,
+    /// Keys lying on the board at the start
+    pub keys: Vec<Key>,
+    /// Doors that open for players holding a key of their color
+    pub doors: Vec<Door>,
+    /// Floor switches that open and shut `switch_walls`
+    pub switches: Vec<Switch>,
+    /// Walls opened and shut by `switches`
+    pub switch_walls: Vec<SwitchWall>,
+}
+
+/// Descriptive details of a map
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MapMeta {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// Score a good player is expected to reach
+    pub par: Option<usize>,
+}
+
+/// A starting point for a snake
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Spawn {
+    pub position: Position,
+    pub direction: Direction,
+}
+
+/// A rectangular area of the board
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Zone {
+    /// Top left corner
+    pub position: Position,
+    pub rows: u32,
+    pub cols: u32,
+}
+
+/// One end of a `Portal`
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct PortalEnd {
+    pub position: Position,
+    /// Direction snakes leave this end in. They keep their direction of travel when `None`
+    pub exit: Option<Direction>,
+}
+
+/// A pair of linked cells. A snake moving into either end comes out of the other, landing on the
+/// cell beyond it
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Portal {
+    pub ends: [PortalEnd; 2],
+}
+
+impl Portal {
+    /// Link two cells, keeping the direction of travel through either end
+    pub fn new(a: Position, b: Position) -> Self {
+        let end = |position| PortalEnd {
+            position,
+            exit: None,
+        };
+        Portal {
+            ends: [end(a), end(b)],
+        }
+    }
+
+    /// Returns the end linked to a position, if the position is one of this portal's ends
+    pub fn partner(&self, position: Position) -> Option<PortalEnd> {
+        match self.ends {
+            [a, b] if a.position == position => Some(b),
+            [a, b] if b.position == position => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// How an `Obstacle` gets about
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Motion {
+    /// A single block that steps on to the next of these cells every period, returning to the
+    /// first after the last
+    Path(Vec<Position>),
+    /// A block of cells, solid for the first `on` ticks of every period and open for the rest
+    Timer { cells: Vec<Position>, on: u32 },
+}
+
+/// A wall that moves or comes and goes as the game runs
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Obstacle {
+    pub motion: Motion,
+    /// Ticks between steps along a path, or in each on and off cycle of a timer
+    pub period: u32,
+    /// Ticks into its period the obstacle starts
+    pub offset: u32,
+    /// Whether the obstacle crushes players in its way, rather than waiting for them to leave
+    pub crush: bool,
+}
+
+impl Obstacle {
+    /// Create an `Obstacle` stepping along `path`, one cell every `period` ticks
+    pub fn mover(path: Vec<Position>, period: u32) -> Self {
+        Obstacle {
+            motion: Motion::Path(path),
+            period,
+            offset: 0,
+            crush: false,
+        }
+    }
+
+    /// Create an `Obstacle` covering `cells` for `on` ticks, then leaving them open for `off`
+    pub fn toggle(cells: Vec<Position>, on: u32, off: u32) -> Self {
+        Obstacle {
+            motion: Motion::Timer { cells, on },
+            period: on + off,
+            offset: 0,
+            crush: false,
+        }
+    }
+
+    /// Crush players in the way
+    pub fn crushing(mut self) -> Self {
+        self.crush = true;
+        self
+    }
+
+    /// Start `offset` ticks into the period
+    pub fn with_offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Returns every cell the obstacle ever covers
+    pub fn cells(&self) -> &[Position] {
+        match &self.motion {
+            Motion::Path(path) => path,
+            Motion::Timer { cells, .. } => cells,
+        }
+    }
+
+    /// Returns the cells covered `step` cells along a path, or by a timer, while `solid`
+    pub fn covers(&self, step: usize, solid: bool) -> Vec<Position> {
+        match &self.motion {
+            _ if !solid => vec![],
+            Motion::Path(path) => path
+                .get(step % path.len().max(1))
+                .copied()
+                .into_iter()
+                .collect(),
+            Motion::Timer { cells, .. } => cells.clone(),
+        }
+    }
+
+    /// Whether the obstacle is due to be solid at `tick`. A path is always solid
+    pub fn solid_at(&self, tick: u64) -> bool {
+        match self.motion {
+            Motion::Path(_) => true,
+            Motion::Timer { on, .. } => self.phase(tick) < on as u64,
+        }
+    }
+
+    /// Whether a path obstacle is due to step on at `tick`
+    pub fn steps_at(&self, tick: u64) -> bool {
+        matches!(self.motion, Motion::Path(_)) && self.phase(tick) == 0
+    }
+
+    /// Returns the obstacle with each of its cells moved by `f`
+    pub fn map_cells<F: Fn(Position) -> Position>(&self, f: F) -> Self {
+        let motion = match &self.motion {
+            Motion::Path(path) => Motion::Path(path.iter().map(|p| f(*p)).collect()),
+            Motion::Timer { cells, on } => Motion::Timer {
+                cells: cells.iter().map(|p| f(*p)).collect(),
+                on: *on,
+            },
+        };
+        Obstacle {
+            motion,
+            ..self.clone()
+        }
+    }
+
+    /// Returns how many ticks into its period the obstacle is at `tick`
+    fn phase(&self, tick: u64) -> u64 {
+        (tick + self.offset as u64) % self.period.max(1) as u64
+    }
+}
+
+/// The colors keys, and the doors they open, come in
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum KeyColor {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+}
+
+impl KeyColor {
+    /// Every key color
+    pub const ALL: [KeyColor; 4] = [
+        KeyColor::Red,
+        KeyColor::Yellow,
+        KeyColor::Green,
+        KeyColor::Blue,
+    ];
+
+    /// Returns the `KeyColor` with the given name, eg "red"
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|color| color.name() == name)
+    }
+
+    /// Returns the name this color is written as in map files
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Red => "red",
+            Self::Yellow => "yellow",
+            Self::Green => "green",
+            Self::Blue => "blue",
+        }
+    }
+
+    /// Returns the color a key of this color is displayed in
+    pub fn key_cell(&self) -> Cell {
+        KEY_COLORS[*self as usize]
+    }
+
+    /// Returns the color a door of this color is displayed in
+    pub fn door_cell(&self) -> Cell {
+        DOOR_COLORS[*self as usize]
+    }
+}
+
+/// A key lying on the board, picked up by the first player to reach it
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Key {
+    pub position: Position,
+    pub color: KeyColor,
+}
+
+/// A door, shut until a player holding a key of its color walks into it
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Door {
+    pub cells: Vec<Position>,
+    pub color: KeyColor,
+}
+
+/// A floor switch that opens or shuts every `SwitchWall` in its group when a player steps on it
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Switch {
+    pub position: Position,
+    pub group: u32,
+}
+
+/// A stretch of wall opened and shut by the `Switch`es in its group
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SwitchWall {
+    pub cells: Vec<Position>,
+    pub group: u32,
+    /// Whether the wall starts open
+    pub open: bool,
+}
+
+/// How far the players have got with a map's keys, doors and switches
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct PuzzleState {
+    /// Whether each of the map's keys still lies on the board
+    pub keys: Vec<bool>,
+    /// Whether each of the map's doors is still shut
+    pub doors: Vec<bool>,
+    /// Whether each of the map's switch walls is shut
+    pub walls: Vec<bool>,
+}
+
+impl Zone {
+    /// Whether a position lies inside this `Zone`
+    pub fn contains(&self, (row, col): Position) -> bool {
+        let (top, left) = self.position;
+        row >= top && col >= left && row < top + self.rows && col < left + self.cols
+    }
+}
+
+impl WorldMap {
+    /// Create a new `WorldMap` with plain walls and no other details
+    pub fn new(walls: Vec<Position>, color: Cell) -> Self {
+        WorldMap {
+            walls,
+            color,
+            tiles: vec![],
+            size: None,
+            meta: MapMeta::default(),
+            player_spawns: vec![],
+            enemy_spawns: vec![],
+            food_zones: vec![],
+            food_spots: vec![],
+            portals: vec![],
+            obstacles: vec![],
+            keys: vec![],
+            doors: vec![],
+            switches: vec![],
+            switch_walls: vec![],
+        }
+    }
+
+    /// Lay a `Tile` at a position, adding it to `walls` if it's solid
+    pub fn place(&mut self, position: Position, tile: Tile) {
+        if tile.is_solid() {
+            self.walls.push(position);
+        }
+        if tile != Tile::Wall && tile != Tile::Floor {
+            self.tiles.push((position, tile));
+        }
+    }
+
+    /// Replace whatever `Tile` is at a position, eg when a snake breaks a brick
+    pub fn set_tile(&mut self, position: Position, tile: Tile) {
+        self.walls.retain(|wall| *wall != position);
+        self.tiles.retain(|(p, _)| *p != position);
+        self.place(position, tile);
+    }
+
+    /// Returns the kind of terrain at a position
+    pub fn tile_at(&self, position: Position) -> Tile {
+        match self.tiles.iter().find(|(p, _)| *p == position) {
+            Some((_, tile)) => *tile,
+            None if self.walls.contains(&position) => Tile::Wall,
+            None => Tile::Floor,
+        }
+    }
+
+    /// Returns the end linked to a position, if a portal opens there
+    pub fn portal_partner(&self, position: Position) -> Option<PortalEnd> {
+        self.portals
+            .iter()
+            .find_map(|portal| portal.partner(position))
+    }
+
+    /// Returns the state of this map's keys, doors and switch walls at the start of a game
+    pub fn start_puzzle(&self) -> PuzzleState {
+        PuzzleState {
+            keys: vec![true; self.keys.len()],
+            doors: vec![true; self.doors.len()],
+            walls: self.switch_walls.iter().map(|wall| !wall.open).collect(),
+        }
+    }
+
+    /// Returns the cells taken up by keys still lying on the board, shut doors and switch walls,
+    /// and switches, with the color each is displayed in
+    pub fn puzzle_cells(&self, puzzle: &PuzzleState) -> Vec<(Position, Cell)> {
+        let keys = self
+            .keys
+            .iter()
+            .zip(puzzle.keys.iter())
+            .filter(|(_, lying)| **lying)
+            .map(|(key, _)| (key.position, key.color.key_cell()));
+        let doors = self
+            .doors
+            .iter()
+            .zip(puzzle.doors.iter())
+            .filter(|(_, shut)| **shut)
+            .flat_map(|(door, _)| door.cells.iter().map(move |p| (*p, door.color.door_cell())));
+        let walls = self
+            .switch_walls
+            .iter()
+            .zip(puzzle.walls.iter())
+            .filter(|(_, shut)| **shut)
+            .flat_map(|(wall, _)| wall.cells.iter().map(|p| (*p, SWITCH_WALL_COLOR)));
+        let switches = self
+            .switches
+            .iter()
+            .map(|switch| (switch.position, SWITCH_COLOR));
+        keys.chain(doors).chain(walls).chain(switches).collect()
+    }
+
+    /// Draw this map on an empty `rows` x `cols` grid, with its obstacles and puzzle as they
+    /// start, eg for a preview
+    pub fn render(&self, rows: u32, cols: u32) -> Grid {
+        let obstacles: Vec<Vec<Position>> = self
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.covers(0, obstacle.solid_at(0)))
+            .collect();
+        self.render_with(rows, cols, &obstacles, &self.start_puzzle())
+    }
+
+    /// Draw this map's walls and portals on an empty `rows` x `cols` grid, along with the cells
+    /// each of its obstacles covers and its puzzle in the given state
+    ///
+    /// Each portal's ends share a color, and crushers stand out from other obstacles. Anything
+    /// that falls outside the grid is left out.
+    pub fn render_with(
+        &self,
+        rows: u32,
+        cols: u32,
+        obstacles: &[Vec<Position>],
+        puzzle: &PuzzleState,
+    ) -> Grid {
+        let mut grid = vec![vec![BG_COLOR; cols as usize]; rows as usize];
+        let in_bounds = |(row, col): &Position| *row < rows && *col < cols;
+        for (row, col) in self.walls.iter().filter(|p| in_bounds(p)) {
+            grid[*row as usize][*col as usize] = self.color;
+        }
+        for ((row, col), tile) in self.tiles.iter().filter(|(p, _)| in_bounds(p)) {
+            grid[*row as usize][*col as usize] = tile.color();
+        }
+        for (portal, color) in self.portals.iter().zip(PORTAL_COLORS.iter().cycle()) {
+            for end in portal.ends.iter().filter(|end| in_bounds(&end.position)) {
+                let (row, col) = end.position;
+                grid[row as usize][col as usize] = *color;
+            }
+        }
+        for ((row, col), color) in self.puzzle_cells(puzzle) {
+            if in_bounds(&(row, col)) {
+                grid[row as usize][col as usize] = color;
+            }
+        }
+        for (obstacle, cells) in self.obstacles.iter().zip(obstacles.iter()) {
+            let color = match obstacle.crush {
+                true => CRUSHER_COLOR,
+                false => OBSTACLE_COLOR,
+            };
+            for (row, col) in cells.iter().filter(|p| in_bounds(p)) {
+                grid[*row as usize][*col as usize] = color;
+            }
+        }
+
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ROCK_COLOR, WALL_COLOR};
+
+    #[test]
+    fn renders_walls() {
+        let map = WorldMap::new(vec![(0, 1), (1, 0), (5, 5)], WALL_COLOR);
+        let expected = vec![vec![BG_COLOR, WALL_COLOR], vec![WALL_COLOR, BG_COLOR]];
+        assert_eq!(map.render(2, 2), expected);
+    }
+
+    #[test]
+    fn renders_tiles() {
+        let mut map = WorldMap::new(vec![(0, 1), (1, 0)], WALL_COLOR);
+        map.tiles.push(((1, 0), Tile::Rock));
+        assert_eq!(map.render(2, 2)[1][0], ROCK_COLOR);
+        assert_eq!(map.tile_at((1, 0)), Tile::Rock);
+        assert_eq!(map.tile_at((0, 1)), Tile::Wall);
+        assert_eq!(map.tile_at((0, 0)), Tile::Floor);
+    }
+
+    #[test]
+    fn places_terrain() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        let conveyor = Tile::Conveyor(Direction::Left);
+        for (position, tile) in [
+            ((0, 0), Tile::Wall),
+            ((0, 1), Tile::Rock),
+            ((1, 0), conveyor),
+        ] {
+            map.place(position, tile);
+        }
+        map.place((1, 1), Tile::Floor);
+        assert_eq!(map.walls, vec![(0, 0), (0, 1)]);
+        assert_eq!(map.tile_at((1, 0)), conveyor);
+        assert_eq!(map.tile_at((1, 1)), Tile::Floor);
+        assert_eq!(map.render(2, 2)[1][0], conveyor.color());
+    }
+
+    #[test]
+    fn replaces_tiles() {
+        let mut map = WorldMap::new(vec![(0, 0)], WALL_COLOR);
+        map.place((0, 1), Tile::Brick(2));
+        map.set_tile((0, 1), Tile::Brick(1));
+        assert_eq!(map.tile_at((0, 1)), Tile::Brick(1));
+        assert_eq!(map.tiles.len(), 1);
+        map.set_tile((0, 1), Tile::Floor);
+        map.set_tile((0, 0), Tile::Ice);
+        assert_eq!(map.walls, vec![]);
+        assert_eq!(map.tiles, vec![((0, 0), Tile::Ice)]);
+    }
+
+    #[test]
+    fn renders_portal_pairs() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.portals = vec![Portal::new((0, 0), (1, 2)), Portal::new((1, 0), (0, 2))];
+        let grid = map.render(2, 3);
+        assert_eq!(grid[0][0], grid[1][2]);
+        assert_eq!(grid[1][0], grid[0][2]);
+        assert_ne!(grid[0][0], grid[1][0]);
+        assert_eq!(grid[0][1], BG_COLOR);
+    }
+
+    #[test]
+    fn finds_portal_partner() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.portals.push(Portal::new((0, 0), (5, 5)));
+        assert_eq!(map.portal_partner((0, 0)).unwrap().position, (5, 5));
+        assert_eq!(map.portal_partner((5, 5)).unwrap().position, (0, 0));
+        assert_eq!(map.portal_partner((0, 5)), None);
+    }
+
+    #[test]
+    fn moves_obstacles() {
+        let mover = Obstacle::mover(vec![(0, 0), (0, 1), (1, 1)], 2).with_offset(1);
+        assert!(mover.steps_at(1));
+        assert!(!mover.steps_at(2));
+        assert_eq!(mover.covers(4, true), vec![(0, 1)]);
+
+        let crusher = Obstacle::toggle(vec![(1, 0), (1, 1)], 1, 3).crushing();
+        let timeline: Vec<bool> = (0..5).map(|tick| crusher.solid_at(tick)).collect();
+        assert_eq!(timeline, vec![true, false, false, false, true]);
+        assert!(crusher.covers(0, false).is_empty());
+
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.obstacles = vec![mover, crusher];
+        let grid = map.render(2, 2);
+        assert_eq!(grid[0][0], OBSTACLE_COLOR);
+        assert_eq!(grid[1], vec![CRUSHER_COLOR, CRUSHER_COLOR]);
+        let grid = map.render_with(2, 2, &[vec![(1, 1)], vec![]], &PuzzleState::default());
+        assert_eq!(grid[0][0], BG_COLOR);
+        assert_eq!(grid[1], vec![BG_COLOR, OBSTACLE_COLOR]);
+    }
+
+    #[test]
+    fn renders_puzzles() {
+        let mut map = WorldMap::new(vec![], WALL_COLOR);
+        map.keys.push(Key {
+            position: (0, 0),
+            color: KeyColor::Green,
+        });
+        map.doors.push(Door {
+            cells: vec![(0, 1), (0, 2)],
+            color: KeyColor::Green,
+        });
+        map.switches.push(Switch {
+            position: (1, 0),
+            group: 3,
+        });
+        for open in [false, true] {
+            map.switch_walls.push(SwitchWall {
+                cells: vec![(1, 1 + open as u32)],
+                group: 3,
+                open,
+            });
+        }
+        let puzzle = map.start_puzzle();
+        assert_eq!(puzzle.walls, vec![true, false]);
+        let grid = map.render(2, 3);
+        assert_eq!(grid[0], vec![KEY_COLORS[2], DOOR_COLORS[2], DOOR_COLORS[2]]);
+        assert_eq!(grid[1], vec![SWITCH_COLOR, SWITCH_WALL_COLOR, BG_COLOR]);
+
+        let solved = PuzzleState {
+            keys: vec![false],
+            doors: vec![false],
+            walls: vec![false, true],
+        };
+        let grid = map.render_with(2, 3, &[], &solved);
+        assert_eq!(grid[0], vec![BG_COLOR; 3]);
+        assert_eq!(grid[1], vec![SWITCH_COLOR, BG_COLOR, SWITCH_WALL_COLOR]);
+        assert_eq!(KeyColor::from_name("green"), Some(KeyColor::Green));
+    }
+
+    #[test]
+    fn zone_bounds() {
+        let zone = Zone {
+            position: (2, 3),
+            rows: 2,
+            cols: 4,
+        };
+        assert!(zone.contains((2, 3)));
+        assert!(zone.contains((3, 6)));
+        assert!(!zone.contains((4, 3)));
+        assert!(!zone.contains((2, 7)));
+        assert!(!zone.contains((1, 3)));
+    }
 }
@@ -1,35 +1,163 @@
-use rand::Rng;
+use std::{borrow::Cow, collections::VecDeque};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    collision,
-    map::Mapper,
+    ai, collision,
+    enemy::{Enemy, Surroundings},
     snake::Snake,
-    types::{self, Direction, Food, GameEvent, GameMode, Grid, SnakeEvent, WorldMap, FOOD_COLOR},
+    types::{
+        self, Direction, EnemySpawn, EvilConfig, Food, GameEvent, Goal, Grid, KeyColor, Portal,
+        Position, PuzzleState, Ruleset, SnakeEvent, Speed, Tile, WorldMap, FOOD_COLOR,
+    },
 };
 
+/// Ticks between each shrink of the battle royale arena
+pub const SHRINK_INTERVAL: u64 = 60;
+
+/// The battle royale arena stops shrinking once it is this narrow
+pub const MIN_ARENA: u32 = 8;
+
+/// Number of snakes in a battle royale
+pub const ROYALE_SNAKES: usize = 8;
+
+/// Enemies never spawn within this many cells of a player's head
+pub const SPAWN_CLEARANCE: u32 = 3;
+
+/// Ticks between chances for a drill to appear on a map with bricks
+pub const DRILL_INTERVAL: u64 = 100;
+
+/// Ticks a drill lasts once picked up
+pub const DRILL_TICKS: u32 = 40;
+
+/// Who steers a `Player`'s snake
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Controller {
+    /// A person, via local or remote input
+    Human,
+    /// The computer
+    Bot,
+}
+
+/// A snake on the board along with its controls and progress
+#[derive(Debug)]
+pub struct Player {
+    /// The player's avatar
+    pub snake: Snake,
+
+    /// The player's direction of travel
+    pub direction: Direction,
+
+    /// The player's score
+    pub score: usize,
+
+    /// Whether the player is still on the board
+    pub alive: bool,
+
+    /// Who steers this player
+    pub controller: Controller,
+
+    /// Keys the player has picked up, in the order it found them
+    pub keys: Vec<KeyColor>,
+
+    /// Ticks left on the player's drill. No drill when 0
+    pub drill: u32,
+}
+
+impl Player {
+    /// Create a new `Player` at the given location
+    pub fn new(snake: Snake, direction: Direction, controller: Controller) -> Self {
+        Player {
+            snake,
+            direction,
+            score: 0,
+            alive: true,
+            controller,
+            keys: vec![],
+            drill: 0,
+        }
+    }
+}
+
+/// How far one of the map's obstacles has got through its motion
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct ObstacleState {
+    /// Steps taken along its path
+    step: usize,
+    /// Whether it currently blocks its cells
+    solid: bool,
+}
+
+/// Returns the starting state of each of a map's obstacles
+fn start_obstacles(world_map: Option<&WorldMap>) -> Vec<ObstacleState> {
+    world_map.map_or(vec![], |world_map| {
+        world_map
+            .obstacles
+            .iter()
+            .map(|obstacle| ObstacleState {
+                step: 0,
+                solid: obstacle.solid_at(0),
+            })
+            .collect()
+    })
+}
+
+/// Returns the starting state of a map's keys, doors and switch walls
+fn start_puzzle(world_map: Option<&WorldMap>) -> PuzzleState {
+    world_map.map_or_else(PuzzleState::default, WorldMap::start_puzzle)
+}
+
+/// Starting location for the player with the given index
+pub fn spawn_point(index: usize, rows: u32, cols: u32) -> Position {
+    match index % ROYALE_SNAKES {
+        0 => (0, 0),
+        1 => (rows - 1, 0),
+        2 => (0, cols - 1),
+        3 => (rows / 2, 0),
+        4 => (rows / 2, cols - 1),
+        5 => (0, cols / 2),
+        6 => (rows - 1, cols / 2),
+        _ => (rows - 1, cols - 1),
+    }
+}
+
+/// Default starting location for the enemy with the given index, one per quarter of the board
+pub fn enemy_point(index: usize, rows: u32, cols: u32) -> Position {
+    let (near, far) = ((rows / 4, cols / 4), (rows * 3 / 4, cols * 3 / 4));
+    match index % 4 {
+        0 => (far.0, far.1),
+        1 => (near.0, far.1),
+        2 => (far.0, near.1),
+        _ => (near.0, near.1),
+    }
+}
+
 /// The state of the gameworld
 #[derive(Debug)]
 pub struct Gamestate {
     pub grid: Grid,
     pub world_size: (u32, u32),
 
-    /// The player's direction of travel
-    pub direction: Direction,
-
-    /// The player's avatar
-    pub player: crate::snake::Snake,
+    /// Every snake on the board. The first is the local player's avatar
+    pub players: Vec<Player>,
 
-    /// A shadow of the player avatar. The player's passive nemesis
-    pub evil: crate::snake::Snake,
+    /// The player's nemeses. By default a single shadow of the player avatar
+    pub enemies: Vec<Enemy>,
 
-    /// The player's objective
+    /// The players' objective
     pub food: Food,
 
-    /// The player's score
-    pub score: usize,
+    /// Edible remains of eliminated snakes
+    pub scraps: Vec<Position>,
+
+    /// Drill power-up lying on the board, if any
+    pub drill: Option<Position>,
+
+    /// Number of simulation steps taken so far
+    pub tick: u64,
 
-    /// The current ruleset
-    game_mode: GameMode,
+    /// The rules this game is played by
+    rules: Ruleset,
 
     /// Delay between gamestate updates. The simulation speed
     game_speed: u64,
@@ -37,85 +165,823 @@ pub struct Gamestate {
     /// Simulation pause flag
     paused: bool,
 
-    /// Optional world map that lays out impassable terrain
+    /// Optional world map that lays out impassable terrain. The game's own copy, changed as
+    /// bricks are broken
     world_map: Option<WorldMap>,
+
+    /// Number of border rings that have been converted to walls
+    arena_ring: u32,
+
+    /// Progress of each of the map's obstacles
+    obstacles: Vec<ObstacleState>,
+
+    /// Which of the map's keys, doors and switch walls are still in place
+    puzzle: PuzzleState,
+
+    /// Every enemy that appears over the course of the game, in order of arrival
+    enemy_spawns: Vec<EnemySpawn>,
+
+    /// Source of randomness for the simulation. Seeded so peers can reproduce it exactly
+    rng: StdRng,
 }
 
 impl Gamestate {
     /// Create a new instance of GameState
-    pub fn new(rows: u32, cols: u32, game_mode: GameMode, mapper: Option<Box<dyn Mapper>>) -> Self {
-        let world_map = match game_mode {
-            GameMode::Map => Some(
-                mapper
-                    .unwrap_or_else(|| {
-                        panic!("Mapper must be supplied when game mode is {:?}", game_mode)
-                    })
-                    .load_map()
-                    .unwrap_or_else(|e| panic!("Failed to load map: {}", e)),
-            ),
-            _ => None,
+    ///
+    /// `rules` may be a `GameMode`, for that mode's preset. `world_map` lays out the walls of a
+    /// labyrinth game, and is ignored otherwise. A map that declares its own size is played on a
+    /// board of that size rather than `rows` x `cols`.
+    pub fn new(
+        rows: u32,
+        cols: u32,
+        rules: impl Into<Ruleset>,
+        world_map: Option<WorldMap>,
+    ) -> Self {
+        let rules = rules.into();
+        let world_map = world_map.filter(|_| rules.labyrinth);
+        let (rows, cols) = world_map
+            .as_ref()
+            .and_then(|world_map| world_map.size)
+            .unwrap_or((rows, cols));
+        let enemy_spawns = match rules.enemies {
+            true => EnemySpawn::waves(&EvilConfig::default()),
+            false => vec![],
         };
+        let start = world_map
+            .as_ref()
+            .and_then(|world_map| world_map.player_spawns.first())
+            .map_or(((0, 0), Direction::Down), |spawn| {
+                (spawn.position, spawn.direction)
+            });
+        let portals = world_map
+            .as_ref()
+            .map_or(vec![], |world_map| world_map.portals.clone());
+        let player = Player::new(
+            Snake::new(start.0 .0, start.0 .1, None)
+                .with_portals(portals)
+                .with_topology(rules.topology),
+            start.1,
+            Controller::Human,
+        );
 
-        Gamestate {
+        let mut state = Gamestate {
             grid: vec![],
-            direction: Direction::Down,
-            player: Snake::new(0, 0, None, Some(game_mode)),
-            evil: Snake::new(35, 35, Some(types::EVIL_COLOR), Some(game_mode)),
-            food: Food::new(rows / 2, cols / 2, Some(FOOD_COLOR), None),
+            players: vec![player],
+            enemies: vec![],
+            food: Food::new(rows / 2, cols / 2, Some(FOOD_COLOR)),
+            scraps: vec![],
+            drill: None,
             world_size: (rows, cols),
-            score: 0,
-            game_mode,
+            tick: 0,
+            rules,
             game_speed: 200,
             paused: false,
+            obstacles: start_obstacles(world_map.as_ref()),
+            puzzle: start_puzzle(world_map.as_ref()),
             world_map,
+            arena_ring: 0,
+            enemy_spawns,
+            rng: StdRng::from_entropy(),
+        };
+        state.spawn_enemies();
+
+        state
+    }
+
+    /// Reseed the simulation's random number generator, making the game reproducible
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Choose how many enemies appear and how they behave
+    pub fn with_evil(self, config: EvilConfig) -> Self {
+        self.with_enemies(EnemySpawn::waves(&config))
+    }
+
+    /// Replace the enemy schedule. Ignored when the rules leave enemies out
+    pub fn with_enemies(mut self, mut spawns: Vec<EnemySpawn>) -> Self {
+        if !self.rules.enemies {
+            return self;
+        }
+        spawns.sort_by_key(|spawn| spawn.at_score);
+        self.enemies.clear();
+        self.enemy_spawns = spawns;
+        self.spawn_enemies();
+        self
+    }
+
+    /// Returns the enemy schedule
+    pub fn enemy_spawns(&self) -> &[EnemySpawn] {
+        &self.enemy_spawns
+    }
+
+    /// Add a snake at the next spawn point. Once a map's spawns run out, the snake takes the free
+    /// cell closest to a default spawn point. Returns the new player's id
+    pub fn add_player(&mut self, controller: Controller) -> usize {
+        let id = self.players.len();
+        let (rows, cols) = self.world_size;
+        let cell = types::RIVAL_COLORS[(id - 1) % types::RIVAL_COLORS.len()];
+        let map_spawn = self
+            .world_map
+            .as_ref()
+            .and_then(|world_map| world_map.player_spawns.get(id));
+        let ((row, col), direction) = match map_spawn {
+            Some(spawn) => (spawn.position, spawn.direction),
+            None => {
+                let start = spawn_point(id, rows, cols);
+                let (row, col) = self.spawn_position(start, 0).unwrap_or(start);
+                let direction = if row < rows / 2 {
+                    Direction::Down
+                } else {
+                    Direction::Up
+                };
+                ((row, col), direction)
+            }
+        };
+
+        self.players.push(Player::new(
+            Snake::new(row, col, Some(cell))
+                .with_portals(self.portals())
+                .with_topology(self.rules.topology),
+            direction,
+            controller,
+        ));
+
+        id
+    }
+
+    /// Add computer controlled snakes until there are `count` snakes on the board
+    pub fn fill_with_bots(&mut self, count: usize) {
+        while self.players.len() < count {
+            self.add_player(Controller::Bot);
+        }
+    }
+
+    /// Remove a player's snake from the board, eg when a remote player disconnects
+    pub fn remove_player(&mut self, player: usize) {
+        if let Some(player) = self.players.get_mut(player) {
+            player.alive = false;
+        }
+    }
+
+    /// Returns the local player's avatar
+    pub fn player(&self) -> &Player {
+        &self.players[0]
+    }
+
+    /// Returns the local player's score
+    pub fn score(&self) -> usize {
+        self.player().score
+    }
+
+    /// Returns every player's score
+    pub fn scores(&self) -> Vec<usize> {
+        self.players.iter().map(|player| player.score).collect()
+    }
+
+    /// Returns the current ruleset
+    pub fn rules(&self) -> Ruleset {
+        self.rules
+    }
+
+    /// Returns the loaded world map, if any
+    pub fn world_map(&self) -> Option<&WorldMap> {
+        self.world_map.as_ref()
+    }
+
+    /// Returns the loaded map's par score, if it sets one
+    pub fn par(&self) -> Option<usize> {
+        self.world_map
+            .as_ref()
+            .and_then(|world_map| world_map.meta.par)
+    }
+
+    /// Swap in an edited version of the loaded map mid-game, eg after its file changes
+    ///
+    /// A snake caught in the new walls is gathered up onto the open cell nearest its head,
+    /// keeping its length, and uncoils as it moves. Food caught in them grows elsewhere and
+    /// scraps are lost. The new map's obstacles start from the beginning of their motion, and its
+    /// keys, doors and switches are reset, though players keep the keys they hold. Only labyrinth
+    /// games have a map to replace.
+    pub fn replace_map(&mut self, world_map: WorldMap) {
+        if !self.rules.labyrinth {
+            return;
+        }
+        self.obstacles = start_obstacles(Some(&world_map));
+        self.puzzle = start_puzzle(Some(&world_map));
+        self.world_map = Some(world_map);
+        let portals = self.portals();
+        for player in self.players.iter_mut() {
+            player.snake.portals = portals.clone();
+        }
+        for evil in self.enemies.iter_mut() {
+            evil.snake.portals = portals.clone();
+        }
+        let grid = self.grid_init();
+        let blocked = |(row, col): &Position| {
+            grid.get(*row as usize)
+                .and_then(|cells| cells.get(*col as usize))
+                .is_none_or(|cell| {
+                    !Direction::ALL
+                        .iter()
+                        .any(|heading| collision::is_passable(*cell, *heading))
+                })
+        };
+
+        let snakes = self.players.len() + self.enemies.len();
+        for i in 0..snakes {
+            let snake = match i.checked_sub(self.players.len()) {
+                None if !self.players[i].alive => continue,
+                None => &self.players[i].snake,
+                Some(enemy) => &self.enemies[enemy].snake,
+            };
+            if !snake.body.iter().any(blocked) {
+                continue;
+            }
+            let length = snake.body.len();
+            let position = match self.open_position(&grid, *snake.position()) {
+                Some(position) => position,
+                None => continue,
+            };
+            let body = std::iter::repeat_n(position, length).collect();
+            match i.checked_sub(self.players.len()) {
+                None => self.players[i].snake.body = body,
+                Some(enemy) => self.enemies[enemy].snake.body = body,
+            }
+        }
+
+        self.scraps.retain(|scrap| !blocked(scrap));
+        self.drill = self.drill.filter(|drill| !blocked(drill));
+        self.refresh_grid();
+        if blocked(self.food.position()) {
+            self.fresh_food();
+            self.refresh_grid();
+        }
+    }
+
+    /// Returns the portals on the map, if any
+    fn portals(&self) -> Vec<Portal> {
+        self.world_map
+            .as_ref()
+            .map_or(vec![], |world_map| world_map.portals.clone())
+    }
+
+    /// Find the closest open cell to `start` on `grid` that no snake occupies
+    fn open_position(&self, grid: &Grid, start: Position) -> Option<Position> {
+        let (rows, cols) = self.world_size;
+        let occupied = |position: &Position| {
+            self.players
+                .iter()
+                .filter(|player| player.alive)
+                .map(|player| &player.snake)
+                .chain(self.enemies.iter().map(|enemy| &enemy.snake))
+                .any(|snake| snake.body.contains(position))
+        };
+        let mut visited = vec![vec![false; cols as usize]; rows as usize];
+        visited[start.0 as usize][start.1 as usize] = true;
+
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some((row, col)) = queue.pop_front() {
+            if grid[row as usize][col as usize] == types::BG_COLOR && !occupied(&(row, col)) {
+                return Some((row, col));
+            }
+            let neighbors = [
+                ((row + rows - 1) % rows, col),
+                (row, (col + 1) % cols),
+                ((row + 1) % rows, col),
+                (row, (col + cols - 1) % cols),
+            ];
+            for (r, c) in neighbors.iter().copied() {
+                if !visited[r as usize][c as usize] {
+                    visited[r as usize][c as usize] = true;
+                    queue.push_back((r, c));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether a position lies inside the playable area
+    fn in_arena(&self, (row, col): Position) -> bool {
+        let (rows, cols) = self.world_size;
+        let ring = self.arena_ring;
+        row >= ring && col >= ring && row + ring < rows && col + ring < cols
+    }
+
+    /// Whether a snake may appear at a position, more than `clearance` steps from every player
+    fn can_spawn(&self, position: Position, clearance: u32) -> bool {
+        let (rows, cols) = self.world_size;
+        let walled = self
+            .world_map
+            .as_ref()
+            .is_some_and(|map| map.walls.contains(&position))
+            || self
+                .obstacle_cells()
+                .iter()
+                .flatten()
+                .any(|p| *p == position)
+            || self.puzzle_cells().iter().any(|(p, _)| *p == position);
+        let occupied = self
+            .players
+            .iter()
+            .filter(|player| player.alive)
+            .map(|player| &player.snake)
+            .chain(self.enemies.iter().map(|enemy| &enemy.snake))
+            .any(|snake| snake.body.contains(&position));
+        let crowded = self
+            .players
+            .iter()
+            .filter(|player| player.alive)
+            .any(|player| {
+                self.rules
+                    .topology
+                    .distance(player.snake.position(), &position, rows, cols)
+                    <= clearance
+            });
+
+        self.in_arena(position)
+            && !walled
+            && !occupied
+            && !crowded
+            && *self.food.position() != position
+            && !self.scraps.contains(&position)
+            && self.drill != Some(position)
+    }
+
+    /// Find the closest position to `start` where a snake may appear, more than `clearance` steps
+    /// from every player
+    fn spawn_position(&self, start: Position, clearance: u32) -> Option<Position> {
+        let (rows, cols) = self.world_size;
+        let mut visited = vec![vec![false; cols as usize]; rows as usize];
+        visited[start.0 as usize][start.1 as usize] = true;
+
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some((row, col)) = queue.pop_front() {
+            if self.can_spawn((row, col), clearance) {
+                return Some((row, col));
+            }
+            let neighbors = [
+                ((row + rows - 1) % rows, col),
+                (row, (col + 1) % cols),
+                ((row + 1) % rows, col),
+                (row, (col + cols - 1) % cols),
+            ];
+            for (r, c) in neighbors.iter().copied() {
+                if !visited[r as usize][c as usize] {
+                    visited[r as usize][c as usize] = true;
+                    queue.push_back((r, c));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Bring in every scheduled enemy the player's score has earned
+    ///
+    /// An enemy with no room to appear waits until there is some.
+    fn spawn_enemies(&mut self) {
+        let (rows, cols) = self.world_size;
+        while let Some(spawn) = self.enemy_spawns.get(self.enemies.len()).copied() {
+            if spawn.at_score > self.score() {
+                break;
+            }
+            let index = self.enemies.len();
+            let map_spawn = self
+                .world_map
+                .as_ref()
+                .and_then(|world_map| world_map.enemy_spawns.get(index))
+                .copied();
+            let start = spawn
+                .position
+                .or_else(|| map_spawn.map(|map_spawn| map_spawn.position))
+                .unwrap_or_else(|| enemy_point(index, rows, cols));
+            let (row, col) = match self.spawn_position(start, SPAWN_CLEARANCE) {
+                Some(position) => position,
+                None => break,
+            };
+
+            let mut enemy = Enemy::new(
+                Snake::new(row, col, Some(spawn.color))
+                    .with_portals(self.portals())
+                    .with_topology(self.rules.topology),
+                spawn.behavior,
+                spawn.difficulty,
+            );
+            if let Some(map_spawn) = map_spawn {
+                enemy.direction = map_spawn.direction;
+            }
+            self.enemies.push(enemy);
         }
     }
 
     /// Create a new target object at a random location
+    ///
+    /// A map with food spots grows food on one of them, otherwise a map with food zones only
+    /// grows food inside them, as long as they have room.
     fn fresh_food(&mut self) {
-        let mut row = rand::thread_rng().gen_range(0, self.grid.len());
-        let mut col = rand::thread_rng().gen_range(0, self.grid[0].len());
+        let (spots, zones) = match self.world_map.as_ref() {
+            Some(world_map) => (&world_map.food_spots[..], &world_map.food_zones[..]),
+            None => (&[][..], &[][..]),
+        };
+        let is_free = |(row, col): &Position| {
+            self.grid
+                .get(*row as usize)
+                .and_then(|cells| cells.get(*col as usize))
+                == Some(&types::BG_COLOR)
+                && self.in_arena((*row, *col))
+        };
+        let mut free: Vec<Position> = spots.iter().copied().filter(is_free).collect();
+        if free.is_empty() {
+            free = zones
+                .iter()
+                .flat_map(|zone| {
+                    let (top, left) = zone.position;
+                    (top..top + zone.rows)
+                        .flat_map(move |row| (left..left + zone.cols).map(move |col| (row, col)))
+                })
+                .filter(is_free)
+                .collect();
+        }
+        if !free.is_empty() {
+            let (row, col) = free[self.rng.gen_range(0, free.len())];
+            self.food = Food::new(row, col, Some(FOOD_COLOR));
+            return;
+        }
 
-        while self.grid[row][col] != types::BG_COLOR {
-            row = rand::thread_rng().gen_range(0, self.grid.len());
-            col = rand::thread_rng().gen_range(0, self.grid[0].len());
+        let (row, col) = self.random_open_cell();
+        self.food = Food::new(row, col, Some(FOOD_COLOR));
+    }
+
+    /// Pick a random empty cell inside the arena
+    fn random_open_cell(&mut self) -> Position {
+        let mut row = self.rng.gen_range(0, self.grid.len());
+        let mut col = self.rng.gen_range(0, self.grid[0].len());
+
+        while self.grid[row][col] != types::BG_COLOR || !self.in_arena((row as u32, col as u32)) {
+            row = self.rng.gen_range(0, self.grid.len());
+            col = self.rng.gen_range(0, self.grid[0].len());
         }
 
-        self.food = Food::new(row as u32, col as u32, Some(FOOD_COLOR), None);
+        (row as u32, col as u32)
     }
 
-    /// Transition game state due to  player collision events
-    fn handle_collision(&mut self, evt: &Option<SnakeEvent>) {
+    /// Remove whatever food lies at a position
+    fn consume_food(&mut self, position: Position) {
+        match self.scraps.iter().position(|scrap| *scrap == position) {
+            Some(i) => {
+                self.scraps.swap_remove(i);
+            }
+            None => self.fresh_food(),
+        }
+    }
+
+    /// Transition a player's state due to its collision events
+    fn handle_collision(&mut self, index: usize, evt: &Option<SnakeEvent>) {
+        let direction = self.players[index].direction;
+        if let Some(heading) = self.move_player(index, direction, evt) {
+            self.players[index].direction = heading;
+        }
+    }
+
+    /// Move a player one cell in `direction` according to its collision event
+    ///
+    /// Returns the direction the player is heading in afterwards, unless it died.
+    fn move_player(
+        &mut self,
+        index: usize,
+        direction: Direction,
+        evt: &Option<SnakeEvent>,
+    ) -> Option<Direction> {
         let (rows, cols) = self.world_size;
+        let player = &mut self.players[index];
         match evt {
             Some(evt @ SnakeEvent::Death) => {
                 println!("event: {:?}", evt);
-                // game over. restart
-                println!("\n\tGAME OVER. restarting\n");
+                player.alive = false;
+                if self.rules.goal == Goal::LastStanding {
+                    self.scraps.extend(player.snake.body.iter());
+                }
+                None
             }
             Some(SnakeEvent::Food) => {
                 println!("event: {:?}", evt);
-                self.score += 1;
-                self.player.grow(&self.direction, cols as i32, rows as i32);
-                self.evil
-                    .grow(&self.direction.flip(), cols as i32, rows as i32);
-                self.fresh_food();
+                player.score += 1;
+                let (_, heading) = player.snake.next_move(&direction, rows as i32, cols as i32);
+                player.snake.grow(&direction, cols as i32, rows as i32);
+                let position = *player.snake.position();
+                self.consume_food(position);
+                Some(heading)
             }
             None => {
-                self.player
-                    .update_position(&self.direction, cols as i32, rows as i32);
-                self.evil
-                    .update_position(&self.direction.flip(), cols as i32, rows as i32);
+                let (_, heading) = player.snake.next_move(&direction, rows as i32, cols as i32);
+                player
+                    .snake
+                    .update_position(&direction, cols as i32, rows as i32);
+                Some(heading)
             }
-            _ => (),
+            _ => Some(direction),
+        }
+    }
+
+    /// Settle a player ramming a brick while moving in `direction`
+    ///
+    /// With a drill the player chips at the brick, waiting in place until it breaks and then
+    /// moving into the hole. Without one the brick is as deadly as any wall. Other events pass
+    /// through as they are.
+    fn dig(
+        &mut self,
+        index: usize,
+        direction: Direction,
+        evt: Option<SnakeEvent>,
+    ) -> Option<SnakeEvent> {
+        if evt != Some(SnakeEvent::Ram) {
+            return evt;
+        }
+        let (rows, cols) = self.world_size;
+        let player = &self.players[index];
+        let target = player
+            .snake
+            .next_position(&direction, rows as i32, cols as i32);
+        let world_map = match self.world_map.as_mut() {
+            Some(world_map) if player.drill > 0 => world_map,
+            _ => return Some(SnakeEvent::Death),
+        };
+        let tile = world_map.tile_at(target).chipped();
+        world_map.set_tile(target, tile);
+        match tile {
+            Tile::Floor => None,
+            _ => Some(SnakeEvent::Ram),
         }
     }
 
+    /// Wear down held drills, let players pick up the one lying on the board, and now and then
+    /// drop a new one while the map has bricks left to break
+    fn use_drills(&mut self) {
+        for player in self.players.iter_mut() {
+            player.drill = player.drill.saturating_sub(1);
+            if player.alive && self.drill == Some(*player.snake.position()) {
+                player.drill = DRILL_TICKS;
+                self.drill = None;
+            }
+        }
+
+        let bricks = self.world_map.as_ref().is_some_and(|world_map| {
+            world_map
+                .tiles
+                .iter()
+                .any(|(_, tile)| tile.is_destructible())
+        });
+        let drilling = self.players.iter().any(|player| player.drill > 0);
+        if bricks && !drilling && self.drill.is_none() && self.tick.is_multiple_of(DRILL_INTERVAL) {
+            self.refresh_grid();
+            self.drill = Some(self.random_open_cell());
+        }
+    }
+
+    /// Push every player whose head is on a conveyor one cell further along it
+    ///
+    /// A push is checked like any other move, so it can eat or crash, but it leaves the player
+    /// heading the way it was. A conveyor running straight against a player doesn't push it.
+    /// Returns each pushed player's id and collision event.
+    fn convey(&mut self) -> Vec<(usize, Option<SnakeEvent>)> {
+        let pushes: Vec<(usize, Direction)> = (0..self.players.len())
+            .filter(|i| self.players[*i].alive)
+            .filter_map(|i| {
+                let player = &self.players[i];
+                self.conveyor_push(&player.snake, player.direction)
+                    .map(|push| (i, push))
+            })
+            .collect();
+        if pushes.is_empty() {
+            return vec![];
+        }
+
+        self.refresh_grid();
+        let moves: Vec<_> = pushes
+            .iter()
+            .map(|(i, push)| (&self.players[*i].snake, *push))
+            .collect();
+        let evts = collision::resolve_moves(&self.grid, &moves);
+        let evts: Vec<_> = pushes
+            .iter()
+            .zip(evts)
+            .map(|((i, push), evt)| self.dig(*i, *push, evt))
+            .collect();
+        for ((i, push), evt) in pushes.iter().zip(evts.iter()) {
+            self.move_player(*i, *push, evt);
+        }
+
+        pushes.into_iter().map(|(i, _)| i).zip(evts).collect()
+    }
+
+    /// Returns the cells each of the map's obstacles currently covers
+    fn obstacle_cells(&self) -> Vec<Vec<Position>> {
+        let obstacles = self
+            .world_map
+            .as_ref()
+            .map_or(&[][..], |world_map| &world_map.obstacles[..]);
+        obstacles
+            .iter()
+            .zip(self.obstacles.iter())
+            .map(|(obstacle, state)| obstacle.covers(state.step, state.solid))
+            .collect()
+    }
+
+    /// Move the map's obstacles on as the tick calls for. Returns whether anyone was crushed
+    ///
+    /// An obstacle about to cover a snake waits for it to leave, unless it's a crusher, which
+    /// eliminates any players in its way. Enemies are never crushed. Food an obstacle covers grows
+    /// elsewhere, and scraps are lost.
+    fn move_obstacles(&mut self) -> bool {
+        let tick = self.tick;
+        let obstacles = self
+            .world_map
+            .as_ref()
+            .map_or(&[][..], |world_map| &world_map.obstacles[..]);
+        let due: Vec<(usize, ObstacleState, Vec<Position>, bool)> = obstacles
+            .iter()
+            .zip(self.obstacles.iter())
+            .enumerate()
+            .filter_map(|(i, (obstacle, state))| {
+                let next = ObstacleState {
+                    step: state.step + obstacle.steps_at(tick) as usize,
+                    solid: obstacle.solid_at(tick),
+                };
+                let cells = obstacle.covers(next.step, next.solid);
+                (next != *state).then_some((i, next, cells, obstacle.crush))
+            })
+            .collect();
+        if due.is_empty() {
+            return false;
+        }
+
+        let mut crushed = false;
+        for (i, next, cells, crush) in due {
+            let in_way = |snake: &Snake| snake.body.iter().any(|p| cells.contains(p));
+            let victims: Vec<usize> = (0..self.players.len())
+                .filter(|id| self.players[*id].alive && in_way(&self.players[*id].snake))
+                .collect();
+            let blocked = self.enemies.iter().any(|evil| in_way(&evil.snake));
+            if blocked || (!crush && !victims.is_empty()) {
+                continue;
+            }
+            for victim in victims {
+                self.handle_collision(victim, &Some(SnakeEvent::Death));
+                crushed = true;
+            }
+            self.obstacles[i] = next;
+        }
+
+        let covered: Vec<Position> = self.obstacle_cells().into_iter().flatten().collect();
+        self.bury(&covered);
+
+        crushed
+    }
+
+    /// Refresh the grid after `covered` cells have turned solid, losing any scraps or drill under
+    /// them and growing any food under them elsewhere
+    fn bury(&mut self, covered: &[Position]) {
+        self.scraps.retain(|scrap| !covered.contains(scrap));
+        self.drill = self.drill.filter(|drill| !covered.contains(drill));
+        self.refresh_grid();
+        if covered.contains(self.food.position()) {
+            self.fresh_food();
+            self.refresh_grid();
+        }
+    }
+
+    /// Returns the cells taken up by the map's keys, switches, and shut doors and switch walls,
+    /// with their colors
+    fn puzzle_cells(&self) -> Vec<(Position, types::Cell)> {
+        self.world_map
+            .as_ref()
+            .map_or(vec![], |world_map| world_map.puzzle_cells(&self.puzzle))
+    }
+
+    /// Open any shut door a player holding its key is about to walk into
+    fn unlock_doors(&mut self) {
+        let (rows, cols) = self.world_size;
+        let doors = self
+            .world_map
+            .as_ref()
+            .map_or(&[][..], |world_map| &world_map.doors[..]);
+        let mut unlocked = false;
+        for player in self.players.iter().filter(|player| player.alive) {
+            let next = player
+                .snake
+                .next_position(&player.direction, rows as i32, cols as i32);
+            for (door, shut) in doors.iter().zip(self.puzzle.doors.iter_mut()) {
+                if *shut && door.cells.contains(&next) && player.keys.contains(&door.color) {
+                    *shut = false;
+                    unlocked = true;
+                }
+            }
+        }
+        if unlocked {
+            self.refresh_grid();
+        }
+    }
+
+    /// Let players pick up the keys and press the switches their heads have reached
+    ///
+    /// A player keeps every key it picks up. A switch opens or shuts each wall in its group, but
+    /// a wall can't shut on a snake, so it stays open when one is in the way. Enemies neither
+    /// pick up keys nor press switches.
+    fn trigger_puzzle(&mut self) {
+        let world_map = match self.world_map.as_ref() {
+            Some(world_map) => world_map,
+            None => return,
+        };
+        let mut covered = vec![];
+        for player in self.players.iter_mut().filter(|player| player.alive) {
+            let head = *player.snake.position();
+            for (key, lying) in world_map.keys.iter().zip(self.puzzle.keys.iter_mut()) {
+                if *lying && key.position == head {
+                    *lying = false;
+                    if !player.keys.contains(&key.color) {
+                        player.keys.push(key.color);
+                    }
+                }
+            }
+        }
+
+        let pressed: Vec<u32> = world_map
+            .switches
+            .iter()
+            .filter(|switch| {
+                self.players
+                    .iter()
+                    .any(|player| player.alive && *player.snake.position() == switch.position)
+            })
+            .map(|switch| switch.group)
+            .collect();
+        let walls = world_map
+            .switch_walls
+            .iter()
+            .zip(self.puzzle.walls.iter_mut());
+        for (wall, shut) in walls.filter(|(wall, _)| pressed.contains(&wall.group)) {
+            let in_way = self
+                .players
+                .iter()
+                .filter(|player| player.alive)
+                .map(|player| &player.snake)
+                .chain(self.enemies.iter().map(|evil| &evil.snake))
+                .any(|snake| snake.body.iter().any(|p| wall.cells.contains(p)));
+            if !*shut && in_way {
+                continue;
+            }
+            *shut = !*shut;
+            if *shut {
+                covered.extend(wall.cells.iter());
+            }
+        }
+
+        self.bury(&covered);
+    }
+
+    /// Returns the terrain at a position, floor when there's no map
+    fn tile_at(&self, position: Position) -> Tile {
+        self.world_map
+            .as_ref()
+            .map_or(Tile::Floor, |world_map| world_map.tile_at(position))
+    }
+
+    /// Returns the way a conveyor under a snake's head pushes it, if one does
+    fn conveyor_push(&self, snake: &Snake, heading: Direction) -> Option<Direction> {
+        match self.tile_at(*snake.position()) {
+            Tile::Conveyor(push) if push != heading.flip() => Some(push),
+            _ => None,
+        }
+    }
+
+    /// Whether a snake's head is on ice, so it can't be steered
+    fn on_ice(&self, snake: &Snake) -> bool {
+        self.tile_at(*snake.position()) == Tile::Ice
+    }
+
     /// Change player movement direction according to input event
     pub fn handle_input(&mut self, input: Option<types::SnakeEvent>) {
+        self.handle_player_input(0, input)
+    }
+
+    /// Route an input event to the player with the given id
+    ///
+    /// Steering is ignored while the player's head is on ice.
+    pub fn handle_player_input(&mut self, player: usize, input: Option<types::SnakeEvent>) {
         match input {
             Some(SnakeEvent::Input(d)) => {
-                self.direction = d;
+                let sliding = self
+                    .players
+                    .get(player)
+                    .is_some_and(|player| self.on_ice(&player.snake));
+                if let Some(player) = self.players.get_mut(player).filter(|_| !sliding) {
+                    player.direction = d;
+                }
             }
             Some(SnakeEvent::Game(GameEvent::Pause)) => {
                 self.toggle_pause();
@@ -124,27 +990,215 @@ impl Gamestate {
         }
     }
 
+    /// Let computer controlled players pick their next move
+    ///
+    /// Bots go after keys and drills as well as food, and treat doors they hold the key to, and
+    /// bricks while they hold a drill, as open.
+    fn steer_bots(&mut self) {
+        let mut targets = self.scraps.clone();
+        targets.push(*self.food.position());
+        let (keys, doors) = match self.world_map.as_ref() {
+            Some(world_map) => (&world_map.keys[..], &world_map.doors[..]),
+            None => (&[][..], &[][..]),
+        };
+        let lying = keys.iter().zip(self.puzzle.keys.iter());
+        targets.extend(
+            lying
+                .filter(|(_, lying)| **lying)
+                .map(|(key, _)| key.position),
+        );
+        targets.extend(self.drill);
+        let bricks: Vec<Position> = self.world_map.as_ref().map_or(vec![], |world_map| {
+            world_map
+                .tiles
+                .iter()
+                .filter(|(_, tile)| tile.is_destructible())
+                .map(|(position, _)| *position)
+                .collect()
+        });
+        let openable: Vec<Vec<Position>> = self
+            .players
+            .iter()
+            .map(|player| {
+                doors
+                    .iter()
+                    .zip(self.puzzle.doors.iter())
+                    .filter(|(door, shut)| **shut && player.keys.contains(&door.color))
+                    .flat_map(|(door, _)| door.cells.iter().copied())
+                    .chain(bricks.iter().copied().filter(|_| player.drill > 0))
+                    .collect()
+            })
+            .collect();
+        let sliding: Vec<bool> = self
+            .players
+            .iter()
+            .map(|player| self.on_ice(&player.snake))
+            .collect();
+
+        let bots = self.players.iter_mut().zip(sliding).zip(openable);
+        for ((player, sliding), openable) in bots {
+            if !player.alive || player.controller != Controller::Bot || sliding {
+                continue;
+            }
+            let mut grid = Cow::Borrowed(&self.grid);
+            for (row, col) in openable {
+                grid.to_mut()[row as usize][col as usize] = types::BG_COLOR;
+            }
+            player.direction = ai::seek(&grid, &player.snake, player.direction, &targets);
+        }
+    }
+
+    /// Move every enemy after the player has moved
+    ///
+    /// Shadowing enemies grow whenever the player eats, while a thief grows by eating the food
+    /// itself. Enemies slide on ice and ride conveyors like players, but a conveyor only pushes
+    /// an enemy onto a safe cell.
+    fn move_enemies(&mut self, player_ate: bool) {
+        let (rows, cols) = self.world_size;
+        let sliding: Vec<bool> = self
+            .enemies
+            .iter()
+            .map(|evil| self.on_ice(&evil.snake))
+            .collect();
+        let player = &self.players[0];
+        let world = Surroundings {
+            grid: &self.grid,
+            player: *player.snake.position(),
+            player_direction: player.direction,
+            food: *self.food.position(),
+        };
+
+        let mut stolen = false;
+        for (evil, sliding) in self.enemies.iter_mut().zip(sliding) {
+            let direction = match sliding {
+                true => evil.direction,
+                false => evil.steer(&world, &mut self.rng),
+            };
+            let (next, heading) = evil.snake.next_move(&direction, rows as i32, cols as i32);
+            let steals = !evil.shadows_player() && next == world.food;
+            if steals || (player_ate && evil.shadows_player()) {
+                evil.snake.grow(&direction, cols as i32, rows as i32);
+            } else {
+                evil.snake
+                    .update_position(&direction, cols as i32, rows as i32);
+            }
+            evil.direction = heading;
+            stolen |= steals;
+        }
+
+        for i in 0..self.enemies.len() {
+            let evil = &self.enemies[i];
+            let push = match self.conveyor_push(&evil.snake, evil.direction) {
+                Some(push) => push,
+                None => continue,
+            };
+            let ((row, col), heading) = evil.snake.next_move(&push, rows as i32, cols as i32);
+            if ai::is_safe(self.grid[row as usize][col as usize], heading) {
+                self.enemies[i]
+                    .snake
+                    .update_position(&push, cols as i32, rows as i32);
+            }
+        }
+
+        if stolen {
+            self.fresh_food();
+        }
+    }
+
+    /// Convert the outermost ring of the arena into walls, eliminating anything caught in it
+    fn shrink_arena(&mut self) {
+        let (rows, cols) = self.world_size;
+        if std::cmp::min(rows, cols) < MIN_ARENA + 2 * (self.arena_ring + 1) {
+            return;
+        }
+        self.arena_ring += 1;
+
+        for i in 0..self.players.len() {
+            let crushed = self.players[i]
+                .snake
+                .body
+                .iter()
+                .any(|position| !self.in_arena(*position));
+            if self.players[i].alive && crushed {
+                self.handle_collision(i, &Some(SnakeEvent::Death));
+            }
+        }
+
+        let scraps = std::mem::take(&mut self.scraps);
+        self.scraps = scraps
+            .into_iter()
+            .filter(|position| self.in_arena(*position))
+            .collect();
+        if !self.in_arena(*self.food.position()) {
+            self.fresh_food();
+        }
+    }
+
     /// Updates the world state
     pub fn simulate(&mut self, _dt: usize) -> Option<types::SnakeEvent> {
         if self.paused {
             return None;
         }
+        self.tick += 1;
+        let mut died = self.move_obstacles();
+        self.steer_bots();
+        self.unlock_doors();
 
-        let evt = collision::collision_check(&self.grid, &self.player, &self.direction);
-        self.handle_collision(&evt);
-        match evt {
-            Some(SnakeEvent::Death) => {
-                return evt;
-            }
-            Some(SnakeEvent::Food) => {
-                if self.game_mode == GameMode::Tal {
-                    self.game_speed = std::cmp::max(1, self.game_speed - 2);
-                }
-            }
-            _ => {}
+        // every snake reacts to the same board, so resolve all moves before moving anyone
+        let alive: Vec<_> = (0..self.players.len())
+            .filter(|i| self.players[*i].alive)
+            .collect();
+        let moves: Vec<_> = alive
+            .iter()
+            .map(|i| (&self.players[*i].snake, self.players[*i].direction))
+            .collect();
+        let evts = collision::resolve_moves(&self.grid, &moves);
+        let evts: Vec<_> = alive
+            .iter()
+            .zip(evts)
+            .map(|(i, evt)| self.dig(*i, self.players[*i].direction, evt))
+            .collect();
+
+        for (i, evt) in alive.iter().zip(evts.iter()) {
+            died |= *evt == Some(SnakeEvent::Death);
+            self.handle_collision(*i, evt);
         }
+        let pushed = self.convey();
+        died |= pushed
+            .iter()
+            .any(|(_, evt)| *evt == Some(SnakeEvent::Death));
+        self.trigger_puzzle();
+        self.use_drills();
 
-        None
+        let player_evt = match alive.first() {
+            Some(0) => evts.first(),
+            _ => None,
+        };
+        let player_ate = player_evt == Some(&Some(SnakeEvent::Food))
+            || pushed.contains(&(0, Some(SnakeEvent::Food)));
+        if player_evt.is_some() && self.players[0].alive {
+            self.move_enemies(player_ate);
+        }
+        self.spawn_enemies();
+        if self.rules.speed == Speed::Accelerating && player_ate {
+            self.game_speed = std::cmp::max(1, self.game_speed - 2);
+        }
+
+        if self.rules.goal != Goal::LastStanding {
+            return match died {
+                true => Some(SnakeEvent::Death),
+                false => None,
+            };
+        }
+
+        if self.tick.is_multiple_of(SHRINK_INTERVAL) {
+            self.shrink_arena();
+        }
+        let mut survivors = (0..self.players.len()).filter(|i| self.players[*i].alive);
+        match (survivors.next(), survivors.next()) {
+            (winner, None) => Some(SnakeEvent::Game(GameEvent::Finish(winner))),
+            _ => None,
+        }
     }
 
     /// Returns the simulation speed
@@ -160,20 +1214,861 @@ impl Gamestate {
     /// Creates a width x height vector of `Cells`
     pub fn grid_init(&self) -> Grid {
         let (height, width) = self.world_size;
-        let mut grid_vector = vec![vec![types::BG_COLOR; width as usize]; height as usize];
+        let mut grid_vector = match self.world_map.as_ref() {
+            Some(world_map) => {
+                world_map.render_with(height, width, &self.obstacle_cells(), &self.puzzle)
+            }
+            None => vec![vec![types::BG_COLOR; width as usize]; height as usize],
+        };
 
-        if self.game_mode == GameMode::Map {
-            let world_map = self.world_map.as_ref().unwrap();
-            for (row, col) in world_map.walls.iter() {
-                grid_vector[*row as usize][*col as usize] = world_map.color;
+        for (row, cells) in (0..).zip(grid_vector.iter_mut()) {
+            for (col, cell) in (0..).zip(cells.iter_mut()) {
+                if !self.in_arena((row, col)) {
+                    *cell = types::WALL_COLOR;
+                }
             }
         }
 
         grid_vector
     }
 
+    /// Rebuild `grid` from the current positions of every game object
+    pub fn refresh_grid(&mut self) {
+        let mut grid = self.grid_init();
+        for player in self.players.iter().filter(|player| player.alive) {
+            grid = player.snake.render(grid);
+        }
+        for evil in self.enemies.iter() {
+            grid = evil.snake.render(grid);
+        }
+        grid = self.food.render(grid);
+        grid = render_scraps(grid, &self.scraps);
+        if let Some((row, col)) = self.drill {
+            grid[row as usize][col as usize] = types::DRILL_COLOR;
+        }
+
+        self.grid = grid;
+    }
+
+    /// Returns a digest of the simulation state, used to detect diverging simulations
+    ///
+    /// Peers compare digests, so the digest is defined byte for byte rather than left to the
+    /// standard library's hashers, which may change between Rust releases.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(self.tick);
+        hash.write(self.game_speed);
+        hash.write(self.paused as u64);
+        hash.write(self.arena_ring as u64);
+        hash.write(self.obstacles.len() as u64);
+        for obstacle in self.obstacles.iter() {
+            hash.write(obstacle.step as u64);
+            hash.write(obstacle.solid as u64);
+        }
+        for flags in [&self.puzzle.keys, &self.puzzle.doors, &self.puzzle.walls].iter() {
+            hash.write(flags.len() as u64);
+            for flag in flags.iter() {
+                hash.write(*flag as u64);
+            }
+        }
+        hash.write_positions(self.drill.iter());
+        if let Some(world_map) = self.world_map.as_ref() {
+            hash.write(world_map.tiles.len() as u64);
+            for (position, tile) in world_map.tiles.iter() {
+                hash.write_position(position);
+                hash.write(tile.symbol() as u64);
+            }
+        }
+        for player in self.players.iter() {
+            hash.write(player.keys.len() as u64);
+            for key in player.keys.iter() {
+                hash.write(*key as u64);
+            }
+            hash.write(player.drill as u64);
+            hash.write(player.alive as u64);
+            hash.write(player.score as u64);
+            hash.write(player.direction as u64);
+            hash.write_positions(player.snake.body.iter());
+        }
+        for evil in self.enemies.iter() {
+            hash.write_positions(evil.snake.body.iter());
+            hash.write(evil.direction as u64);
+        }
+        hash.write_positions(self.food.body.iter());
+        hash.write_positions(self.scraps.iter());
+
+        hash.0
+    }
+
     /// Toggle the pause state
     fn toggle_pause(&mut self) {
         self.paused = !self.paused
     }
 }
+
+/// 64-bit FNV-1a, fed every number as its 8 little-endian bytes
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, n: u64) {
+        for byte in n.to_le_bytes().iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_position(&mut self, (row, col): &Position) {
+        self.write(*row as u64);
+        self.write(*col as u64);
+    }
+
+    /// Write a list of positions, led by its length
+    fn write_positions<'a, I: ExactSizeIterator<Item = &'a Position>>(&mut self, positions: I) {
+        self.write(positions.len() as u64);
+        for position in positions {
+            self.write_position(position);
+        }
+    }
+}
+
+/// Update grid to display leftover food
+fn render_scraps(mut grid: Grid, scraps: &[Position]) -> Grid {
+    for (row, col) in scraps.iter() {
+        grid[*row as usize][*col as usize] = FOOD_COLOR;
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        Behavior, Difficulty, Door, GameMode, Key, Obstacle, Spawn, Switch, SwitchWall, Tile,
+        Topology, Zone,
+    };
+
+    fn run(state: &mut Gamestate, ticks: usize) -> Option<SnakeEvent> {
+        let mut evt = None;
+        for _ in 0..ticks {
+            state.refresh_grid();
+            evt = state.simulate(1);
+        }
+        evt
+    }
+
+    #[test]
+    fn seeded_states_match() {
+        let mut a = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(7);
+        let mut b = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(7);
+        // steer the player through the food so the rng is exercised
+        for state in [&mut a, &mut b].iter_mut() {
+            state.food = Food::new(0, 5, Some(FOOD_COLOR));
+            state.handle_input(Some(SnakeEvent::Input(Direction::Right)));
+        }
+        run(&mut a, 20);
+        run(&mut b, 20);
+        assert_eq!(a.score(), 1);
+        assert_eq!(a.food.position(), b.food.position());
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn hash_detects_divergence() {
+        let mut a = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(7);
+        let mut b = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(7);
+        b.handle_input(Some(SnakeEvent::Input(Direction::Right)));
+        run(&mut a, 1);
+        run(&mut b, 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn hash_is_pinned() {
+        // peers on different builds must agree, so the digest of a known state never changes
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(7);
+        state.food = Food::new(0, 5, Some(FOOD_COLOR));
+        state.enemies.clear();
+        assert_eq!(state.state_hash(), 0x32ad_b7f9_992b_2faa);
+    }
+
+    #[test]
+    fn bounded_edges_kill() {
+        let rules = Ruleset {
+            topology: Topology::Bounded,
+            ..GameMode::Tal.into()
+        };
+        let mut state = Gamestate::new(36, 36, rules, None).with_seed(1);
+        let id = state.add_player(Controller::Human);
+        assert_eq!(state.players[id].snake.topology, Topology::Bounded);
+        assert!(state
+            .enemies
+            .iter()
+            .all(|evil| evil.snake.topology == Topology::Bounded));
+
+        run(&mut state, 3);
+        assert!(state.players[0].alive);
+        state.handle_input(Some(SnakeEvent::Input(Direction::Left)));
+        assert_eq!(run(&mut state, 1), Some(SnakeEvent::Death));
+    }
+
+    #[test]
+    fn rival_follows_its_input() {
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(1);
+        let id = state.add_player(Controller::Human);
+        state.handle_player_input(id, Some(SnakeEvent::Input(Direction::Right)));
+        run(&mut state, 1);
+        assert_eq!(*state.players[id].snake.position(), (35, 1));
+    }
+
+    #[test]
+    fn removed_rival_leaves_board() {
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(1);
+        let id = state.add_player(Controller::Human);
+        state.remove_player(id);
+        state.refresh_grid();
+        assert_eq!(state.grid[35][0], types::BG_COLOR);
+    }
+
+    #[test]
+    fn royale_has_no_evil_twin() {
+        let state = Gamestate::new(36, 36, GameMode::Royale, None);
+        assert!(state.enemies.is_empty());
+    }
+
+    #[test]
+    fn mirror_twin_opposes_player() {
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(1);
+        state.handle_input(Some(SnakeEvent::Input(Direction::Right)));
+        run(&mut state, 2);
+        assert_eq!(*state.enemies[0].snake.position(), (27, 25));
+    }
+
+    #[test]
+    fn mirror_twin_grows_with_player() {
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None).with_seed(1);
+        state.food = Food::new(1, 0, Some(FOOD_COLOR));
+        run(&mut state, 1);
+        assert_eq!(state.enemies[0].snake.body.len(), 2);
+    }
+
+    #[test]
+    fn chaser_closes_in() {
+        let config = EvilConfig {
+            behavior: Behavior::Chaser,
+            difficulty: Difficulty::Hard,
+            ..EvilConfig::default()
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None)
+            .with_seed(1)
+            .with_evil(config);
+        state.enemies[0].snake = Snake::new(18, 18, None);
+        // both snakes close the gap each tick
+        run(&mut state, 4);
+        let evil = *state.enemies[0].snake.position();
+        let player = *state.player().snake.position();
+        assert_eq!(ai_distance(evil, player), 36 - 8);
+    }
+
+    #[test]
+    fn thief_steals_food() {
+        let config = EvilConfig {
+            behavior: Behavior::Thief,
+            difficulty: Difficulty::Hard,
+            ..EvilConfig::default()
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None)
+            .with_seed(1)
+            .with_evil(config);
+        state.handle_input(Some(SnakeEvent::Input(Direction::Right)));
+        state.food = Food::new(25, 27, Some(FOOD_COLOR));
+        run(&mut state, 2);
+        let evil = &state.enemies[0];
+        assert_eq!(evil.snake.body.len(), 2);
+        assert_ne!(*state.food.position(), (25, 27));
+        assert_eq!(state.score(), 0);
+    }
+
+    #[test]
+    fn enemy_spawns_on_any_board() {
+        let state = Gamestate::new(20, 30, GameMode::Normal, None);
+        assert_eq!(state.enemies.len(), 1);
+        assert_eq!(*state.enemies[0].snake.position(), (15, 22));
+    }
+
+    #[test]
+    fn wraps_and_grows_on_wide_board() {
+        let mut state = Gamestate::new(10, 40, GameMode::Normal, None).with_seed(1);
+        state.food = Food::new(0, 39, Some(FOOD_COLOR));
+        state.handle_input(Some(SnakeEvent::Input(Direction::Left)));
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (0, 39));
+        assert_eq!(state.player().snake.body.len(), 2);
+
+        state.handle_input(Some(SnakeEvent::Input(Direction::Up)));
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (9, 39));
+        assert_eq!(state.grid.len(), 10);
+        assert_eq!(state.grid[0].len(), 40);
+    }
+
+    #[test]
+    fn map_sets_board_size() {
+        let world_map = WorldMap {
+            size: Some((12, 30)),
+            ..WorldMap::new(vec![(11, 29)], types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map));
+        state.refresh_grid();
+        assert_eq!(state.world_size, (12, 30));
+        assert_eq!(state.grid[11][29], types::WALL_COLOR);
+        assert_eq!(*state.enemies[0].snake.position(), (9, 22));
+    }
+
+    #[test]
+    fn enemies_spawn_as_score_rises() {
+        let config = EvilConfig {
+            count: 3,
+            ..EvilConfig::default()
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Normal, None)
+            .with_seed(1)
+            .with_evil(config);
+        assert_eq!(state.enemies.len(), 1);
+
+        state.players[0].score = 5;
+        run(&mut state, 1);
+        assert_eq!(state.enemies.len(), 2);
+        assert_ne!(
+            state.enemies[0].snake.position(),
+            state.enemies[1].snake.position()
+        );
+        assert_eq!(state.enemies[1].snake.cell, types::ENEMY_COLORS[1]);
+    }
+
+    #[test]
+    fn enemies_avoid_walls() {
+        let world_map = WorldMap::new(vec![(27, 27), (27, 26), (26, 27)], types::WALL_COLOR);
+        let state = Gamestate::new(36, 36, GameMode::Map, Some(world_map));
+        let position = *state.enemies[0].snake.position();
+        assert!(!state.world_map().unwrap().walls.contains(&position));
+        assert_eq!(position, (27, 28));
+    }
+
+    #[test]
+    fn extra_players_avoid_walls() {
+        // two map spawns, and walls on every default spawn point
+        let walls = (0..ROYALE_SNAKES).map(|i| spawn_point(i, 36, 36)).collect();
+        let spawn = |position| Spawn {
+            position,
+            direction: Direction::Down,
+        };
+        let world_map = WorldMap {
+            player_spawns: vec![spawn((4, 4)), spawn((31, 31))],
+            ..WorldMap::new(walls, types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map));
+        state.fill_with_bots(ROYALE_SNAKES);
+
+        let walls = &state.world_map().unwrap().walls;
+        assert_eq!(state.players.len(), ROYALE_SNAKES);
+        assert!(state
+            .players
+            .iter()
+            .all(|player| !walls.contains(player.snake.position())));
+    }
+
+    #[test]
+    fn last_standing_on_a_labyrinth() {
+        let rules = Ruleset {
+            labyrinth: true,
+            ..GameMode::Royale.into()
+        };
+        let world_map = crate::map::load_file(std::path::Path::new("./resource/map_02.snek"));
+        let mut state = Gamestate::new(36, 36, rules, world_map.ok()).with_seed(1);
+        state.fill_with_bots(ROYALE_SNAKES);
+
+        let world_map = state.world_map().unwrap();
+        let heads: std::collections::HashSet<_> =
+            state.players.iter().map(|p| *p.snake.position()).collect();
+        assert_eq!(heads.len(), ROYALE_SNAKES);
+        assert!(heads.iter().all(|head| !world_map.walls.contains(head)));
+        assert!(state.enemies.is_empty());
+        run(&mut state, 1);
+        assert!(state.players.iter().all(|player| player.alive));
+    }
+
+    #[test]
+    fn map_places_spawns() {
+        let world_map = WorldMap {
+            player_spawns: vec![
+                Spawn {
+                    position: (5, 6),
+                    direction: Direction::Right,
+                },
+                Spawn {
+                    position: (30, 6),
+                    direction: Direction::Up,
+                },
+            ],
+            enemy_spawns: vec![Spawn {
+                position: (20, 20),
+                direction: Direction::Left,
+            }],
+            ..WorldMap::new(vec![], types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map));
+        state.add_player(Controller::Bot);
+
+        assert_eq!(*state.players[0].snake.position(), (5, 6));
+        assert_eq!(state.players[0].direction, Direction::Right);
+        assert_eq!(*state.players[1].snake.position(), (30, 6));
+        assert_eq!(state.players[1].direction, Direction::Up);
+        assert_eq!(*state.enemies[0].snake.position(), (20, 20));
+        assert_eq!(state.enemies[0].direction, Direction::Left);
+    }
+
+    /// A labyrinth with the player at 0:1 heading right, toward a portal at 0:3
+    fn portal_map(walls: Vec<Position>, exit: Option<Direction>) -> WorldMap {
+        let mut portal = Portal::new((0, 3), (10, 10));
+        portal.ends[1].exit = exit;
+        WorldMap {
+            player_spawns: vec![Spawn {
+                position: (0, 1),
+                direction: Direction::Right,
+            }],
+            portals: vec![portal],
+            ..WorldMap::new(walls, types::WALL_COLOR)
+        }
+    }
+
+    #[test]
+    fn portals_carry_snakes() {
+        let world_map = portal_map(vec![], Some(Direction::Down));
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        state.food = Food::new(0, 2, Some(FOOD_COLOR));
+        run(&mut state, 2);
+        assert_eq!(*state.player().snake.position(), (11, 10));
+        assert_eq!(state.player().direction, Direction::Down);
+        assert_eq!(state.player().snake.body[1], (0, 2));
+
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (12, 10));
+        // portal cells show their pairing and stay clear of the snake
+        assert_eq!(state.grid[0][3], state.grid[10][10]);
+        assert_eq!(state.grid[0][3], types::PORTAL_COLORS[0]);
+    }
+
+    #[test]
+    fn portal_exit_is_checked() {
+        let world_map = portal_map(vec![(10, 11)], None);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        assert_eq!(run(&mut state, 2), Some(SnakeEvent::Death));
+        assert!(!state.player().alive);
+    }
+
+    /// A labyrinth with the player at 0:1 heading right, onto the given terrain
+    fn terrain_map(terrain: &[(Position, Tile)]) -> WorldMap {
+        let mut world_map = WorldMap {
+            player_spawns: vec![Spawn {
+                position: (0, 1),
+                direction: Direction::Right,
+            }],
+            ..WorldMap::new(vec![], types::WALL_COLOR)
+        };
+        for (position, tile) in terrain.iter() {
+            world_map.place(*position, *tile);
+        }
+        world_map
+    }
+
+    #[test]
+    fn ice_ignores_steering() {
+        let world_map = terrain_map(&[((0, 2), Tile::Ice), ((0, 3), Tile::Ice)]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        run(&mut state, 1);
+        for _ in 0..2 {
+            state.handle_input(Some(SnakeEvent::Input(Direction::Down)));
+            run(&mut state, 1);
+        }
+        assert_eq!(*state.player().snake.position(), (0, 4));
+
+        // off the ice the snake answers to its controls again
+        state.handle_input(Some(SnakeEvent::Input(Direction::Down)));
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (1, 4));
+    }
+
+    #[test]
+    fn conveyors_push_snakes() {
+        let world_map = terrain_map(&[
+            ((0, 2), Tile::Conveyor(Direction::Down)),
+            ((1, 4), Tile::Conveyor(Direction::Left)),
+        ]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        state.food = Food::new(1, 2, Some(FOOD_COLOR));
+        run(&mut state, 1);
+        // pushed onto the food, still heading right
+        assert_eq!(*state.player().snake.position(), (1, 2));
+        assert_eq!(state.player().direction, Direction::Right);
+        assert_eq!(state.score(), 1);
+
+        // a conveyor running against the snake doesn't push it
+        run(&mut state, 2);
+        assert_eq!(*state.player().snake.position(), (1, 4));
+        // the conveyor shows again once the snake has moved off it
+        assert_eq!(state.grid[0][2], Tile::Conveyor(Direction::Down).color());
+    }
+
+    #[test]
+    fn gates_open_one_way() {
+        let world_map = terrain_map(&[((0, 2), Tile::Gate(Direction::Right))]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        run(&mut state, 2);
+        assert!(state.player().alive);
+
+        let world_map = terrain_map(&[((0, 2), Tile::Gate(Direction::Left))]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        assert_eq!(run(&mut state, 1), Some(SnakeEvent::Death));
+    }
+
+    #[test]
+    fn obstacles_wait_for_snakes() {
+        let mut world_map = terrain_map(&[]);
+        world_map.obstacles = vec![Obstacle::mover(vec![(1, 2), (0, 2)], 2)];
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        run(&mut state, 2);
+        // due to step onto the snake's head, the mover stays put
+        assert_eq!(state.grid[1][2], types::OBSTACLE_COLOR);
+        run(&mut state, 2);
+        assert_eq!(state.grid[0][2], types::OBSTACLE_COLOR);
+        assert_eq!(state.grid[1][2], types::BG_COLOR);
+        assert!(state.player().alive);
+    }
+
+    #[test]
+    fn crushers_eliminate_snakes() {
+        let toggle = Obstacle::toggle(vec![(0, 3)], 1, 1).with_offset(1);
+        for crush in [false, true] {
+            let mut world_map = terrain_map(&[]);
+            world_map.obstacles = match crush {
+                true => vec![toggle.clone().crushing()],
+                false => vec![toggle.clone()],
+            };
+            let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+            // the snake slips through while the cell is open, and is caught as it closes
+            assert_eq!(run(&mut state, 2), None);
+            let evt = run(&mut state, 1);
+            assert_eq!(state.player().alive, !crush);
+            assert_eq!(evt.is_some(), crush);
+        }
+    }
+
+    #[test]
+    fn keys_open_doors() {
+        let mut world_map = terrain_map(&[]);
+        world_map.keys = vec![Key {
+            position: (0, 3),
+            color: KeyColor::Red,
+        }];
+        world_map.doors = vec![
+            Door {
+                cells: vec![(0, 5)],
+                color: KeyColor::Red,
+            },
+            Door {
+                cells: vec![(0, 7)],
+                color: KeyColor::Blue,
+            },
+        ];
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        run(&mut state, 2);
+        assert_eq!(state.player().keys, vec![KeyColor::Red]);
+        run(&mut state, 1);
+        assert_eq!(state.grid[0][3], types::BG_COLOR);
+        assert_eq!(state.grid[0][5], KeyColor::Red.door_cell());
+
+        // the red key opens the red door for good, but not the blue one
+        assert_eq!(run(&mut state, 2), None);
+        assert_eq!(state.grid[0][5], types::BG_COLOR);
+        assert_eq!(run(&mut state, 1), Some(SnakeEvent::Death));
+    }
+
+    #[test]
+    fn switches_move_walls() {
+        let mut world_map = terrain_map(&[]);
+        world_map.switches = vec![Switch {
+            position: (0, 2),
+            group: 1,
+        }];
+        world_map.switch_walls = [((0, 4), false), ((1, 2), true), ((0, 1), true)]
+            .iter()
+            .map(|(cell, open)| SwitchWall {
+                cells: vec![*cell],
+                group: 1,
+                open: *open,
+            })
+            .collect();
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        state.players[0].snake.body.push_back((0, 0));
+        state.refresh_grid();
+        assert_eq!(state.grid[0][4], types::SWITCH_WALL_COLOR);
+
+        // the wall ahead opens and the one beside the switch shuts, but not on the snake's tail
+        run(&mut state, 1);
+        assert_eq!(state.grid[0][4], types::BG_COLOR);
+        assert_eq!(state.grid[1][2], types::SWITCH_WALL_COLOR);
+        assert_eq!(state.grid[0][1], types::SNAKE_COLOR);
+        assert_eq!(run(&mut state, 3), None);
+        assert_eq!(*state.player().snake.position(), (0, 5));
+    }
+
+    #[test]
+    fn drills_break_bricks() {
+        let world_map = terrain_map(&[((0, 3), Tile::Brick(2))]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        state.drill = Some((0, 2));
+        run(&mut state, 1);
+        assert_eq!(state.player().drill, DRILL_TICKS);
+        assert_eq!(state.drill, None);
+
+        // each ram chips the brick while the snake waits, until it breaks through
+        assert_eq!(run(&mut state, 1), None);
+        assert_eq!(*state.player().snake.position(), (0, 2));
+        assert_eq!(state.world_map().unwrap().tile_at((0, 3)), Tile::Brick(1));
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (0, 3));
+        assert_eq!(state.world_map().unwrap().tile_at((0, 3)), Tile::Floor);
+        assert!(!state.world_map().unwrap().walls.contains(&(0, 3)));
+
+        // without a drill a brick is just a wall
+        let world_map = terrain_map(&[((0, 2), Tile::Brick(1))]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        assert_eq!(run(&mut state, 1), Some(SnakeEvent::Death));
+    }
+
+    #[test]
+    fn drills_appear_among_bricks() {
+        let world_map = terrain_map(&[((9, 9), Tile::Brick(1))]);
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        run(&mut state, DRILL_INTERVAL as usize - 1);
+        assert_eq!(state.drill, None);
+        run(&mut state, 1);
+        assert!(state.drill.is_some());
+
+        // no drills on a map without bricks
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(terrain_map(&[])));
+        run(&mut state, DRILL_INTERVAL as usize);
+        assert_eq!(state.drill, None);
+    }
+
+    #[test]
+    fn food_grows_in_zones() {
+        let zone = Zone {
+            position: (10, 10),
+            rows: 2,
+            cols: 3,
+        };
+        let world_map = WorldMap {
+            food_zones: vec![zone],
+            ..WorldMap::new(vec![], types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(3);
+        state.refresh_grid();
+        for _ in 0..20 {
+            state.fresh_food();
+            assert!(zone.contains(*state.food.position()));
+        }
+    }
+
+    #[test]
+    fn food_grows_on_spots() {
+        let world_map = WorldMap {
+            food_spots: vec![(3, 3), (30, 30)],
+            food_zones: vec![Zone {
+                position: (10, 10),
+                rows: 2,
+                cols: 2,
+            }],
+            ..WorldMap::new(vec![], types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(3);
+        state.refresh_grid();
+        for _ in 0..20 {
+            state.fresh_food();
+            assert!([(3, 3), (30, 30)].contains(state.food.position()));
+        }
+    }
+
+    #[test]
+    fn replaced_map_moves_things_out_of_walls() {
+        let world_map = WorldMap {
+            player_spawns: vec![Spawn {
+                position: (5, 5),
+                direction: Direction::Right,
+            }],
+            ..WorldMap::new(vec![], types::WALL_COLOR)
+        };
+        let mut state = Gamestate::new(36, 36, GameMode::Map, Some(world_map)).with_seed(1);
+        state.players[0].snake.body.push_back((5, 4));
+        state.food = Food::new(20, 20, Some(FOOD_COLOR));
+        state.scraps.push((30, 30));
+
+        let walls = vec![(5, 4), (5, 5), (20, 20), (30, 30)];
+        state.replace_map(WorldMap::new(walls.clone(), types::WALL_COLOR));
+
+        let body: Vec<Position> = state.players[0].snake.body.iter().copied().collect();
+        assert_eq!(body.len(), 2);
+        assert!(body.iter().all(|cell| !walls.contains(cell)), "{:?}", body);
+        assert!(ai_distance(body[0], (5, 5)) <= 1);
+        assert!(!walls.contains(state.food.position()));
+        assert!(state.scraps.is_empty());
+        assert_eq!(state.world_map().unwrap().walls, walls);
+    }
+
+    #[test]
+    fn replaced_map_keeps_clear_snakes_still() {
+        let mut state = Gamestate::new(36, 36, GameMode::Map, None);
+        let before: Vec<Position> = state.players[0].snake.body.iter().copied().collect();
+        state.replace_map(WorldMap::new(vec![(9, 9)], types::WALL_COLOR));
+        let after: Vec<Position> = state.players[0].snake.body.iter().copied().collect();
+        assert_eq!(before, after);
+
+        let mut normal = Gamestate::new(36, 36, GameMode::Normal, None);
+        normal.replace_map(WorldMap::new(vec![(9, 9)], types::WALL_COLOR));
+        assert!(normal.world_map().is_none());
+    }
+
+    #[test]
+    fn tal_rules_play_on_labyrinth() {
+        let rules = Ruleset {
+            labyrinth: true,
+            ..GameMode::Tal.into()
+        };
+        let world_map = WorldMap::new(vec![(9, 9)], types::WALL_COLOR);
+        let mut state = Gamestate::new(36, 36, rules, Some(world_map)).with_seed(1);
+        assert!(state.world_map().is_some());
+
+        // over the top edge of the projective plane, onto the food in the far corner
+        state.food = Food::new(35, 35, Some(FOOD_COLOR));
+        state.handle_input(Some(SnakeEvent::Input(Direction::Up)));
+        run(&mut state, 1);
+        assert_eq!(*state.player().snake.position(), (35, 35));
+        assert_eq!(state.score(), 1);
+        assert_eq!(state.speed(), 198);
+        assert_eq!(state.grid[9][9], types::WALL_COLOR);
+    }
+
+    #[test]
+    fn map_tiles_are_painted() {
+        let world_map = WorldMap {
+            tiles: vec![((4, 4), Tile::Rock)],
+            ..WorldMap::new(vec![(4, 4), (40, 40)], types::WALL_COLOR)
+        };
+        let state = Gamestate::new(36, 36, GameMode::Map, Some(world_map));
+        assert_eq!(state.grid_init()[4][4], types::ROCK_COLOR);
+    }
+
+    #[test]
+    fn enemies_keep_clear_of_player() {
+        let spawn = EnemySpawn {
+            position: Some((1, 1)),
+            ..EnemySpawn::new(Behavior::Chaser, Difficulty::Normal)
+        };
+        let state = Gamestate::new(36, 36, GameMode::Normal, None).with_enemies(vec![spawn]);
+        let position = *state.enemies[0].snake.position();
+        assert!(Topology::Torus.distance(&position, &(0, 0), 36, 36) > SPAWN_CLEARANCE);
+    }
+
+    #[test]
+    fn enemies_wait_for_room() {
+        let spawn = EnemySpawn::new(Behavior::Chaser, Difficulty::Normal);
+        // a board too small to keep clear of the player
+        let state = Gamestate::new(3, 3, GameMode::Normal, None).with_enemies(vec![spawn]);
+        assert!(state.enemies.is_empty());
+    }
+
+    /// Manhattan distance on a 36x36 board that wraps at its edges
+    fn ai_distance(a: Position, b: Position) -> u32 {
+        let dy = (a.0 as i32 - b.0 as i32).unsigned_abs();
+        let dx = (a.1 as i32 - b.1 as i32).unsigned_abs();
+        std::cmp::min(dy, 36 - dy) + std::cmp::min(dx, 36 - dx)
+    }
+
+    #[test]
+    fn dead_snakes_become_food() {
+        let mut state = Gamestate::new(36, 36, GameMode::Royale, None).with_seed(1);
+        let id = state.add_player(Controller::Human);
+        state.refresh_grid();
+        state.handle_collision(id, &Some(SnakeEvent::Death));
+        assert_eq!(state.scraps, vec![(35, 0)]);
+
+        state.refresh_grid();
+        assert_eq!(state.grid[35][0], FOOD_COLOR);
+    }
+
+    #[test]
+    fn eating_scraps_keeps_food() {
+        let mut state = Gamestate::new(36, 36, GameMode::Royale, None).with_seed(1);
+        state.add_player(Controller::Human);
+        state.scraps.push((1, 0));
+        run(&mut state, 1);
+        assert_eq!(state.score(), 1);
+        assert!(state.scraps.is_empty());
+        assert_eq!(*state.food.position(), (18, 18));
+    }
+
+    #[test]
+    fn arena_shrinks() {
+        let mut state = Gamestate::new(36, 36, GameMode::Royale, None).with_seed(1);
+        state.refresh_grid();
+        state.shrink_arena();
+        state.refresh_grid();
+        assert_eq!(state.grid[0][10], types::WALL_COLOR);
+        assert_eq!(state.grid[35][10], types::WALL_COLOR);
+        assert_eq!(state.grid[1][1], types::BG_COLOR);
+        // the player was standing on the border
+        assert!(!state.player().alive);
+    }
+
+    #[test]
+    fn arena_stops_shrinking() {
+        let mut state = Gamestate::new(20, 20, GameMode::Royale, None).with_seed(1);
+        state.refresh_grid();
+        for _ in 0..20 {
+            state.shrink_arena();
+        }
+        assert_eq!(state.arena_ring, (20 - MIN_ARENA) / 2);
+    }
+
+    #[test]
+    fn last_survivor_wins() {
+        let mut state = Gamestate::new(36, 36, GameMode::Royale, None).with_seed(1);
+        let id = state.add_player(Controller::Human);
+        state.remove_player(0);
+        let expected = Some(SnakeEvent::Game(GameEvent::Finish(Some(id))));
+        assert_eq!(run(&mut state, 1), expected);
+    }
+
+    #[test]
+    fn bots_play_royale() {
+        let mut state = Gamestate::new(36, 36, GameMode::Royale, None).with_seed(3);
+        state.players[0].controller = Controller::Bot;
+        state.fill_with_bots(ROYALE_SNAKES);
+        assert_eq!(state.players.len(), ROYALE_SNAKES);
+
+        let mut evt = None;
+        for _ in 0..5000 {
+            state.refresh_grid();
+            evt = state.simulate(1);
+            if evt.is_some() {
+                break;
+            }
+        }
+        assert!(matches!(evt, Some(SnakeEvent::Game(GameEvent::Finish(_)))));
+    }
+}